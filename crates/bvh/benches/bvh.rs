@@ -10,13 +10,17 @@ fn main() {
     divan::main();
 }
 
-use bvh_region::{create_random_elements_1, random_aabb, Bvh, Heuristic, TrivialHeuristic};
+use bvh_region::{
+    aabb::Aabb, create_clustered_elements, create_random_elements_1, random_aabb, BroadPhase, Bvh,
+    Heuristic, SahHeuristic, TrivialHeuristic,
+};
+use glam::Vec3;
 
 const ENTITY_COUNTS: &[usize] = &[100, 1_000, 10_000];
 
 #[divan::bench(
     args = ENTITY_COUNTS,
-    types = [TrivialHeuristic],
+    types = [TrivialHeuristic, SahHeuristic],
 )]
 fn build<H: Heuristic>(b: Bencher, count: usize) {
     let elements = create_random_elements_1(count, 100.0);
@@ -26,7 +30,7 @@ fn build<H: Heuristic>(b: Bencher, count: usize) {
 
 #[divan::bench(
     args = ENTITY_COUNTS,
-    types = [TrivialHeuristic],
+    types = [TrivialHeuristic, SahHeuristic],
 )]
 fn query<T: Heuristic>(b: Bencher, count: usize) {
     let elements = create_random_elements_1(count, 100.0);
@@ -66,6 +70,31 @@ fn query_par_sparse<T: Heuristic>(b: Bencher, count: usize) {
     });
 }
 
+#[divan::bench(
+    args = ENTITY_COUNTS,
+    types = [TrivialHeuristic],
+)]
+fn par_collide_all<T: Heuristic>(b: Bencher, count: usize) {
+    let elements = create_random_elements_1(100_000, 100.0);
+    let bvh = Bvh::build::<T>(elements);
+
+    let elements = (0..count).map(|_| random_aabb(100.0)).collect::<Vec<_>>();
+
+    b.counter(count)
+        .bench_local(|| bvh.par_collide_all(&elements, usize::MAX));
+}
+
+#[divan::bench(
+    args = ENTITY_COUNTS,
+    types = [TrivialHeuristic],
+)]
+fn par_self_pairs<T: Heuristic>(b: Bencher, count: usize) {
+    let elements = create_clustered_elements(count, count / 100 + 1, 2_000.0);
+    let bvh = Bvh::build::<T>(elements);
+
+    b.counter(count).bench_local(|| bvh.par_self_pairs());
+}
+
 #[divan::bench(
     args = ENTITY_COUNTS,
 types = [TrivialHeuristic],
@@ -87,6 +116,33 @@ fn query_par_compact<T: Heuristic>(b: Bencher, count: usize) {
     });
 }
 
+/// Crowds around players, which is what the zombie swarm looks like.
+#[divan::bench(
+    args = ENTITY_COUNTS,
+    types = [TrivialHeuristic, SahHeuristic],
+)]
+fn query_clustered<T: Heuristic>(b: Bencher, count: usize) {
+    let elements = create_clustered_elements(20_000, 100, 2_000.0);
+    let bvh = Bvh::build::<T>(elements);
+
+    let targets = (0..count)
+        // areas around arbitrary points on the ground, like a zombie looking for nearby players
+        .map(|_| {
+            let feet = Vec3::new(fastrand::f32(), 0.0, fastrand::f32()) * 2_000.0;
+            Aabb::create(feet, 16.0, 4.0)
+        })
+        .collect::<Vec<_>>();
+
+    b.counter(count).bench_local(|| {
+        for target in &targets {
+            bvh.get_collisions(*target, |elem| {
+                black_box(elem);
+                true
+            });
+        }
+    });
+}
+
 const THREAD_COUNTS: &[usize] = &[1, 2, 4, 8];
 
 #[divan::bench(
@@ -110,3 +166,51 @@ fn build_1m_rayon<T: Heuristic>(b: Bencher, count: usize) {
         });
     });
 }
+
+#[divan::bench(
+    args = ENTITY_COUNTS,
+    types = [TrivialHeuristic],
+)]
+fn raycast<T: Heuristic>(b: Bencher, count: usize) {
+    let elements = create_random_elements_1(100_000, 100.0);
+    let bvh = Bvh::build::<T>(elements);
+
+    let rays = (0..count)
+        .map(|_| {
+            let origin = Vec3::from_array(std::array::from_fn(|_| fastrand::f32() * 100.0));
+            let dir = Vec3::from_array(std::array::from_fn(|_| fastrand::f32() - 0.5));
+            (origin, dir)
+        })
+        .collect::<Vec<_>>();
+
+    b.counter(count).bench_local(|| {
+        for &(origin, dir) in &rays {
+            black_box(bvh.raycast(origin, dir, 64.0));
+        }
+    });
+}
+
+const REFIT_COUNT: usize = 100_000;
+
+#[divan::bench(types = [TrivialHeuristic])]
+fn rebuild_moved<T: Heuristic>(b: Bencher) {
+    let elements = create_random_elements_1(REFIT_COUNT, 1_000.0);
+
+    b.counter(REFIT_COUNT).bench_local(|| {
+        let moved = elements
+            .iter()
+            .map(|elem| elem.move_by(Vec3::splat(0.1)))
+            .collect();
+        Bvh::build::<T>(moved)
+    });
+}
+
+#[divan::bench(types = [TrivialHeuristic])]
+fn refit_moved<T: Heuristic>(b: Bencher) {
+    let elements = create_random_elements_1(REFIT_COUNT, 1_000.0);
+    let mut bvh = Bvh::build::<T>(elements);
+
+    b.counter(REFIT_COUNT).bench_local(|| {
+        bvh.refit_or_rebuild::<T>(|elem| *elem = elem.move_by(Vec3::splat(0.1)));
+    });
+}
@@ -1,7 +1,10 @@
 use std::hint::black_box;
 
-use bvh_region::{aabb::Aabb, random_aabb, Bvh, TrivialHeuristic};
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use bvh_region::{
+    aabb::Aabb, create_clustered_elements, create_random_elements_1, random_aabb, BroadPhase, Bvh,
+    SpatialGrid, TrivialHeuristic,
+};
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use tango_bench::{
     benchmark_fn, tango_benchmarks, tango_main, IntoBenchmarks, MeasurementSettings,
 };
@@ -16,6 +19,78 @@ fn build_tree(elements: Vec<Aabb>) -> Bvh<Aabb> {
     Bvh::build::<TrivialHeuristic>(elements)
 }
 
+fn build_broad_phase<B: BroadPhase<Aabb>>(elements: Vec<Aabb>) -> B {
+    let mut broad_phase = B::default();
+    broad_phase.rebuild(elements);
+    broad_phase
+}
+
+fn collide_each<B: BroadPhase<Aabb>>(broad_phase: &B, targets: &[Aabb]) {
+    targets.par_iter().for_each(|target| {
+        broad_phase.get_collisions(*target, |elem| {
+            black_box(elem);
+            true
+        });
+    });
+}
+
+/// Compares building, querying every element and finding all pairs between a [`Bvh`] and a
+/// [`SpatialGrid`].
+macro_rules! compare_broad_phases {
+    ($name:literal, $elements:expr) => {{
+        let elements: Vec<Aabb> = $elements;
+
+        let bvh: Bvh<Aabb> = build_broad_phase(elements.clone());
+        let grid: SpatialGrid<Aabb> = build_broad_phase(elements.clone());
+
+        let (bvh_pairs, grid_pairs) = (bvh.clone(), grid.clone());
+        let (bvh_elements, grid_elements) = (elements.clone(), elements.clone());
+        let (bvh_targets, grid_targets) = (elements.clone(), elements);
+
+        [
+            benchmark_fn(concat!("build_", $name, "_bvh"), move || {
+                build_broad_phase::<Bvh<Aabb>>(bvh_elements.clone())
+            }),
+            benchmark_fn(concat!("build_", $name, "_grid"), move || {
+                build_broad_phase::<SpatialGrid<Aabb>>(grid_elements.clone())
+            }),
+            benchmark_fn(concat!("collisions_", $name, "_bvh"), move || {
+                collide_each(&bvh, &bvh_targets)
+            }),
+            benchmark_fn(concat!("collisions_", $name, "_grid"), move || {
+                collide_each(&grid, &grid_targets)
+            }),
+            benchmark_fn(concat!("self_pairs_", $name, "_bvh"), move || {
+                bvh_pairs
+                    .par_self_pairs()
+                    .iter()
+                    .map(Vec::len)
+                    .sum::<usize>()
+            }),
+            benchmark_fn(concat!("self_pairs_", $name, "_grid"), move || {
+                grid_pairs
+                    .par_self_pairs()
+                    .iter()
+                    .map(Vec::len)
+                    .sum::<usize>()
+            }),
+        ]
+    }};
+}
+
+fn uniform_benchmarks() -> impl IntoBenchmarks {
+    compare_broad_phases!("uniform", create_random_elements_1(COUNT, 100.0))
+}
+
+/// Crowds of zombies around players.
+fn clustered_benchmarks() -> impl IntoBenchmarks {
+    compare_broad_phases!("clustered", create_clustered_elements(COUNT, 20, 2_000.0))
+}
+
+fn sparse_benchmarks() -> impl IntoBenchmarks {
+    compare_broad_phases!("sparse", create_random_elements_1(COUNT, 10_000.0))
+}
+
 fn build_benchmarks() -> impl IntoBenchmarks {
     // thread pool
     rayon::ThreadPoolBuilder::default()
@@ -101,7 +176,12 @@ fn build_benchmarks() -> impl IntoBenchmarks {
     ]
 }
 
-tango_benchmarks!(build_benchmarks());
+tango_benchmarks!(
+    build_benchmarks(),
+    uniform_benchmarks(),
+    clustered_benchmarks(),
+    sparse_benchmarks()
+);
 
 tango_main!(MeasurementSettings {
     min_iterations_per_sample: 10,
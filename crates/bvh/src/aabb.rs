@@ -1,8 +1,8 @@
-use std::fmt::Display;
+use std::{fmt::Display, simd::prelude::*};
 
 use glam::Vec3;
 
-use crate::HasAabb;
+use crate::{ray::Ray, HasAabb};
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Aabb {
@@ -128,6 +128,12 @@ impl Aabb {
             && self.max.z >= other.min.z
     }
 
+    /// Whether `other` is completely inside of this box.
+    #[must_use]
+    pub fn contains(&self, other: &Self) -> bool {
+        self.min.cmple(other.min).all() && self.max.cmpge(other.max).all()
+    }
+
     pub fn collides_point(&self, point: Vec3) -> bool {
         let point = point.as_ref();
         let self_min = self.min.as_ref();
@@ -143,6 +149,87 @@ impl Aabb {
         collide == 1
     }
 
+    /// How far along `ray` it enters this box, using the slab test on all axes at once. Returns 0
+    /// if the ray starts inside and `None` if it misses or only reaches the box after `max_dist`.
+    #[must_use]
+    pub fn intersect_ray(&self, ray: &Ray) -> Option<f32> {
+        // the unused lane spans everything so it never limits the result
+        let min = f32x4::from_array([self.min.x, self.min.y, self.min.z, f32::NEG_INFINITY]);
+        let max = f32x4::from_array([self.max.x, self.max.y, self.max.z, f32::INFINITY]);
+        let origin = f32x4::from_array([ray.origin.x, ray.origin.y, ray.origin.z, 0.0]);
+        let inv_dir = f32x4::from_array([ray.inv_dir.x, ray.inv_dir.y, ray.inv_dir.z, 1.0]);
+
+        // empty boxes like `NULL` would otherwise span everything
+        if !min.simd_le(max).all() {
+            return None;
+        }
+
+        let a = (min - origin) * inv_dir;
+        let b = (max - origin) * inv_dir;
+
+        // a ray which does not move along an axis and starts on a face gets 0 * inf = NaN, and
+        // touching counts as a hit like in `collides`
+        let a = a.is_nan().select(f32x4::splat(f32::NEG_INFINITY), a);
+        let b = b.is_nan().select(f32x4::splat(f32::INFINITY), b);
+
+        let enter = a.simd_min(b).reduce_max().max(0.0);
+        let exit = a.simd_max(b).reduce_min().min(ray.max_dist);
+
+        (enter <= exit).then_some(enter)
+    }
+
+    /// When this box first touches `other` while moving by `velocity`, as a fraction of `velocity`
+    /// from 0 to 1, and the normal of the face of `other` it touches. Boxes which already collide
+    /// touch at 0 with a zero normal. Returns `None` if they never touch.
+    #[must_use]
+    pub fn sweep(&self, velocity: Vec3, other: &Self) -> Option<(f32, Vec3)> {
+        if self.collides(other) {
+            return Some((0.0, Vec3::ZERO));
+        }
+
+        let mut enter = f32::NEG_INFINITY;
+        let mut exit = f32::INFINITY;
+        let mut normal = Vec3::ZERO;
+
+        for axis in 0..3 {
+            let speed = velocity[axis];
+
+            if speed == 0.0 {
+                // the boxes have to overlap along this axis the whole time
+                if self.max[axis] < other.min[axis] || self.min[axis] > other.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+
+            // the distances to the face which is reached first and the face which is left last
+            let (near, far) = if speed > 0.0 {
+                (
+                    other.min[axis] - self.max[axis],
+                    other.max[axis] - self.min[axis],
+                )
+            } else {
+                (
+                    other.max[axis] - self.min[axis],
+                    other.min[axis] - self.max[axis],
+                )
+            };
+
+            let axis_enter = near / speed;
+
+            // the boxes touch once they overlap along the axis they start furthest apart on
+            if axis_enter > enter {
+                enter = axis_enter;
+                normal = Vec3::ZERO;
+                normal[axis] = -speed.signum();
+            }
+
+            exit = exit.min(far / speed);
+        }
+
+        (enter <= exit && (0.0..=1.0).contains(&enter)).then_some((enter, normal))
+    }
+
     pub fn dist2(&self, point: Vec3) -> f32 {
         let point = point.as_ref();
         let self_min = self.min.as_ref();
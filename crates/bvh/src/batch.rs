@@ -0,0 +1,115 @@
+//! Running many queries against a [`BroadPhase`] at once.
+//!
+//! Queries which are close to each other visit mostly the same nodes or cells, so they are sorted
+//! along a Morton curve and handed to threads in chunks. What a thread needs is then usually still
+//! in its cache from the previous query. Results go into one buffer per thread, so no thread ever
+//! waits on another.
+
+use rayon::prelude::*;
+use rayon_local::RayonLocal;
+
+use crate::{aabb::Aabb, BroadPhase, HasAabb};
+
+/// How many queries a thread takes at once.
+const CHUNK_LEN: usize = 64;
+
+/// The number of bits of each axis in a Morton code.
+const MORTON_BITS: u32 = 10;
+
+/// Spreads the lower 10 bits of `x` so there are two zero bits between each of them.
+const fn spread_bits(x: u32) -> u32 {
+    let x = x & 0x0000_03ff;
+    let x = (x | (x << 16)) & 0x0300_00ff;
+    let x = (x | (x << 8)) & 0x0300_f00f;
+    let x = (x | (x << 4)) & 0x030c_30c3;
+    (x | (x << 2)) & 0x0924_9249
+}
+
+/// The position of the center of `aabb` along a Morton curve through `bounds`.
+fn morton_code(aabb: &Aabb, bounds: &Aabb) -> u32 {
+    let max = ((1 << MORTON_BITS) - 1) as f32;
+
+    let lens = bounds.lens().max(glam::Vec3::splat(f32::EPSILON));
+    let normalized = ((aabb.mid() - bounds.min) / lens).clamp(glam::Vec3::ZERO, glam::Vec3::ONE);
+    let [x, y, z] = (normalized * max).to_array().map(|v| v as u32);
+
+    spread_bits(x) | (spread_bits(y) << 1) | (spread_bits(z) << 2)
+}
+
+/// See [`BroadPhase::par_collide_all`].
+#[tracing::instrument(skip_all, fields(queries_len = queries.len()))]
+pub(crate) fn par_collide_all<'a, T, Q, B>(
+    broad_phase: &'a B,
+    queries: &[Q],
+    limit: usize,
+) -> RayonLocal<Vec<(usize, &'a T)>>
+where
+    T: HasAabb + Send + Sync,
+    Q: HasAabb + Sync,
+    B: BroadPhase<T>,
+{
+    let result = RayonLocal::init(Vec::new);
+
+    let aabbs: Vec<_> = queries.par_iter().map(HasAabb::aabb).collect();
+    let bounds = Aabb::from(aabbs.as_slice());
+
+    let mut order: Vec<_> = aabbs
+        .par_iter()
+        .enumerate()
+        .map(|(idx, aabb)| (morton_code(aabb, &bounds), idx))
+        .collect();
+
+    order.par_sort_unstable_by_key(|&(code, _)| code);
+
+    order.par_chunks(CHUNK_LEN).for_each(|chunk| {
+        // SAFETY: a thread only ever accesses its own buffer
+        let result = unsafe { &mut *result.get_local_raw().get() };
+
+        for &(_, idx) in chunk {
+            let mut found = 0;
+
+            broad_phase.get_collisions(aabbs[idx], |elem| {
+                if found == limit {
+                    return false;
+                }
+
+                result.push((idx, elem));
+                found += 1;
+                true
+            });
+        }
+    });
+
+    result
+}
+
+/// See [`BroadPhase::par_self_pairs`].
+#[tracing::instrument(skip_all, fields(elements_len = broad_phase.elements().len()))]
+pub(crate) fn par_self_pairs<T, B>(broad_phase: &B) -> RayonLocal<Vec<(&T, &T)>>
+where
+    T: HasAabb + Send + Sync,
+    B: BroadPhase<T>,
+{
+    let result = RayonLocal::init(Vec::new);
+
+    // elements are stored in a spatial order, so neighbouring queries visit the same places
+    broad_phase
+        .elements()
+        .par_chunks(CHUNK_LEN)
+        .for_each(|chunk| {
+            // SAFETY: a thread only ever accesses its own buffer
+            let result = unsafe { &mut *result.get_local_raw().get() };
+
+            for elem in chunk {
+                broad_phase.get_collisions(elem.aabb(), |other| {
+                    // both elements are in `elements()`, so this keeps one of the two orders
+                    if std::ptr::from_ref(other) > std::ptr::from_ref(elem) {
+                        result.push((elem, other));
+                    }
+                    true
+                });
+            }
+        });
+
+    result
+}
@@ -0,0 +1,124 @@
+//! A common interface for the structures which find colliding bounding boxes, so users can pick
+//! whichever is fastest for how their elements are laid out.
+
+use std::fmt::Debug;
+
+use glam::Vec3;
+use rayon_local::RayonLocal;
+
+use crate::{aabb::Aabb, batch, sweep, sweep::SweepHit, Bvh, HasAabb, TrivialHeuristic};
+
+/// Finds elements whose bounding boxes collide.
+///
+/// [`Bvh`] adapts to any distribution of elements, while [`crate::SpatialGrid`] is faster for
+/// dense crowds of similarly sized elements which move every tick.
+pub trait BroadPhase<T: HasAabb + Send + Sync>: Default + Sync {
+    /// Replaces all elements with `elements`, keeping the settings of the structure.
+    fn rebuild(&mut self, elements: Vec<T>);
+
+    /// Calls `update` on every element, for example to move them, and then fixes up the
+    /// structure in whichever way is cheapest.
+    fn update(&mut self, update: impl Fn(&mut T) + Send + Sync);
+
+    /// Removes all elements.
+    fn clear(&mut self) {
+        self.rebuild(Vec::new());
+    }
+
+    /// All elements. References passed to callbacks always point into this slice.
+    fn elements(&self) -> &[T];
+
+    /// Calls `process` with every element which collides with `target` until it returns `false`.
+    fn get_collisions<'a>(&'a self, target: Aabb, process: impl FnMut(&'a T) -> bool)
+    where
+        T: 'a;
+
+    /// Returns the element whose center is closest to `target`, and the distance squared to it.
+    fn get_closest(&self, target: Vec3) -> Option<(&T, f32)>;
+
+    /// Returns the first element `aabb` hits while moving by `velocity`, ignoring elements for
+    /// which `filter` returns `false`.
+    fn sweep_query<'a>(
+        &'a self,
+        aabb: &Aabb,
+        velocity: Vec3,
+        mut filter: impl FnMut(&T) -> bool,
+    ) -> Option<SweepHit<'a, T>>
+    where
+        T: 'a,
+    {
+        let mut swept = *aabb;
+        swept.expand_to_fit(&aabb.move_by(velocity));
+
+        let mut closest = None;
+
+        self.get_collisions(swept, |elem| {
+            let elem = std::slice::from_ref(elem);
+            sweep::closest_sweep(elem, aabb, velocity, &mut filter, &mut closest);
+            true
+        });
+
+        closest
+    }
+
+    /// Finds the elements which collide with each of `queries` in parallel, but at most `limit`
+    /// per query.
+    ///
+    /// Each pair is the index of the query in `queries` and the element it collides with. All
+    /// pairs of a query are next to each other in the same buffer.
+    fn par_collide_all<Q: HasAabb + Sync>(
+        &self,
+        queries: &[Q],
+        limit: usize,
+    ) -> RayonLocal<Vec<(usize, &T)>> {
+        batch::par_collide_all(self, queries, limit)
+    }
+
+    /// Finds every pair of elements which collide with each other in parallel. Each pair is only
+    /// returned once and elements are never paired with themselves.
+    fn par_self_pairs(&self) -> RayonLocal<Vec<(&T, &T)>> {
+        batch::par_self_pairs(self)
+    }
+}
+
+/// Builds with [`TrivialHeuristic`] and refits while that is cheaper than rebuilding.
+impl<T: HasAabb + Send + Copy + Sync + Debug> BroadPhase<T> for Bvh<T> {
+    fn rebuild(&mut self, elements: Vec<T>) {
+        *self = Self::build::<TrivialHeuristic>(elements);
+    }
+
+    fn update(&mut self, update: impl Fn(&mut T) + Send + Sync) {
+        self.refit_or_rebuild::<TrivialHeuristic>(update);
+    }
+
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    fn elements(&self) -> &[T] {
+        &self.elements
+    }
+
+    fn get_collisions<'a>(&'a self, target: Aabb, process: impl FnMut(&'a T) -> bool)
+    where
+        T: 'a,
+    {
+        Self::get_collisions(self, target, process);
+    }
+
+    fn get_closest(&self, target: Vec3) -> Option<(&T, f32)> {
+        Self::get_closest(self, target)
+    }
+
+    fn sweep_query<'a>(
+        &'a self,
+        aabb: &Aabb,
+        velocity: Vec3,
+        filter: impl FnMut(&T) -> bool,
+    ) -> Option<SweepHit<'a, T>>
+    where
+        T: 'a,
+    {
+        Self::sweep_query(self, aabb, velocity, filter)
+    }
+}
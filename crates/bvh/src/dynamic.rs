@@ -0,0 +1,464 @@
+//! A tree which supports inserting, removing and moving elements, for things which rarely change
+//! like regions, trigger volumes, custom block hitboxes and projectiles stuck in walls.
+//!
+//! Unlike [`crate::Bvh`], which is rebuilt from scratch, elements are inserted one at a time next
+//! to the node where they increase the surface area the least, and rotations keep the tree
+//! balanced. Leaves store a fattened bounding box so elements which move a little do not have to
+//! be reinserted.
+//!
+//! See <https://box2d.org/files/ErinCatto_DynamicBVH_GDC2019.pdf>.
+
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use glam::Vec3;
+
+use crate::{aabb::Aabb, HasAabb};
+
+const NULL: u32 = u32::MAX;
+
+/// How far the bounding boxes of leaves are grown by default.
+pub const DEFAULT_MARGIN: f32 = 0.1;
+
+/// A handle to an element in a [`DynamicBvh`], which stays the same until it is removed. After
+/// that, it may refer to an element inserted later.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ProxyId(u32);
+
+#[derive(Clone, Debug)]
+pub(crate) struct DynamicNode<T> {
+    /// For leaves, the bounding box of the element grown by the margin.
+    pub(crate) aabb: Aabb,
+    /// The next free node if this node is free.
+    pub(crate) parent: u32,
+    /// [`NULL`] for leaves.
+    pub(crate) left: u32,
+    pub(crate) right: u32,
+    /// 0 for leaves, -1 for free nodes.
+    pub(crate) height: i32,
+    pub(crate) element: Option<T>,
+}
+
+impl<T> DynamicNode<T> {
+    const fn is_leaf(&self) -> bool {
+        self.left == NULL
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DynamicBvh<T> {
+    pub(crate) nodes: Vec<DynamicNode<T>>,
+    pub(crate) root: u32,
+    free: u32,
+    len: usize,
+    margin: f32,
+}
+
+impl<T> Default for DynamicBvh<T> {
+    fn default() -> Self {
+        Self::new(DEFAULT_MARGIN)
+    }
+}
+
+impl<T> DynamicBvh<T> {
+    /// `margin` is how far the bounding box of each element is grown. Larger margins make moving
+    /// cheaper and queries more expensive.
+    #[must_use]
+    pub const fn new(margin: f32) -> Self {
+        Self {
+            nodes: Vec::new(),
+            root: NULL,
+            free: NULL,
+            len: 0,
+            margin,
+        }
+    }
+
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new(self.margin);
+    }
+
+    #[must_use]
+    pub fn get(&self, id: ProxyId) -> Option<&T> {
+        self.nodes.get(id.0 as usize)?.element.as_ref()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ProxyId, &T)> {
+        self.nodes.iter().enumerate().filter_map(|(idx, node)| {
+            let element = node.element.as_ref()?;
+            Some((ProxyId(idx as u32), element))
+        })
+    }
+
+    pub(crate) fn node(&self, idx: u32) -> &DynamicNode<T> {
+        &self.nodes[idx as usize]
+    }
+
+    fn node_mut(&mut self, idx: u32) -> &mut DynamicNode<T> {
+        &mut self.nodes[idx as usize]
+    }
+
+    fn allocate(&mut self, aabb: Aabb, element: Option<T>) -> u32 {
+        let node = DynamicNode {
+            aabb,
+            parent: NULL,
+            left: NULL,
+            right: NULL,
+            height: 0,
+            element,
+        };
+
+        if self.free == NULL {
+            self.nodes.push(node);
+            return (self.nodes.len() - 1) as u32;
+        }
+
+        let idx = self.free;
+        self.free = self.node(idx).parent;
+        *self.node_mut(idx) = node;
+
+        idx
+    }
+
+    fn deallocate(&mut self, idx: u32) -> Option<T> {
+        let free = self.free;
+        let node = self.node_mut(idx);
+
+        node.parent = free;
+        node.height = -1;
+        let element = node.element.take();
+
+        self.free = idx;
+
+        element
+    }
+
+    /// Points the parent of `old` at `new` instead.
+    fn replace_child(&mut self, parent: u32, old: u32, new: u32) {
+        if parent == NULL {
+            self.root = new;
+            return;
+        }
+
+        let parent = self.node_mut(parent);
+
+        if parent.left == old {
+            parent.left = new;
+        } else {
+            parent.right = new;
+        }
+    }
+
+    fn insert_leaf(&mut self, leaf: u32) {
+        if self.root == NULL {
+            self.root = leaf;
+            self.node_mut(leaf).parent = NULL;
+            return;
+        }
+
+        let leaf_aabb = self.node(leaf).aabb;
+
+        // find the sibling which increases the surface area the least
+        let mut idx = self.root;
+
+        while !self.node(idx).is_leaf() {
+            let node = self.node(idx);
+
+            let area = node.aabb.surface_area();
+            let combined_area = Aabb::containing(&[node.aabb, leaf_aabb]).surface_area();
+
+            // making a new parent for this node and the leaf
+            let cost = 2.0 * combined_area;
+
+            // every ancestor grows when descending further
+            let inheritance = 2.0 * (combined_area - area);
+
+            let child_cost = |child: u32| {
+                let child = self.node(child);
+                let combined = Aabb::containing(&[child.aabb, leaf_aabb]).surface_area();
+
+                if child.is_leaf() {
+                    combined + inheritance
+                } else {
+                    combined - child.aabb.surface_area() + inheritance
+                }
+            };
+
+            let left_cost = child_cost(node.left);
+            let right_cost = child_cost(node.right);
+
+            if cost < left_cost && cost < right_cost {
+                break;
+            }
+
+            idx = if left_cost < right_cost {
+                node.left
+            } else {
+                node.right
+            };
+        }
+
+        let sibling = idx;
+        let old_parent = self.node(sibling).parent;
+
+        let aabb = Aabb::containing(&[self.node(sibling).aabb, leaf_aabb]);
+        let new_parent = self.allocate(aabb, None);
+
+        let height = self.node(sibling).height + 1;
+        let node = self.node_mut(new_parent);
+        node.parent = old_parent;
+        node.left = sibling;
+        node.right = leaf;
+        node.height = height;
+
+        self.replace_child(old_parent, sibling, new_parent);
+
+        self.node_mut(sibling).parent = new_parent;
+        self.node_mut(leaf).parent = new_parent;
+
+        self.fix_upwards(new_parent);
+    }
+
+    fn remove_leaf(&mut self, leaf: u32) {
+        if leaf == self.root {
+            self.root = NULL;
+            return;
+        }
+
+        let parent = self.node(leaf).parent;
+        let grandparent = self.node(parent).parent;
+
+        let sibling = if self.node(parent).left == leaf {
+            self.node(parent).right
+        } else {
+            self.node(parent).left
+        };
+
+        self.replace_child(grandparent, parent, sibling);
+        self.node_mut(sibling).parent = grandparent;
+        self.deallocate(parent);
+
+        self.fix_upwards(grandparent);
+    }
+
+    /// Recomputes the bounding boxes and heights of `idx` and its ancestors, rotating unbalanced
+    /// nodes on the way.
+    fn fix_upwards(&mut self, mut idx: u32) {
+        while idx != NULL {
+            idx = self.balance(idx);
+
+            let node = self.node(idx);
+            let left = self.node(node.left);
+            let right = self.node(node.right);
+
+            let height = 1 + left.height.max(right.height);
+            let aabb = Aabb::containing(&[left.aabb, right.aabb]);
+
+            let node = self.node_mut(idx);
+            node.height = height;
+            node.aabb = aabb;
+
+            idx = node.parent;
+        }
+    }
+
+    /// Rotates the taller child of `a` above it if one child is more than one level taller than
+    /// the other. Returns the node which is now where `a` was.
+    fn balance(&mut self, a: u32) -> u32 {
+        let node = self.node(a);
+
+        // the height of `a` itself may be stale here, only its children are up to date
+        if node.is_leaf() {
+            return a;
+        }
+
+        let b = node.left;
+        let c = node.right;
+
+        let balance = self.node(c).height - self.node(b).height;
+
+        if balance > 1 {
+            return self.rotate_up(a, c, b);
+        }
+
+        if balance < -1 {
+            return self.rotate_up(a, b, c);
+        }
+
+        a
+    }
+
+    /// Makes `child` the parent of `a`, and gives its shorter child to `a` in place of itself.
+    /// `other` is the remaining child of `a`.
+    fn rotate_up(&mut self, a: u32, child: u32, other: u32) -> u32 {
+        let f = self.node(child).left;
+        let g = self.node(child).right;
+
+        let parent = self.node(a).parent;
+
+        self.node_mut(child).left = a;
+        self.node_mut(child).parent = parent;
+        self.node_mut(a).parent = child;
+
+        self.replace_child(parent, a, child);
+
+        // the taller grandchild stays with `child`
+        let (keep, give) = if self.node(f).height > self.node(g).height {
+            (f, g)
+        } else {
+            (g, f)
+        };
+
+        self.node_mut(child).right = keep;
+
+        let a_node = self.node_mut(a);
+        if a_node.left == child {
+            a_node.left = give;
+        } else {
+            a_node.right = give;
+        }
+
+        self.node_mut(give).parent = a;
+
+        let a_aabb = Aabb::containing(&[self.node(other).aabb, self.node(give).aabb]);
+        let a_height = 1 + self.node(other).height.max(self.node(give).height);
+
+        let child_aabb = Aabb::containing(&[a_aabb, self.node(keep).aabb]);
+        let child_height = 1 + a_height.max(self.node(keep).height);
+
+        let a_node = self.node_mut(a);
+        a_node.aabb = a_aabb;
+        a_node.height = a_height;
+
+        let child_node = self.node_mut(child);
+        child_node.aabb = child_aabb;
+        child_node.height = child_height;
+
+        child
+    }
+}
+
+impl<T: HasAabb> DynamicBvh<T> {
+    /// Adds `element` to the tree and returns a handle to move or remove it later.
+    pub fn insert(&mut self, element: T) -> ProxyId {
+        let aabb = element.aabb().expand(self.margin);
+        let leaf = self.allocate(aabb, Some(element));
+
+        self.insert_leaf(leaf);
+        self.len += 1;
+
+        ProxyId(leaf)
+    }
+
+    /// Removes the element and returns it, or `None` if it was already removed.
+    pub fn remove(&mut self, id: ProxyId) -> Option<T> {
+        self.get(id)?;
+
+        self.remove_leaf(id.0);
+        self.len -= 1;
+
+        self.deallocate(id.0)
+    }
+
+    /// Replaces the element, which usually moved. It is only reinserted if it left its fattened
+    /// bounding box, which is what this returns.
+    ///
+    /// # Panics
+    /// If the element was removed.
+    pub fn update(&mut self, id: ProxyId, element: T) -> bool {
+        let aabb = element.aabb();
+        let margin = self.margin;
+
+        let node = self.node_mut(id.0);
+        assert!(
+            node.element.is_some(),
+            "updated an element which was removed"
+        );
+
+        node.element = Some(element);
+
+        if node.aabb.contains(&aabb) {
+            return false;
+        }
+
+        self.remove_leaf(id.0);
+        self.node_mut(id.0).aabb = aabb.expand(margin);
+        self.insert_leaf(id.0);
+
+        true
+    }
+
+    /// Calls `process` with every element which collides with `target` until it returns `false`.
+    pub fn get_collisions(&self, target: Aabb, mut process: impl FnMut(&T) -> bool) {
+        if self.root == NULL {
+            return;
+        }
+
+        // insertions keep the tree balanced, but rotations only bound its height loosely
+        let mut stack = vec![self.root];
+
+        while let Some(idx) = stack.pop() {
+            let node = self.node(idx);
+
+            if !node.aabb.collides(&target) {
+                continue;
+            }
+
+            match &node.element {
+                Some(element) => {
+                    if element.aabb().collides(&target) && !process(element) {
+                        return;
+                    }
+                }
+                None => {
+                    stack.push(node.left);
+                    stack.push(node.right);
+                }
+            }
+        }
+    }
+
+    /// Returns the element whose center is closest to the target and the distance squared to it.
+    pub fn get_closest(&self, target: Vec3) -> Option<(&T, f32)> {
+        // distances are never negative, so their bits sort the same way they do
+        let mut heap = BinaryHeap::new();
+
+        let push = |heap: &mut BinaryHeap<_>, idx: u32| {
+            let node = self.node(idx);
+
+            let dist2 = node.element.as_ref().map_or_else(
+                || node.aabb.dist2(target),
+                |element| (element.aabb().mid() - target).length_squared(),
+            );
+
+            heap.push(Reverse((dist2.to_bits(), idx)));
+        };
+
+        if self.root != NULL {
+            push(&mut heap, self.root);
+        }
+
+        while let Some(Reverse((dist2, idx))) = heap.pop() {
+            let node = self.node(idx);
+
+            match &node.element {
+                Some(element) => return Some((element, f32::from_bits(dist2))),
+                None => {
+                    push(&mut heap, node.left);
+                    push(&mut heap, node.right);
+                }
+            }
+        }
+
+        None
+    }
+}
@@ -0,0 +1,266 @@
+//! A uniform grid which buckets elements by the cell their center is in.
+//!
+//! Building is a parallel sort and a single pass over the elements, which is much cheaper than
+//! building a [`crate::Bvh`]. Queries look at every cell the target overlaps, so the grid works
+//! best when elements are all about the size of a cell, like a crowd of zombies on a grid of
+//! blocks. Elements are stored in exactly one cell, so queries are expanded by the largest element
+//! to find the ones which stick out of their cell.
+
+use std::ops::Range;
+
+use fxhash::FxHashMap;
+use glam::{IVec3, Vec3};
+use rayon::prelude::*;
+
+use crate::{aabb::Aabb, BroadPhase, HasAabb};
+
+/// The cell of `point` in a grid whose cells are `1 / inv_cell_size` wide.
+fn cell_of(point: Vec3, inv_cell_size: f32) -> IVec3 {
+    (point * inv_cell_size).floor().as_ivec3()
+}
+
+/// Orders cells by x, then y, then z, packed so they compare as a single integer.
+fn sort_key(cell: IVec3) -> u128 {
+    let [x, y, z] = cell.to_array().map(|v| u128::from((v as u32) ^ (1 << 31)));
+    x << 64 | y << 32 | z
+}
+
+fn from_sort_key(key: u128) -> IVec3 {
+    let axis = |shift: u32| (((key >> shift) as u32) ^ (1 << 31)) as i32;
+    IVec3::new(axis(64), axis(32), axis(0))
+}
+
+/// A broad-phase which splits space into cubes of the same size.
+#[derive(Clone, Debug)]
+pub struct SpatialGrid<T> {
+    cell_size: f32,
+    /// Sorted by cell, so the elements of each cell are next to each other.
+    elements: Vec<T>,
+    cells: FxHashMap<IVec3, Range<usize>>,
+    /// Half the largest size of any element along each axis, which is how far an element can stick
+    /// out of the cell its center is in.
+    reach: Vec3,
+}
+
+impl<T> Default for SpatialGrid<T> {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CELL_SIZE)
+    }
+}
+
+impl<T> SpatialGrid<T> {
+    /// One block.
+    pub const DEFAULT_CELL_SIZE: f32 = 1.0;
+
+    /// An empty grid with cells which are `cell_size` wide.
+    #[must_use]
+    pub fn new(cell_size: f32) -> Self {
+        assert!(cell_size > 0.0, "cell size must be positive");
+
+        Self {
+            cell_size,
+            elements: Vec::new(),
+            cells: FxHashMap::default(),
+            reach: Vec3::ZERO,
+        }
+    }
+
+    #[must_use]
+    pub const fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// The elements, sorted by the cell they are in.
+    pub fn elements(&self) -> &[T] {
+        &self.elements
+    }
+
+    pub fn clear(&mut self) {
+        self.elements.clear();
+        self.cells.clear();
+        self.reach = Vec3::ZERO;
+    }
+}
+
+impl<T: HasAabb + Send + Sync> SpatialGrid<T> {
+    /// Builds a grid with [`SpatialGrid::DEFAULT_CELL_SIZE`].
+    #[must_use]
+    pub fn build(elements: Vec<T>) -> Self {
+        Self::build_with_cell_size(elements, Self::DEFAULT_CELL_SIZE)
+    }
+
+    #[must_use]
+    #[tracing::instrument(skip_all, fields(elements_len = elements.len()))]
+    pub fn build_with_cell_size(elements: Vec<T>, cell_size: f32) -> Self {
+        let mut grid = Self::new(cell_size);
+        grid.fill(elements);
+        grid
+    }
+
+    fn fill(&mut self, elements: Vec<T>) {
+        let inv_cell_size = self.cell_size.recip();
+
+        let mut keyed: Vec<_> = elements
+            .into_par_iter()
+            .map(|elem| (sort_key(cell_of(elem.aabb().mid(), inv_cell_size)), elem))
+            .collect();
+
+        keyed.par_sort_unstable_by_key(|&(key, _)| key);
+
+        let (keys, elements): (Vec<_>, Vec<_>) = keyed.into_par_iter().unzip();
+
+        self.reach = elements
+            .par_iter()
+            .map(|elem| elem.aabb().lens() * 0.5)
+            .reduce(|| Vec3::ZERO, Vec3::max);
+
+        self.cells.clear();
+
+        let mut start = 0;
+
+        for run in keys.chunk_by(|a, b| a == b) {
+            let end = start + run.len();
+            self.cells.insert(from_sort_key(run[0]), start..end);
+            start = end;
+        }
+
+        self.elements = elements;
+    }
+
+    /// Calls `process` with every element which collides with `target` until it returns `false`.
+    pub fn get_collisions<'a>(&'a self, target: Aabb, mut process: impl FnMut(&'a T) -> bool) {
+        let inv_cell_size = self.cell_size.recip();
+
+        let min = cell_of(target.min - self.reach, inv_cell_size).as_i64vec3();
+        let max = cell_of(target.max + self.reach, inv_cell_size).as_i64vec3();
+
+        let lens = max - min + 1;
+
+        // nothing can collide with an empty target
+        if lens.min_element() <= 0 {
+            return;
+        }
+
+        let cell_count = lens.x.saturating_mul(lens.y).saturating_mul(lens.z);
+
+        // a target which covers more cells than are occupied is faster to check element by element
+        if cell_count > self.cells.len() as i64 {
+            for elem in &self.elements {
+                if elem.aabb().collides(&target) && !process(elem) {
+                    return;
+                }
+            }
+            return;
+        }
+
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    let cell = IVec3::new(x as i32, y as i32, z as i32);
+
+                    let Some(range) = self.cells.get(&cell) else {
+                        continue;
+                    };
+
+                    for elem in &self.elements[range.clone()] {
+                        if elem.aabb().collides(&target) && !process(elem) {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the element whose center is closest to `target`, and the distance squared to it.
+    ///
+    /// Cells are searched in growing shells around `target` until no unsearched cell can hold
+    /// anything closer.
+    pub fn get_closest(&self, target: Vec3) -> Option<(&T, f32)> {
+        let center = cell_of(target, self.cell_size.recip());
+
+        let mut closest: Option<(&T, f32)> = None;
+
+        for shell in 0_i32.. {
+            let side = i64::from(shell) * 2 + 1;
+
+            // once the shells hold more cells than are occupied it is faster to check everything
+            if side.saturating_mul(side).saturating_mul(side) > self.cells.len() as i64 {
+                consider(&mut closest, &self.elements, target);
+                break;
+            }
+
+            for x in -shell..=shell {
+                for y in -shell..=shell {
+                    let on_face = x.abs() == shell || y.abs() == shell;
+
+                    let zs = if on_face {
+                        (-shell..=shell).step_by(1)
+                    } else {
+                        // only the front and back of the shell; the inside was searched already
+                        (-shell..=shell).step_by((2 * shell).max(1) as usize)
+                    };
+
+                    for z in zs {
+                        if let Some(range) = self.cells.get(&(center + IVec3::new(x, y, z))) {
+                            consider(&mut closest, &self.elements[range.clone()], target);
+                        }
+                    }
+                }
+            }
+
+            // anything in a cell further out is at least this far from the target
+            let reached = shell as f32 * self.cell_size;
+
+            if closest.is_some_and(|(_, dist2)| dist2 <= reached * reached) {
+                break;
+            }
+        }
+
+        closest
+    }
+}
+
+/// Replaces `closest` with the element of `elements` whose center is closest to `target`, if it is
+/// closer.
+fn consider<'a, T: HasAabb>(closest: &mut Option<(&'a T, f32)>, elements: &'a [T], target: Vec3) {
+    for elem in elements {
+        let dist2 = (elem.aabb().mid() - target).length_squared();
+
+        if closest.map_or(true, |(_, closest)| dist2 < closest) {
+            *closest = Some((elem, dist2));
+        }
+    }
+}
+
+impl<T: HasAabb + Send + Sync> BroadPhase<T> for SpatialGrid<T> {
+    fn rebuild(&mut self, elements: Vec<T>) {
+        self.fill(elements);
+    }
+
+    /// Elements move between cells, so the grid is always rebuilt.
+    fn update(&mut self, update: impl Fn(&mut T) + Send + Sync) {
+        let mut elements = std::mem::take(&mut self.elements);
+        elements.par_iter_mut().for_each(update);
+        self.fill(elements);
+    }
+
+    fn clear(&mut self) {
+        Self::clear(self);
+    }
+
+    fn elements(&self) -> &[T] {
+        &self.elements
+    }
+
+    fn get_collisions<'a>(&'a self, target: Aabb, process: impl FnMut(&'a T) -> bool)
+    where
+        T: 'a,
+    {
+        Self::get_collisions(self, target, process);
+    }
+
+    fn get_closest(&self, target: Vec3) -> Option<(&T, f32)> {
+        Self::get_closest(self, target)
+    }
+}
@@ -12,12 +12,31 @@ use std::{
 use arrayvec::ArrayVec;
 use glam::Vec3;
 
-use crate::aabb::Aabb;
+use crate::{
+    aabb::Aabb,
+    ray::{Ray, RayHit},
+};
 
 const ELEMENTS_TO_ACTIVATE_LEAF: usize = 16;
 const VOLUME_TO_ACTIVATE_LEAF: f32 = 5.0;
 
+/// Splits never leave fewer elements than this on either side, so leaves are never tiny and a
+/// build never needs more nodes than it allocates.
+pub const MIN_ELEMENTS_PER_SIDE: usize = ELEMENTS_TO_ACTIVATE_LEAF / 2;
+
 pub mod aabb;
+mod batch;
+mod broad_phase;
+pub mod dynamic;
+mod grid;
+pub mod ray;
+mod refit;
+mod sah;
+pub mod sweep;
+
+pub use broad_phase::BroadPhase;
+pub use grid::SpatialGrid;
+pub use sah::SahHeuristic;
 
 #[cfg(feature = "plot")]
 pub mod plot;
@@ -56,6 +75,9 @@ pub struct Bvh<T> {
     nodes: Vec<BvhNode>,
     elements: Vec<T>,
     root: i32,
+    /// The SAH cost right after the last build, which refits are compared against. This is only
+    /// computed by the first refit, so trees which are built every tick never pay for it.
+    built_cost: Option<f32>,
 }
 
 impl<T> Default for Bvh<T> {
@@ -64,6 +86,7 @@ impl<T> Default for Bvh<T> {
             nodes: vec![BvhNode::DUMMY],
             elements: Vec::new(),
             root: 0,
+            built_cost: None,
         }
     }
 }
@@ -72,6 +95,11 @@ impl<T> Bvh<T> {
     pub fn clear(&mut self) {
         *self = Self::default();
     }
+
+    /// The elements in the order they are stored in the leaves.
+    pub fn elements(&self) -> &[T] {
+        &self.elements
+    }
 }
 
 impl<T: Debug> Debug for Bvh<T> {
@@ -80,6 +108,7 @@ impl<T: Debug> Debug for Bvh<T> {
             .field("nodes", &self.nodes)
             .field("elems", &self.elements)
             .field("root", &self.root)
+            .field("built_cost", &self.built_cost)
             .finish()
     }
 }
@@ -136,110 +165,282 @@ impl<T: HasAabb + Send + Copy + Sync + Debug> Bvh<T> {
 
         let nodes_slice = &mut nodes[1..];
 
-        let (root, _) = BvhNode::build_in(&bvh, &mut elements, max_threads, 0, nodes_slice);
+        let (root, _) = BvhNode::build_in::<T, H>(&bvh, &mut elements, max_threads, 0, nodes_slice);
 
         Self {
             nodes,
             elements,
             root,
+            built_cost: None,
         }
     }
 
     /// Returns the closest element to the target and the distance squared to it.
     pub fn get_closest(&self, target: Vec3) -> Option<(&T, f32)> {
-        struct NodeOrd<'a> {
-            node: &'a BvhNode,
-            dist2: f32,
-        }
+        self.nearest(target).next()
+    }
 
-        impl PartialEq<Self> for NodeOrd<'_> {
-            fn eq(&self, other: &Self) -> bool {
-                self.dist2 == other.dist2
-            }
+    pub fn get_collisions<'a>(&'a self, target: Aabb, mut process: impl FnMut(&'a T) -> bool) {
+        BvhIter::consume(self, target, &mut process);
+    }
+}
+
+impl<T: HasAabb> Bvh<T> {
+    /// Returns the elements sorted by the distance from their center to `target`, together with
+    /// the distance squared. Elements are only visited once they are the next closest, so
+    /// stopping early skips most of the tree.
+    pub fn nearest(&self, target: Vec3) -> Nearest<'_, T> {
+        let mut nearest = Nearest {
+            bvh: self,
+            target,
+            heap: BinaryHeap::new(),
+        };
+
+        // the root of an empty tree is a dummy node which points to itself
+        if self.elements.is_empty() {
+            return nearest;
         }
-        impl PartialOrd for NodeOrd<'_> {
-            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-                Some(self.cmp(other))
-            }
+
+        match self.root() {
+            Node::Internal(internal) => nearest.push_node(internal),
+            Node::Leaf(leaf) => nearest.push_elements(leaf),
         }
 
-        impl Eq for NodeOrd<'_> {}
+        nearest
+    }
+
+    /// Returns the `k` elements closest to `target` and the distance squared to each, closest
+    /// first.
+    pub fn k_nearest(&self, target: Vec3, k: usize) -> Vec<(&T, f32)> {
+        self.nearest(target).take(k).collect()
+    }
+
+    /// Calls `process` with every element whose center is within `radius` of `target`, closest
+    /// first, until it returns `false`.
+    pub fn within_radius(&self, target: Vec3, radius: f32, mut process: impl FnMut(&T) -> bool) {
+        let radius2 = radius * radius;
 
-        impl Ord for NodeOrd<'_> {
-            fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-                self.dist2.partial_cmp(&other.dist2).unwrap()
+        for (elem, dist2) in self.nearest(target) {
+            if dist2 > radius2 || !process(elem) {
+                return;
             }
         }
+    }
+
+    /// Returns the first element hit by a ray from `origin` towards `dir` within `max_dist`.
+    pub fn raycast(&self, origin: Vec3, dir: Vec3, max_dist: f32) -> Option<RayHit<'_, T>> {
+        self.cast(Ray::new(origin, dir, max_dist))
+    }
 
-        let mut min_dist2 = f32::MAX;
-        let mut min_node = None;
+    /// Returns every element hit by a ray from `origin` towards `dir` within `max_dist`, in no
+    /// particular order.
+    pub fn raycast_all(&self, origin: Vec3, dir: Vec3, max_dist: f32) -> RayHits<'_, T> {
+        self.cast_all(Ray::new(origin, dir, max_dist))
+    }
 
-        let on = self.root();
+    /// Returns the first element hit by `ray`. Use [`Ray::segment`] to test a line segment.
+    pub fn cast(&self, mut ray: Ray) -> Option<RayHit<'_, T>> {
+        let mut closest = None;
 
-        let on = match on {
+        let root = match self.root() {
             Node::Internal(internal) => internal,
             Node::Leaf(leaf) => {
-                return leaf
-                    .iter()
-                    .map(|elem| {
-                        let aabb = elem.aabb();
-                        let mid = aabb.mid();
-                        let dist2 = (mid - target).length_squared();
-
-                        (elem, dist2)
-                    })
-                    .min_by_key(|(_, dist)| dist.to_bits())
+                closest_hit(leaf, &mut ray, &mut closest);
+                return closest;
             }
         };
 
-        // let mut stack: SmallVec<&BvhNode, 64> = SmallVec::new();
-        let mut heap: BinaryHeap<_> = std::iter::once(on)
-            .map(|node| Reverse(NodeOrd { node, dist2: 0.0 }))
-            .collect();
+        let dist = root.aabb.intersect_ray(&ray)?;
 
-        while let Some(on) = heap.pop() {
-            let on = on.0.node;
-            let dist2 = on.aabb.dist2(target);
+        let mut stack: Vec<(&BvhNode, f32)> = Vec::new();
+        stack.push((root, dist));
 
-            if dist2 > min_dist2 {
-                break;
+        while let Some((on, dist)) = stack.pop() {
+            // a closer hit was found since the node was pushed
+            if dist > ray.max_dist {
+                continue;
             }
 
+            let mut children: ArrayVec<(&BvhNode, f32), 2> = ArrayVec::new();
+
             for child in on.children(self) {
                 match child {
                     Node::Internal(internal) => {
-                        heap.push(Reverse(NodeOrd {
-                            node: internal,
-                            dist2: internal.aabb.dist2(target),
-                        }));
-                    }
-                    Node::Leaf(leaf) => {
-                        let (elem, dist2) = leaf
-                            .iter()
-                            .map(|elem| {
-                                let aabb = elem.aabb();
-                                let mid = aabb.mid();
-                                let dist = (mid - target).length_squared();
-
-                                (elem, dist)
-                            })
-                            .min_by_key(|(_, dist)| dist.to_bits())
-                            .unwrap();
-
-                        if dist2 < min_dist2 {
-                            min_dist2 = dist2;
-                            min_node = Some(elem);
+                        if let Some(dist) = internal.aabb.intersect_ray(&ray) {
+                            children.push((internal, dist));
                         }
                     }
+                    Node::Leaf(leaf) => closest_hit(leaf, &mut ray, &mut closest),
                 }
             }
+
+            // visit the nearer child first so farther ones are more likely to be skipped
+            children.sort_unstable_by(|(_, a), (_, b)| b.total_cmp(a));
+            stack.extend(children);
         }
 
-        min_node.map(|elem| (elem, min_dist2))
+        closest
     }
 
-    pub fn get_collisions(&self, target: Aabb, mut process: impl FnMut(&T) -> bool) {
-        BvhIter::consume(self, target, &mut process);
+    /// Returns every element hit by `ray`, in no particular order.
+    pub fn cast_all(&self, ray: Ray) -> RayHits<'_, T> {
+        let mut hits = RayHits {
+            bvh: self,
+            ray,
+            stack: Vec::new(),
+            leaf: [].iter(),
+        };
+
+        match self.root() {
+            Node::Internal(internal) => {
+                if internal.aabb.intersect_ray(&ray).is_some() {
+                    hits.stack.push(internal);
+                }
+            }
+            Node::Leaf(leaf) => hits.leaf = leaf.iter(),
+        }
+
+        hits
+    }
+}
+
+enum Candidate<'a, T> {
+    Node(&'a BvhNode),
+    Element(&'a T),
+}
+
+/// A node or element in the queue of [`Nearest`]. For nodes the distance is to their bounding
+/// box, which is never farther than any element inside of them.
+struct HeapEntry<'a, T> {
+    dist2: f32,
+    candidate: Candidate<'a, T>,
+}
+
+impl<T> PartialEq for HeapEntry<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl<T> Eq for HeapEntry<'_, T> {}
+
+impl<T> PartialOrd for HeapEntry<'_, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for HeapEntry<'_, T> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.dist2.total_cmp(&other.dist2)
+    }
+}
+
+/// The elements of a [`Bvh`] from closest to farthest. See [`Bvh::nearest`].
+pub struct Nearest<'a, T> {
+    bvh: &'a Bvh<T>,
+    target: Vec3,
+    heap: BinaryHeap<Reverse<HeapEntry<'a, T>>>,
+}
+
+impl<'a, T: HasAabb> Nearest<'a, T> {
+    fn push_node(&mut self, node: &'a BvhNode) {
+        self.heap.push(Reverse(HeapEntry {
+            dist2: node.aabb.dist2(self.target),
+            candidate: Candidate::Node(node),
+        }));
+    }
+
+    fn push_elements(&mut self, elements: &'a [T]) {
+        let target = self.target;
+
+        self.heap.extend(elements.iter().map(|elem| {
+            Reverse(HeapEntry {
+                dist2: (elem.aabb().mid() - target).length_squared(),
+                candidate: Candidate::Element(elem),
+            })
+        }));
+    }
+}
+
+impl<'a, T: HasAabb> Iterator for Nearest<'a, T> {
+    type Item = (&'a T, f32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(Reverse(entry)) = self.heap.pop() {
+            let node = match entry.candidate {
+                Candidate::Element(elem) => return Some((elem, entry.dist2)),
+                Candidate::Node(node) => node,
+            };
+
+            for child in node.children(self.bvh) {
+                match child {
+                    Node::Internal(internal) => self.push_node(internal),
+                    Node::Leaf(leaf) => self.push_elements(leaf),
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Updates `closest` with the elements of `leaf` which are hit before it, and shortens `ray` to
+/// the closest hit so far.
+fn closest_hit<'a, T: HasAabb>(leaf: &'a [T], ray: &mut Ray, closest: &mut Option<RayHit<'a, T>>) {
+    for element in leaf {
+        let Some(dist) = element.aabb().intersect_ray(ray) else {
+            continue;
+        };
+
+        if closest.as_ref().map_or(true, |closest| dist < closest.dist) {
+            ray.max_dist = dist;
+            *closest = Some(RayHit {
+                element,
+                dist,
+                point: ray.at(dist),
+            });
+        }
+    }
+}
+
+/// The elements hit by a ray. See [`Bvh::cast_all`].
+pub struct RayHits<'a, T> {
+    bvh: &'a Bvh<T>,
+    ray: Ray,
+    stack: Vec<&'a BvhNode>,
+    leaf: std::slice::Iter<'a, T>,
+}
+
+impl<'a, T: HasAabb> Iterator for RayHits<'a, T> {
+    type Item = RayHit<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            for element in self.leaf.by_ref() {
+                if let Some(dist) = element.aabb().intersect_ray(&self.ray) {
+                    return Some(RayHit {
+                        element,
+                        dist,
+                        point: self.ray.at(dist),
+                    });
+                }
+            }
+
+            let on = self.stack.pop()?;
+
+            for child in on.children(self.bvh) {
+                match child {
+                    Node::Internal(internal) => {
+                        if internal.aabb.intersect_ray(&self.ray).is_some() {
+                            self.stack.push(internal);
+                        }
+                    }
+                    // a node has either one leaf or only internal children
+                    Node::Leaf(leaf) => self.leaf = leaf.iter(),
+                }
+            }
+        }
     }
 }
 
@@ -264,16 +465,20 @@ impl HasAabb for Aabb {
     }
 }
 
+/// Decides how the elements of a node are split between its children.
 pub trait Heuristic {
-    /// left are partitioned to the left side,
-    /// middle cannot be partitioned to either, right are partitioned to the right side
-    fn heuristic<T: HasAabb>(elements: &[T]) -> usize;
+    /// Reorders `elements`, which are contained in `aabb`, so the ones before the returned index
+    /// go to the left child and the rest go to the right child. Each side must get at least
+    /// [`MIN_ELEMENTS_PER_SIDE`] elements.
+    fn heuristic<T: HasAabb>(elements: &mut [T], aabb: &Aabb) -> usize;
 }
 
+/// Splits at the median along the largest axis, which is fast to build and always balanced.
 pub struct TrivialHeuristic;
 
 impl Heuristic for TrivialHeuristic {
-    fn heuristic<T: HasAabb>(elements: &[T]) -> usize {
+    fn heuristic<T: HasAabb>(elements: &mut [T], aabb: &Aabb) -> usize {
+        sort_by_largest_axis(elements, aabb);
         elements.len() / 2
     }
 }
@@ -400,7 +605,7 @@ impl BvhNode {
     }
 
     #[allow(clippy::float_cmp)]
-    fn build_in<T>(
+    fn build_in<T, H: Heuristic>(
         root: &BvhBuild<T>,
         elements: &mut [T],
         max_threads: usize,
@@ -430,9 +635,10 @@ impl BvhNode {
             return (idx, nodes_idx + 1);
         }
 
-        sort_by_largest_axis(elements, &aabb);
+        let element_split_idx = H::heuristic(elements, &aabb);
 
-        let element_split_idx = elements.len() / 2;
+        debug_assert!(element_split_idx >= MIN_ELEMENTS_PER_SIDE);
+        debug_assert!(elements.len() - element_split_idx >= MIN_ELEMENTS_PER_SIDE);
 
         let (left_elems, right_elems) = elements.split_at_mut(element_split_idx);
 
@@ -443,10 +649,10 @@ impl BvhNode {
         let (left, right, nodes_idx, to_set) = if max_threads == 1 {
             let start_idx = nodes_idx;
             let (left, nodes_idx) =
-                Self::build_in(root, left_elems, max_threads, nodes_idx + 1, nodes);
+                Self::build_in::<T, H>(root, left_elems, max_threads, nodes_idx + 1, nodes);
 
             let (right, nodes_idx) =
-                Self::build_in(root, right_elems, max_threads, nodes_idx, nodes);
+                Self::build_in::<T, H>(root, right_elems, max_threads, nodes_idx, nodes);
             let end_idx = nodes_idx;
 
             debug_assert!(start_idx != end_idx);
@@ -462,7 +668,9 @@ impl BvhNode {
 
             let (to_set, nodes) = nodes.split_at_mut(1);
 
-            let node_split_idx = nodes.len() / 2;
+            // heuristics can split unevenly, and each side needs nodes in proportion to its elements
+            let node_split_idx =
+                nodes.len() * left_elems.len() / (left_elems.len() + right_elems.len());
             // todo: remove fastrand
             let (left_nodes, right_nodes) = match true {
                 true => {
@@ -476,8 +684,8 @@ impl BvhNode {
             };
 
             let (left, right) = rayon::join(
-                || Self::build_in(root, left_elems, max_threads, 0, left_nodes),
-                || Self::build_in(root, right_elems, max_threads, 0, right_nodes),
+                || Self::build_in::<T, H>(root, left_elems, max_threads, 0, left_nodes),
+                || Self::build_in::<T, H>(root, right_elems, max_threads, 0, right_nodes),
             );
 
             (left.0, right.0, 0, to_set)
@@ -506,7 +714,7 @@ impl<'a, T> BvhIter<'a, T>
 where
     T: HasAabb,
 {
-    fn consume(bvh: &'a Bvh<T>, target: Aabb, process: &mut impl FnMut(&T) -> bool) {
+    fn consume(bvh: &'a Bvh<T>, target: Aabb, process: &mut impl FnMut(&'a T) -> bool) {
         let root = bvh.root();
 
         let root = match root {
@@ -530,10 +738,12 @@ where
         iter.process(root, process);
     }
 
-    pub fn process(&self, on: &BvhNode, process: &mut impl FnMut(&T) -> bool) {
-        let mut stack: ArrayVec<&BvhNode, 64> = ArrayVec::new();
+    pub fn process(&self, on: &'a BvhNode, process: &mut impl FnMut(&'a T) -> bool) {
+        let mut stack: Vec<&BvhNode> = Vec::new();
         stack.push(on);
 
+        let mut stopped = false;
+
         while let Some(on) = stack.pop() {
             on.switch_children(
                 self.bvh,
@@ -545,11 +755,16 @@ where
                 |elements| {
                     for elem in elements {
                         if elem.aabb().collides(&self.target) && !process(elem) {
+                            stopped = true;
                             return;
                         }
                     }
                 },
             );
+
+            if stopped {
+                return;
+            }
         }
     }
 }
@@ -572,5 +787,20 @@ pub fn create_random_elements_1(count: usize, width: f32) -> Vec<Aabb> {
     elements
 }
 
+/// Creates `count` player-sized elements in `clusters` crowds spread over `width`, like zombies
+/// swarming around players.
+pub fn create_clustered_elements(count: usize, clusters: usize, width: f32) -> Vec<Aabb> {
+    let centers: Vec<_> = (0..clusters)
+        .map(|_| Vec3::new(fastrand::f32() * width, 0.0, fastrand::f32() * width))
+        .collect();
+
+    (0..count)
+        .map(|idx| {
+            let offset = Vec3::new(fastrand::f32() - 0.5, 0.0, fastrand::f32() - 0.5) * 32.0;
+            Aabb::create(centers[idx % clusters] + offset, 0.6, 1.8)
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,52 @@
+use glam::Vec3;
+
+/// A half-line starting at `origin` which is only tested up to `max_dist`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    /// Normalized so distances along the ray are measured in world units.
+    pub dir: Vec3,
+    /// `1 / dir`, precomputed for the slab test. Axes the ray does not move along are positive
+    /// infinity.
+    pub inv_dir: Vec3,
+    pub max_dist: f32,
+}
+
+impl Ray {
+    /// `dir` does not have to be normalized.
+    #[must_use]
+    pub fn new(origin: Vec3, dir: Vec3, max_dist: f32) -> Self {
+        // -0 would make the inverse negative infinity, which the slab test does not expect
+        let dir = dir.normalize_or_zero() + Vec3::ZERO;
+
+        Self {
+            origin,
+            dir,
+            inv_dir: dir.recip(),
+            max_dist,
+        }
+    }
+
+    /// The ray from `start` which ends at `end`.
+    #[must_use]
+    pub fn segment(start: Vec3, end: Vec3) -> Self {
+        let delta = end - start;
+        Self::new(start, delta, delta.length())
+    }
+
+    /// The point `dist` along the ray.
+    #[must_use]
+    pub fn at(&self, dist: f32) -> Vec3 {
+        self.origin + self.dir * dist
+    }
+}
+
+/// An element hit by a [`Ray`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RayHit<'a, T> {
+    pub element: &'a T,
+    /// How far along the ray the element was entered. This is 0 if the ray starts inside of it.
+    pub dist: f32,
+    /// Where the ray enters the element.
+    pub point: Vec3,
+}
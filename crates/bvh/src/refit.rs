@@ -0,0 +1,156 @@
+//! Updating a [`Bvh`] whose elements moved without rebuilding it.
+//!
+//! Refitting keeps the shape of the tree and only grows or shrinks the bounding boxes of the nodes,
+//! which is much cheaper than a build when elements move a little each tick. The tree gets worse
+//! as elements move away from where they were when it was built, so [`Bvh::refit_or_rebuild`]
+//! compares the SAH cost against the cost after the last build.
+
+use std::fmt::Debug;
+
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+
+use crate::{aabb::Aabb, thread_count_pow2, Bvh, BvhNode, HasAabb, Heuristic, Node};
+
+/// The cost of visiting a node relative to testing an element.
+const TRAVERSAL_COST: f32 = 1.0;
+const INTERSECTION_COST: f32 = 1.0;
+
+/// Lets threads write to disjoint nodes of the same tree.
+struct NodesPtr(*mut BvhNode);
+
+unsafe impl Send for NodesPtr {}
+unsafe impl Sync for NodesPtr {}
+
+impl<T> Bvh<T> {
+    /// How much the SAH cost may grow through refits before [`Bvh::refit_or_rebuild`] rebuilds.
+    pub const REBUILD_COST_RATIO: f32 = 1.5;
+
+    /// The surface area heuristic: the expected cost of a query relative to the root, assuming
+    /// the chance of entering a node is proportional to its surface area. Lower is better.
+    pub fn sah_cost(&self) -> f32
+    where
+        T: HasAabb,
+    {
+        if self.elements.is_empty() || self.root < 0 {
+            return 0.0;
+        }
+
+        let root = &self.nodes[self.root as usize];
+        let root_area = root.aabb.surface_area();
+
+        if root_area <= 0.0 {
+            return 0.0;
+        }
+
+        let mut cost = 0.0;
+
+        let mut stack: Vec<&BvhNode> = Vec::new();
+        stack.push(root);
+
+        while let Some(on) = stack.pop() {
+            let area = on.aabb.surface_area() / root_area;
+
+            if on.left < 0 {
+                cost = (area * on.right as f32).mul_add(INTERSECTION_COST, cost);
+                continue;
+            }
+
+            cost = area.mul_add(TRAVERSAL_COST, cost);
+
+            for child in on.children(self) {
+                if let Node::Internal(child) = child {
+                    stack.push(child);
+                }
+            }
+        }
+
+        cost
+    }
+
+    /// Whether refits made the tree enough worse than a fresh build that it should be rebuilt.
+    pub fn needs_rebuild(&self) -> bool
+    where
+        T: HasAabb,
+    {
+        // trees which were never refit are as good as built
+        self.built_cost
+            .is_some_and(|built| self.sah_cost() > built * Self::REBUILD_COST_RATIO)
+    }
+}
+
+impl<T: HasAabb + Send + Copy + Sync + Debug> Bvh<T> {
+    /// Calls `update` on every element in parallel and then recomputes the bounding boxes of the
+    /// nodes from the leaves up. The elements stay in the same leaves however far they moved.
+    #[tracing::instrument(skip_all, fields(elements_len = self.elements.len()))]
+    pub fn refit(&mut self, update: impl Fn(&mut T) + Send + Sync) {
+        if self.built_cost.is_none() {
+            self.built_cost = Some(self.sah_cost());
+        }
+
+        self.elements.par_iter_mut().for_each(update);
+
+        // the root of an empty tree is a dummy node which points to itself
+        if self.elements.is_empty() || self.root < 0 {
+            return;
+        }
+
+        let nodes = NodesPtr(self.nodes.as_mut_ptr());
+
+        // SAFETY: every node is reachable from the root through exactly one path, so each one is
+        // written by exactly one thread
+        unsafe {
+            refit_in(
+                &nodes,
+                &self.elements,
+                self.root as usize,
+                thread_count_pow2(),
+            );
+        }
+    }
+
+    /// Refits the tree and rebuilds it with `H` if it got too much worse. See
+    /// [`Bvh::needs_rebuild`].
+    pub fn refit_or_rebuild<H: Heuristic>(&mut self, update: impl Fn(&mut T) + Send + Sync) {
+        self.refit(update);
+
+        if self.needs_rebuild() {
+            let elements = std::mem::take(&mut self.elements);
+            *self = Self::build::<H>(elements);
+        }
+    }
+}
+
+/// Recomputes the bounding box of the node at `idx` and everything below it, and returns it.
+unsafe fn refit_in<T: HasAabb + Sync>(
+    nodes: &NodesPtr,
+    elements: &[T],
+    idx: usize,
+    max_threads: usize,
+) -> Aabb {
+    let node = nodes.0.add(idx);
+    let BvhNode { left, right, .. } = *node;
+
+    let aabb = if left < 0 {
+        let start = (-left - 1) as usize;
+        Aabb::from(&elements[start..start + right as usize])
+    } else if max_threads > 1 {
+        let max_threads = max_threads >> 1;
+
+        let (left, right) = rayon::join(
+            || refit_in(nodes, elements, left as usize, max_threads),
+            || refit_in(nodes, elements, right as usize, max_threads),
+        );
+
+        let mut aabb = left;
+        aabb.expand_to_fit(&right);
+        aabb
+    } else {
+        let mut aabb = refit_in(nodes, elements, left as usize, 1);
+        aabb.expand_to_fit(&refit_in(nodes, elements, right as usize, 1));
+        aabb
+    };
+
+    (*node).aabb = aabb;
+
+    aabb
+}
@@ -0,0 +1,136 @@
+//! A [`Heuristic`] which minimizes the surface area heuristic.
+//!
+//! The chance that a query enters a node is roughly proportional to its surface area, so the
+//! expected cost of a split is the surface area of each side times the number of elements in it.
+//! Trying every possible split is too slow, so the centers of the elements are sorted into a few
+//! bins along each axis and only the boundaries between bins are tried.
+//!
+//! This is much better than a median split when elements are clustered, like players and the
+//! zombies crowding around them, because a median split cuts straight through the clusters.
+
+use crate::{aabb::Aabb, HasAabb, Heuristic, TrivialHeuristic, MIN_ELEMENTS_PER_SIDE};
+
+const BINS: usize = 16;
+
+/// Splits where the binned surface area heuristic is lowest.
+pub struct SahHeuristic;
+
+#[derive(Copy, Clone)]
+struct Bin {
+    aabb: Aabb,
+    count: usize,
+}
+
+impl Bin {
+    const EMPTY: Self = Self {
+        aabb: Aabb::NULL,
+        count: 0,
+    };
+}
+
+/// Which bin the center of `aabb` falls into along `axis`.
+fn bin_of(aabb: &Aabb, axis: usize, min: f32, scale: f32) -> usize {
+    let offset = (aabb.mid().as_ref()[axis] - min) * scale;
+    (offset as usize).min(BINS - 1)
+}
+
+/// Moves the elements for which `left` is true to the front and returns how many there are.
+fn partition<T>(elements: &mut [T], mut left: impl FnMut(&T) -> bool) -> usize {
+    let mut split = 0;
+
+    for idx in 0..elements.len() {
+        if left(&elements[idx]) {
+            elements.swap(split, idx);
+            split += 1;
+        }
+    }
+
+    split
+}
+
+struct Split {
+    cost: f32,
+    axis: usize,
+    /// The first bin on the right side.
+    bin: usize,
+}
+
+impl Heuristic for SahHeuristic {
+    fn heuristic<T: HasAabb>(elements: &mut [T], aabb: &Aabb) -> usize {
+        let mut centers = Aabb::NULL;
+
+        for elem in elements.iter() {
+            let mid = elem.aabb().mid();
+            centers.expand_to_fit(&Aabb::new(mid, mid));
+        }
+
+        let extent = centers.lens();
+        let mut best: Option<Split> = None;
+
+        for axis in 0..3 {
+            let extent = extent.as_ref()[axis];
+
+            // every center is at the same place
+            if extent <= 0.0 {
+                continue;
+            }
+
+            let min = centers.min.as_ref()[axis];
+            let scale = BINS as f32 / extent;
+
+            let mut bins = [Bin::EMPTY; BINS];
+
+            for elem in elements.iter() {
+                let aabb = elem.aabb();
+                let bin = &mut bins[bin_of(&aabb, axis, min, scale)];
+
+                bin.aabb.expand_to_fit(&aabb);
+                bin.count += 1;
+            }
+
+            // the cost of everything right of each boundary
+            let mut right = [Bin::EMPTY; BINS];
+            let mut acc = Bin::EMPTY;
+
+            for idx in (1..BINS).rev() {
+                acc.aabb.expand_to_fit(&bins[idx].aabb);
+                acc.count += bins[idx].count;
+                right[idx] = acc;
+            }
+
+            let mut left = Bin::EMPTY;
+
+            for bin in 1..BINS {
+                left.aabb.expand_to_fit(&bins[bin - 1].aabb);
+                left.count += bins[bin - 1].count;
+
+                let right = right[bin];
+
+                if left.count < MIN_ELEMENTS_PER_SIDE || right.count < MIN_ELEMENTS_PER_SIDE {
+                    continue;
+                }
+
+                let cost = left.aabb.surface_area().mul_add(
+                    left.count as f32,
+                    right.aabb.surface_area() * right.count as f32,
+                );
+
+                if best.as_ref().map_or(true, |best| cost < best.cost) {
+                    best = Some(Split { cost, axis, bin });
+                }
+            }
+        }
+
+        // all elements are in too few bins to split them, for example when they are all stacked
+        let Some(Split { axis, bin, .. }) = best else {
+            return TrivialHeuristic::heuristic(elements, aabb);
+        };
+
+        let min = centers.min.as_ref()[axis];
+        let scale = BINS as f32 / extent.as_ref()[axis];
+
+        partition(elements, |elem| {
+            bin_of(&elem.aabb(), axis, min, scale) < bin
+        })
+    }
+}
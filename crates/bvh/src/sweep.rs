@@ -0,0 +1,96 @@
+//! Finding the first element a moving box hits, so fast movers do not tunnel through thin
+//! elements they would skip over if only their end position was tested.
+
+use arrayvec::ArrayVec;
+use glam::Vec3;
+
+use crate::{aabb::Aabb, Bvh, BvhNode, HasAabb, Node};
+
+/// An element hit by a moving box.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SweepHit<'a, T> {
+    pub element: &'a T,
+    /// When the element is hit, as a fraction of the movement from 0 to 1. This is 0 if the box
+    /// already touches it.
+    pub toi: f32,
+    /// The normal of the face of the element which is hit, or zero if the box already touches it.
+    pub normal: Vec3,
+}
+
+/// Updates `closest` with the elements of `leaf` which `filter` accepts and which are hit before
+/// it.
+pub(crate) fn closest_sweep<'a, T: HasAabb>(
+    leaf: &'a [T],
+    aabb: &Aabb,
+    velocity: Vec3,
+    filter: &mut impl FnMut(&T) -> bool,
+    closest: &mut Option<SweepHit<'a, T>>,
+) {
+    for element in leaf {
+        let Some((toi, normal)) = aabb.sweep(velocity, &element.aabb()) else {
+            continue;
+        };
+
+        if closest.as_ref().map_or(true, |closest| toi < closest.toi) && filter(element) {
+            *closest = Some(SweepHit {
+                element,
+                toi,
+                normal,
+            });
+        }
+    }
+}
+
+impl<T: HasAabb> Bvh<T> {
+    /// Returns the first element `aabb` hits while moving by `velocity`, ignoring elements for
+    /// which `filter` returns `false`.
+    pub fn sweep_query(
+        &self,
+        aabb: &Aabb,
+        velocity: Vec3,
+        mut filter: impl FnMut(&T) -> bool,
+    ) -> Option<SweepHit<'_, T>> {
+        let mut closest = None;
+
+        let root = match self.root() {
+            Node::Internal(internal) => internal,
+            Node::Leaf(leaf) => {
+                closest_sweep(leaf, aabb, velocity, &mut filter, &mut closest);
+                return closest;
+            }
+        };
+
+        let (toi, _) = aabb.sweep(velocity, &root.aabb)?;
+
+        let mut stack: Vec<(&BvhNode, f32)> = Vec::new();
+        stack.push((root, toi));
+
+        while let Some((on, toi)) = stack.pop() {
+            // a closer hit was found since the node was pushed
+            if closest.as_ref().is_some_and(|closest| toi >= closest.toi) {
+                continue;
+            }
+
+            let mut children: ArrayVec<(&BvhNode, f32), 2> = ArrayVec::new();
+
+            for child in on.children(self) {
+                match child {
+                    Node::Internal(internal) => {
+                        if let Some((toi, _)) = aabb.sweep(velocity, &internal.aabb) {
+                            children.push((internal, toi));
+                        }
+                    }
+                    Node::Leaf(leaf) => {
+                        closest_sweep(leaf, aabb, velocity, &mut filter, &mut closest);
+                    }
+                }
+            }
+
+            // visit the child which is hit earlier first so the other is more likely to be skipped
+            children.sort_unstable_by(|(_, a), (_, b)| b.total_cmp(a));
+            stack.extend(children);
+        }
+
+        closest
+    }
+}
@@ -3,7 +3,7 @@ use std::collections::HashSet;
 use itertools::Itertools;
 
 use super::*;
-use crate::aabb::CheckableAabb;
+use crate::{aabb::CheckableAabb, dynamic::DynamicBvh, ray::Ray};
 
 fn collisions_naive(
     elements: &[Aabb],
@@ -92,6 +92,7 @@ fn children_returns_empty_leaf_when_no_children() {
         nodes: vec![BvhNode::DUMMY, node],
         elements: Vec::new(),
         root: 1,
+        built_cost: None,
     };
     assert_eq!(node.children(&bvh).next(), Some(Node::Leaf(&[])));
 }
@@ -112,6 +113,7 @@ fn children_returns_internal_nodes() {
         nodes: vec![BvhNode::DUMMY, child_node, child_node],
         elements: Vec::new(),
         root: 42, // root does not matter in this case
+        built_cost: None,
     };
     let mut children = node.children(&bvh);
     assert_eq!(children.next(), Some(Node::Internal(&child_node)));
@@ -181,3 +183,890 @@ fn get_closest_returns_none_when_no_elements() {
 
     assert!(closest.is_none());
 }
+
+fn raycast_naive(elements: &[Aabb], ray: &Ray) -> Vec<(Aabb, f32)> {
+    elements
+        .iter()
+        .filter_map(|elem| elem.intersect_ray(ray).map(|dist| (*elem, dist)))
+        .collect()
+}
+
+#[test]
+fn intersect_ray_slab_test() {
+    let aabb = Aabb::new(Vec3::new(2.0, 0.0, 0.0), Vec3::new(3.0, 1.0, 1.0));
+
+    let towards = Ray::new(Vec3::new(0.0, 0.5, 0.5), Vec3::X, 10.0);
+    assert_eq!(aabb.intersect_ray(&towards), Some(2.0));
+
+    let away = Ray::new(Vec3::new(0.0, 0.5, 0.5), Vec3::NEG_X, 10.0);
+    assert_eq!(aabb.intersect_ray(&away), None);
+
+    let too_short = Ray::new(Vec3::new(0.0, 0.5, 0.5), Vec3::X, 1.5);
+    assert_eq!(aabb.intersect_ray(&too_short), None);
+
+    let inside = Ray::new(Vec3::new(2.5, 0.5, 0.5), Vec3::Y, 10.0);
+    assert_eq!(aabb.intersect_ray(&inside), Some(0.0));
+
+    // runs along a face without moving on that axis
+    let grazing = Ray::new(Vec3::new(0.0, 1.0, 0.5), Vec3::X, 10.0);
+    assert_eq!(aabb.intersect_ray(&grazing), Some(2.0));
+
+    let diagonal = Ray::segment(Vec3::new(0.0, 0.0, 0.0), Vec3::new(4.0, 0.0, 4.0));
+    assert_eq!(aabb.intersect_ray(&diagonal), None);
+
+    assert_eq!(Aabb::NULL.intersect_ray(&towards), None);
+}
+
+#[test]
+fn raycast_returns_first_hit() {
+    let elements = vec![
+        Aabb::new(Vec3::new(5.0, 0.0, 0.0), Vec3::new(6.0, 1.0, 1.0)),
+        Aabb::new(Vec3::new(2.0, 0.0, 0.0), Vec3::new(3.0, 1.0, 1.0)),
+        Aabb::new(Vec3::new(2.0, 5.0, 0.0), Vec3::new(3.0, 6.0, 1.0)),
+    ];
+    let bvh = Bvh::build::<TrivialHeuristic>(elements);
+
+    let hit = bvh
+        .raycast(Vec3::new(0.0, 0.5, 0.5), Vec3::X, 100.0)
+        .unwrap();
+
+    assert_eq!(
+        *hit.element,
+        Aabb::new(Vec3::new(2.0, 0.0, 0.0), Vec3::new(3.0, 1.0, 1.0))
+    );
+    assert_eq!(hit.dist, 2.0);
+    assert_eq!(hit.point, Vec3::new(2.0, 0.5, 0.5));
+
+    assert_eq!(
+        bvh.raycast_all(Vec3::new(0.0, 0.5, 0.5), Vec3::X, 100.0)
+            .count(),
+        2
+    );
+
+    let segment = Ray::segment(Vec3::new(0.0, 0.5, 0.5), Vec3::new(1.0, 0.5, 0.5));
+    assert!(bvh.cast(segment).is_none());
+}
+
+#[test]
+fn raycast_matches_naive_with_random_data() {
+    let elements = create_random_elements_1(10_000, 100.0);
+    let bvh = Bvh::build::<TrivialHeuristic>(elements.clone());
+
+    for _ in 0..100 {
+        let origin = Vec3::from_array(std::array::from_fn(|_| fastrand::f32() * 100.0));
+        let dir = Vec3::from_array(std::array::from_fn(|_| fastrand::f32() - 0.5));
+        let ray = Ray::new(origin, dir, 30.0);
+
+        let naive = raycast_naive(&elements, &ray);
+
+        let closest = naive.iter().map(|(_, dist)| *dist).min_by(f32::total_cmp);
+        assert_eq!(bvh.cast(ray).map(|hit| hit.dist), closest);
+
+        let mut all: Vec<_> = bvh
+            .cast_all(ray)
+            .map(|hit| CheckableAabb::try_from(*hit.element).unwrap())
+            .collect();
+        let mut expected: Vec<_> = naive
+            .iter()
+            .map(|(elem, _)| CheckableAabb::try_from(*elem).unwrap())
+            .collect();
+
+        all.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(all, expected);
+    }
+}
+
+#[test]
+fn raycast_returns_none_when_no_elements() {
+    let bvh = Bvh::build::<TrivialHeuristic>(Vec::<Aabb>::new());
+    assert!(bvh.raycast(Vec3::ZERO, Vec3::X, 100.0).is_none());
+    assert_eq!(bvh.raycast_all(Vec3::ZERO, Vec3::X, 100.0).count(), 0);
+
+    let bvh = Bvh::<Aabb>::default();
+    assert!(bvh.raycast(Vec3::ZERO, Vec3::X, 100.0).is_none());
+}
+
+fn mid_dist2(elem: &Aabb, target: Vec3) -> f32 {
+    (elem.mid() - target).length_squared()
+}
+
+#[test]
+fn nearest_is_sorted_and_complete() {
+    let elements = create_random_elements_1(10_000, 100.0);
+    let bvh = Bvh::build::<TrivialHeuristic>(elements);
+
+    let target = Vec3::splat(50.0);
+    let nearest: Vec<_> = bvh.nearest(target).collect();
+
+    assert_eq!(nearest.len(), 10_000);
+
+    for ((elem, dist2), (_, next)) in nearest.iter().tuple_windows() {
+        assert_eq!(*dist2, mid_dist2(elem, target));
+        assert!(dist2 <= next);
+    }
+}
+
+#[test]
+fn k_nearest_matches_naive() {
+    let elements = create_random_elements_1(10_000, 100.0);
+    let bvh = Bvh::build::<TrivialHeuristic>(elements.clone());
+
+    let target = Vec3::from_array(std::array::from_fn(|_| fastrand::f32() * 100.0));
+
+    let mut naive: Vec<_> = elements
+        .iter()
+        .map(|elem| mid_dist2(elem, target))
+        .collect();
+    naive.sort_unstable_by(f32::total_cmp);
+
+    let k_nearest: Vec<_> = bvh
+        .k_nearest(target, 10)
+        .into_iter()
+        .map(|(_, dist2)| dist2)
+        .collect();
+
+    assert_eq!(k_nearest, naive[..10]);
+
+    assert_eq!(bvh.k_nearest(target, 20_000).len(), 10_000);
+}
+
+#[test]
+fn within_radius_matches_naive() {
+    let elements = create_random_elements_1(10_000, 100.0);
+    let bvh = Bvh::build::<TrivialHeuristic>(elements.clone());
+
+    let target = Vec3::from_array(std::array::from_fn(|_| fastrand::f32() * 100.0));
+    let radius = 10.0;
+
+    let naive = elements
+        .iter()
+        .filter(|elem| mid_dist2(elem, target) <= radius * radius)
+        .count();
+
+    let mut found = 0;
+    bvh.within_radius(target, radius, |elem| {
+        assert!(mid_dist2(elem, target) <= radius * radius);
+        found += 1;
+        true
+    });
+
+    assert_eq!(found, naive);
+
+    let mut found = 0;
+    bvh.within_radius(target, radius, |_| {
+        found += 1;
+        false
+    });
+
+    assert_eq!(found, usize::from(naive > 0));
+}
+
+#[test]
+fn nearest_returns_nothing_when_no_elements() {
+    let bvh = Bvh::build::<TrivialHeuristic>(Vec::<Aabb>::new());
+    assert!(bvh.k_nearest(Vec3::ZERO, 5).is_empty());
+
+    let bvh = Bvh::<Aabb>::default();
+    assert_eq!(bvh.nearest(Vec3::ZERO).count(), 0);
+}
+
+fn assert_nodes_contain_children(bvh: &Bvh<Aabb>) {
+    let mut stack = vec![&bvh.nodes[bvh.root as usize]];
+
+    while let Some(on) = stack.pop() {
+        for child in on.children(bvh) {
+            match child {
+                Node::Internal(child) => {
+                    assert_eq!(Aabb::containing(&[on.aabb, child.aabb]), on.aabb);
+                    stack.push(child);
+                }
+                Node::Leaf(elements) => assert_eq!(Aabb::from(elements), on.aabb),
+            }
+        }
+    }
+}
+
+#[test]
+fn refit_matches_moved_elements() {
+    let elements = create_random_elements_1(10_000, 100.0);
+    let mut bvh = Bvh::build::<TrivialHeuristic>(elements);
+
+    bvh.refit(|elem| {
+        let offset = Vec3::from_array(std::array::from_fn(|_| fastrand::f32() - 0.5));
+        *elem = elem.move_by(offset);
+    });
+
+    assert_nodes_contain_children(&bvh);
+
+    let moved = bvh.elements().to_vec();
+    let target = random_aabb(30.0).expand(5.0);
+
+    let naive = collisions_naive(&moved, target).unwrap();
+
+    let mut found = HashSet::new();
+    bvh.get_collisions(target, |elem| {
+        found.insert(CheckableAabb::try_from(*elem).unwrap());
+        true
+    });
+
+    assert_eq!(found, naive);
+}
+
+#[test]
+fn refit_or_rebuild_rebuilds_scrambled_tree() {
+    let elements = create_random_elements_1(10_000, 100.0);
+    let mut bvh = Bvh::build::<TrivialHeuristic>(elements);
+
+    let built = bvh.sah_cost();
+
+    // small movements keep the tree
+    bvh.refit_or_rebuild::<TrivialHeuristic>(|elem| *elem = elem.move_by(Vec3::splat(0.01)));
+    assert!(!bvh.needs_rebuild());
+
+    // elements teleporting somewhere else make every node span the whole world
+    let mut scrambled = bvh.clone();
+    scrambled.refit(|elem| *elem = random_aabb(100.0));
+    assert!(scrambled.sah_cost() > built * Bvh::<Aabb>::REBUILD_COST_RATIO);
+    assert!(scrambled.needs_rebuild());
+
+    bvh.refit_or_rebuild::<TrivialHeuristic>(|elem| *elem = random_aabb(100.0));
+    assert!(!bvh.needs_rebuild());
+    assert_nodes_contain_children(&bvh);
+    assert_eq!(bvh.elements().len(), 10_000);
+}
+
+#[test]
+fn built_cost_is_measured_on_the_first_refit() {
+    let elements = create_random_elements_1(1_000, 100.0);
+    let mut bvh = Bvh::build::<TrivialHeuristic>(elements);
+
+    assert!(bvh.built_cost.is_none());
+    assert!(!bvh.needs_rebuild());
+
+    let built = bvh.sah_cost();
+    bvh.refit(|elem| *elem = random_aabb(100.0));
+
+    assert_eq!(bvh.built_cost, Some(built));
+    assert!(bvh.needs_rebuild());
+}
+
+#[test]
+fn refit_empty() {
+    let mut bvh = Bvh::<Aabb>::default();
+    bvh.refit(|_| {});
+    assert!(bvh.sah_cost().abs() < f32::EPSILON);
+
+    let mut bvh = Bvh::build::<TrivialHeuristic>(Vec::<Aabb>::new());
+    bvh.refit_or_rebuild::<TrivialHeuristic>(|_| {});
+    assert!(bvh.elements().is_empty());
+}
+
+fn collisions<H: Heuristic>(elements: &[Aabb], targets: &[Aabb]) -> Vec<HashSet<CheckableAabb>> {
+    let bvh = Bvh::build::<H>(elements.to_vec());
+
+    targets
+        .iter()
+        .map(|target| {
+            let mut found = HashSet::new();
+            bvh.get_collisions(*target, |elem| {
+                found.insert(CheckableAabb::try_from(*elem).unwrap());
+                true
+            });
+            found
+        })
+        .collect()
+}
+
+#[test]
+fn sah_matches_trivial() {
+    let uniform = create_random_elements_1(10_000, 100.0);
+    let clustered = create_clustered_elements(10_000, 20, 1_000.0);
+
+    for elements in [uniform, clustered] {
+        let targets: Vec<_> = elements
+            .iter()
+            .step_by(100)
+            .map(|elem| elem.expand(2.0))
+            .collect();
+
+        assert_eq!(
+            collisions::<SahHeuristic>(&elements, &targets),
+            collisions::<TrivialHeuristic>(&elements, &targets)
+        );
+
+        let sah = Bvh::build::<SahHeuristic>(elements.clone());
+        let trivial = Bvh::build::<TrivialHeuristic>(elements);
+
+        let target = Vec3::splat(50.0);
+        assert_eq!(
+            sah.get_closest(target).map(|(_, dist2)| dist2),
+            trivial.get_closest(target).map(|(_, dist2)| dist2)
+        );
+    }
+}
+
+#[test]
+fn sah_is_cheaper_for_clusters() {
+    let elements = create_clustered_elements(10_000, 20, 1_000.0);
+
+    let sah = Bvh::build::<SahHeuristic>(elements.clone());
+    let trivial = Bvh::build::<TrivialHeuristic>(elements);
+
+    assert!(sah.sah_cost() < trivial.sah_cost());
+}
+
+#[test]
+fn sah_handles_stacked_elements() {
+    // every center is the same, so there is nothing to bin
+    let elements = vec![Aabb::new(Vec3::ZERO, Vec3::splat(10.0)); 1_000];
+    let bvh = Bvh::build::<SahHeuristic>(elements);
+
+    let mut count = 0;
+    bvh.get_collisions(Aabb::EVERYTHING, |_| {
+        count += 1;
+        true
+    });
+
+    assert_eq!(count, 1_000);
+}
+
+#[test]
+fn sah_builds_all_sizes() {
+    let counts = &[0, 1, 10, 100, 1_000, 10_000, 100_000];
+
+    for &count in counts {
+        let bvh = Bvh::build::<SahHeuristic>(create_clustered_elements(count, 7, 500.0));
+        assert_eq!(bvh.elements().len(), count);
+
+        Bvh::build::<SahHeuristic>(create_random_elements_1(count, 100.0));
+    }
+}
+
+#[test]
+fn par_collide_all_matches_naive() {
+    let elements = create_random_elements_1(10_000, 100.0);
+    let bvh = Bvh::build::<TrivialHeuristic>(elements.clone());
+
+    let queries = (0..1_000).map(|_| random_aabb(100.0)).collect_vec();
+
+    let mut found = vec![HashSet::new(); queries.len()];
+
+    for (idx, elem) in bvh
+        .par_collide_all(&queries, usize::MAX)
+        .into_iter()
+        .flatten()
+    {
+        assert!(found[idx].insert(CheckableAabb::try_from(*elem).unwrap()));
+    }
+
+    for (query, found) in queries.iter().zip(found) {
+        assert_eq!(found, collisions_naive(&elements, *query).unwrap());
+    }
+}
+
+#[test]
+fn par_collide_all_groups_pairs_by_query() {
+    let bvh = Bvh::build::<TrivialHeuristic>(create_random_elements_1(1_000, 20.0));
+    let queries = (0..1_000).map(|_| random_aabb(20.0)).collect_vec();
+
+    let mut seen = HashSet::new();
+
+    for pairs in bvh.par_collide_all(&queries, usize::MAX) {
+        for (idx, _) in pairs.iter().dedup_by(|a, b| a.0 == b.0) {
+            assert!(seen.insert(*idx), "pairs of query {idx} are split up");
+        }
+    }
+}
+
+#[test]
+fn par_collide_all_limits_pairs_per_query() {
+    let elements = create_clustered_elements(2_000, 10, 100.0);
+    let bvh = Bvh::build::<SahHeuristic>(elements.clone());
+
+    let queries = (0..1_000)
+        .map(|_| random_aabb(100.0).expand(3.0))
+        .collect_vec();
+
+    let mut found = vec![0; queries.len()];
+
+    for (idx, _) in bvh.par_collide_all(&queries, 4).into_iter().flatten() {
+        found[idx] += 1;
+    }
+
+    for (query, found) in queries.iter().zip(found) {
+        let all = elements.iter().filter(|elem| elem.collides(query)).count();
+        assert_eq!(found, all.min(4));
+    }
+}
+
+#[test]
+fn get_collisions_stops_when_asked() {
+    let bvh = Bvh::build::<TrivialHeuristic>(create_random_elements_1(10_000, 10.0));
+
+    let mut count = 0;
+    bvh.get_collisions(random_aabb(10.0).expand(5.0), |_| {
+        count += 1;
+        count < 3
+    });
+
+    assert_eq!(count, 3);
+}
+
+#[test]
+fn par_self_pairs_matches_naive() {
+    let elements = create_clustered_elements(2_000, 10, 200.0);
+    let bvh = Bvh::build::<SahHeuristic>(elements.clone());
+
+    let mut expected = 0;
+
+    for (idx, a) in elements.iter().enumerate() {
+        expected += elements[idx + 1..].iter().filter(|b| a.collides(b)).count();
+    }
+
+    let mut found = HashSet::new();
+
+    for (a, b) in bvh.par_self_pairs().into_iter().flatten() {
+        assert!(!std::ptr::eq(a, b));
+        assert!(a.collides(b));
+
+        let (a, b) = (std::ptr::from_ref(a), std::ptr::from_ref(b));
+        assert!(found.insert((a.min(b), a.max(b))));
+    }
+
+    assert_eq!(found.len(), expected);
+}
+
+#[test]
+fn batched_queries_on_empty_tree() {
+    let bvh = Bvh::<Aabb>::default();
+    assert_eq!(
+        bvh.par_collide_all(&[random_aabb(10.0)], usize::MAX)
+            .into_iter()
+            .flatten()
+            .count(),
+        0
+    );
+    assert_eq!(bvh.par_self_pairs().into_iter().flatten().count(), 0);
+
+    let bvh = Bvh::build::<TrivialHeuristic>(create_random_elements_1(100, 10.0));
+    assert_eq!(
+        bvh.par_collide_all::<Aabb>(&[], usize::MAX)
+            .into_iter()
+            .flatten()
+            .count(),
+        0
+    );
+}
+
+fn random_velocity(speed: f32) -> Vec3 {
+    // some axes do not move at all, which the slab test handles separately
+    Vec3::from_array(std::array::from_fn(|_| {
+        if fastrand::u8(..4) == 0 {
+            0.0
+        } else {
+            fastrand::f32().mul_add(2.0 * speed, -speed)
+        }
+    }))
+}
+
+/// The first of `steps` evenly spaced times at which `aabb` moving by `velocity` collides with
+/// `other`.
+fn sweep_sampled(aabb: &Aabb, velocity: Vec3, other: &Aabb, steps: usize) -> Option<f32> {
+    (0..=steps)
+        .map(|step| step as f32 / steps as f32)
+        .find(|&time| aabb.move_by(velocity * time).collides(other))
+}
+
+#[test]
+fn sweep_matches_sampling() {
+    const STEPS: usize = 1_000;
+
+    for _ in 0..10_000 {
+        let aabb = random_aabb(10.0);
+        let other = random_aabb(10.0);
+        let velocity = random_velocity(20.0);
+
+        let sampled = sweep_sampled(&aabb, velocity, &other, STEPS);
+
+        let Some((toi, normal)) = aabb.sweep(velocity, &other) else {
+            assert!(sampled.is_none(), "missed a hit at {sampled:?}");
+            continue;
+        };
+
+        assert!((0.0..=1.0).contains(&toi));
+
+        // touching at the time of impact, allowing for rounding
+        let moved = aabb.move_by(velocity * toi);
+        assert!(moved.expand(1e-3).collides(&other));
+
+        // no sample before the time of impact collides; sampling can only find a later hit
+        if let Some(sampled) = sampled {
+            assert!(sampled + 1e-4 >= toi, "sampled {sampled} before {toi}");
+            assert!(sampled - toi <= 1.0 / STEPS as f32 + 1e-4);
+        }
+
+        if toi > 0.0 {
+            assert_eq!(normal.abs().element_sum(), 1.0);
+            assert!(normal.dot(velocity) < 0.0);
+        } else {
+            assert!(aabb.collides(&other));
+            assert_eq!(normal, Vec3::ZERO);
+        }
+    }
+}
+
+#[test]
+fn sweep_does_not_tunnel() {
+    let player = Aabb::create(Vec3::ZERO, 0.6, 1.8);
+    let wall = Aabb::new(Vec3::new(5.0, -10.0, -10.0), Vec3::new(5.1, 10.0, 10.0));
+    let velocity = Vec3::new(20.0, 0.0, 0.0);
+
+    // the start and the end are on either side of the wall
+    assert!(!player.collides(&wall));
+    assert!(!player.move_by(velocity).collides(&wall));
+
+    let (toi, normal) = player.sweep(velocity, &wall).unwrap();
+    assert!((toi - 4.7 / 20.0).abs() < 1e-6);
+    assert_eq!(normal, Vec3::NEG_X);
+
+    assert!(player.sweep(-velocity, &wall).is_none());
+    assert!(player.sweep(Vec3::ZERO, &wall).is_none());
+}
+
+/// Checks `sweep_query` against sweeping every element, ignoring elements on the negative x
+/// half.
+fn check_sweep_query<B: BroadPhase<Aabb>>(broad_phase: &B, elements: &[Aabb]) {
+    let filter = |elem: &Aabb| elem.mid().x >= 50.0;
+
+    for _ in 0..1_000 {
+        let aabb = random_aabb(100.0);
+        let velocity = random_velocity(30.0);
+
+        let naive = elements
+            .iter()
+            .filter(|elem| filter(elem))
+            .filter_map(|elem| aabb.sweep(velocity, elem))
+            .map(|(toi, _)| toi)
+            .min_by(f32::total_cmp);
+
+        let hit = broad_phase.sweep_query(&aabb, velocity, filter);
+
+        assert_eq!(hit.map(|hit| hit.toi), naive);
+
+        if let Some(hit) = hit {
+            assert!(filter(hit.element));
+            assert_eq!(
+                aabb.sweep(velocity, hit.element),
+                Some((hit.toi, hit.normal))
+            );
+        }
+    }
+}
+
+#[test]
+fn sweep_query_matches_naive() {
+    let elements = create_random_elements_1(2_000, 100.0);
+
+    let bvh = Bvh::build::<TrivialHeuristic>(elements.clone());
+    check_sweep_query(&bvh, &elements);
+
+    let bvh = Bvh::build::<SahHeuristic>(elements.clone());
+    check_sweep_query(&bvh, &elements);
+
+    let grid = SpatialGrid::build_with_cell_size(elements.clone(), 4.0);
+    check_sweep_query(&grid, &elements);
+
+    // small enough to be a single leaf
+    let few = create_random_elements_1(5, 100.0);
+    check_sweep_query(&Bvh::build::<TrivialHeuristic>(few.clone()), &few);
+}
+
+#[test]
+fn sweep_query_empty() {
+    let aabb = random_aabb(10.0);
+    let velocity = Vec3::splat(10.0);
+
+    assert!(Bvh::<Aabb>::default()
+        .sweep_query(&aabb, velocity, |_| true)
+        .is_none());
+
+    let bvh = Bvh::build::<TrivialHeuristic>(Vec::<Aabb>::new());
+    assert!(bvh.sweep_query(&aabb, velocity, |_| true).is_none());
+}
+
+/// Checks the links, heights and bounding boxes of every node of a [`DynamicBvh`], and returns
+/// the height.
+fn validate_dynamic<T: HasAabb>(bvh: &DynamicBvh<T>, idx: u32) -> i32 {
+    let node = bvh.node(idx);
+
+    if let Some(element) = &node.element {
+        assert_eq!(node.height, 0);
+        assert!(node.aabb.contains(&element.aabb()));
+        return 0;
+    }
+
+    let left = bvh.node(node.left);
+    let right = bvh.node(node.right);
+
+    assert_eq!(left.parent, idx);
+    assert_eq!(right.parent, idx);
+
+    let left_height = validate_dynamic(bvh, node.left);
+    let right_height = validate_dynamic(bvh, node.right);
+
+    assert_eq!(node.height, 1 + left_height.max(right_height));
+    assert!(node.aabb.contains(&left.aabb));
+    assert!(node.aabb.contains(&right.aabb));
+
+    node.height
+}
+
+fn dynamic_collisions(bvh: &DynamicBvh<Aabb>, target: Aabb) -> HashSet<CheckableAabb> {
+    let mut found = HashSet::new();
+
+    bvh.get_collisions(target, |elem| {
+        found.insert(CheckableAabb::try_from(*elem).unwrap());
+        true
+    });
+
+    found
+}
+
+#[test]
+fn dynamic_insert_remove_update_match_naive() {
+    let mut bvh = DynamicBvh::default();
+
+    let mut ids: Vec<_> = (0..1_000).map(|_| bvh.insert(random_aabb(100.0))).collect();
+
+    validate_dynamic(&bvh, bvh.root);
+
+    for id in ids.drain(..500) {
+        assert!(bvh.remove(id).is_some());
+        assert!(bvh.remove(id).is_none());
+    }
+
+    for &id in &ids {
+        let moved = bvh.get(id).unwrap().move_by(Vec3::new(0.05, 0.0, 0.0));
+        assert!(!bvh.update(id, moved));
+    }
+
+    for &id in ids.iter().step_by(2) {
+        assert!(bvh.update(id, random_aabb(100.0)));
+    }
+
+    assert_eq!(bvh.len(), 500);
+
+    // a new leaf can be paired with a whole subtree, so nodes are not strictly balanced, but
+    // the tree should never get much taller than a balanced one
+    let height = validate_dynamic(&bvh, bvh.root);
+    assert!(height <= 2 * bvh.len().ilog2() as i32, "tree is unbalanced");
+
+    let elements: Vec<_> = bvh.iter().map(|(_, elem)| *elem).collect();
+
+    for _ in 0..100 {
+        let target = random_aabb(100.0).expand(3.0);
+        let naive = collisions_naive(&elements, target).unwrap();
+
+        assert_eq!(dynamic_collisions(&bvh, target), naive);
+    }
+}
+
+#[test]
+fn dynamic_get_closest_matches_naive() {
+    let mut bvh = DynamicBvh::default();
+
+    for _ in 0..1_000 {
+        bvh.insert(random_aabb(100.0));
+    }
+
+    let target = Vec3::splat(50.0);
+    let (_, dist2) = bvh.get_closest(target).unwrap();
+
+    let naive = bvh
+        .iter()
+        .map(|(_, elem)| (elem.mid() - target).length_squared())
+        .min_by(f32::total_cmp)
+        .unwrap();
+
+    assert_eq!(dist2, naive);
+}
+
+#[test]
+fn dynamic_reuses_removed_nodes() {
+    let mut bvh = DynamicBvh::default();
+
+    let a = bvh.insert(random_aabb(10.0));
+    bvh.insert(random_aabb(10.0));
+    let nodes = bvh.nodes.len();
+
+    bvh.remove(a);
+    bvh.insert(random_aabb(10.0));
+
+    assert_eq!(bvh.nodes.len(), nodes);
+    assert_eq!(bvh.len(), 2);
+
+    bvh.clear();
+    assert!(bvh.is_empty());
+    assert!(bvh.get_closest(Vec3::ZERO).is_none());
+    assert!(dynamic_collisions(&bvh, Aabb::EVERYTHING).is_empty());
+}
+
+/// A tree where every internal node has a leaf with one element as its left child, which is as
+/// deep as a tree with `len` elements can get.
+fn chain(len: usize) -> Bvh<Aabb> {
+    let elements: Vec<_> = (0..len)
+        .map(|idx| {
+            let x = idx as f32;
+            Aabb::new((x, 0.0, 0.0), (x + 1.0, 1.0, 1.0))
+        })
+        .collect();
+
+    let mut nodes = vec![BvhNode::DUMMY];
+
+    for idx in 0..len - 1 {
+        let internal = nodes.len() as i32;
+        nodes.push(BvhNode {
+            aabb: Aabb::NULL,
+            left: internal + 1,
+            right: internal + 2,
+        });
+        nodes.push(BvhNode::create_leaf(Aabb::NULL, idx, 1));
+    }
+
+    nodes.push(BvhNode::create_leaf(Aabb::NULL, len - 1, 1));
+
+    let mut bvh = Bvh {
+        nodes,
+        elements,
+        root: 1,
+        built_cost: None,
+    };
+
+    bvh.refit(|_| {});
+    bvh
+}
+
+#[test]
+fn deep_trees_can_be_queried() {
+    let mut bvh = chain(200);
+    let elements = bvh.elements().to_vec();
+    assert_nodes_contain_children(&bvh);
+
+    let target = elements[199];
+    let naive = collisions_naive(&elements, target).unwrap();
+
+    let mut found = HashSet::new();
+    bvh.get_collisions(target, |elem| {
+        found.insert(CheckableAabb::try_from(*elem).unwrap());
+        true
+    });
+    assert_eq!(found, naive);
+
+    let origin = Vec3::new(199.5, -1.0, 0.5);
+    assert_eq!(bvh.get_closest(origin).unwrap().0, &elements[199]);
+    assert_eq!(
+        bvh.raycast(origin, Vec3::Y, 2.0).unwrap().element,
+        &elements[199]
+    );
+    assert_eq!(bvh.raycast_all(origin, Vec3::Y, 2.0).count(), 1);
+
+    let hit = bvh.sweep_query(&target.move_by(-Vec3::Y * 2.0), Vec3::Y * 2.0, |_| true);
+    assert!(hit.is_some());
+
+    assert!(bvh.sah_cost() > 0.0);
+    bvh.refit_or_rebuild::<SahHeuristic>(|_| {});
+    assert_nodes_contain_children(&bvh);
+}
+
+fn broad_phase_collisions<B: BroadPhase<Aabb>>(
+    broad_phase: &B,
+    target: Aabb,
+) -> HashSet<CheckableAabb> {
+    let mut found = HashSet::new();
+
+    broad_phase.get_collisions(target, |elem| {
+        assert!(found.insert(CheckableAabb::try_from(*elem).unwrap()));
+        true
+    });
+
+    found
+}
+
+fn check_grid_matches_bvh(elements: &[Aabb], width: f32, cell_size: f32) {
+    let grid = SpatialGrid::build_with_cell_size(elements.to_vec(), cell_size);
+    let bvh = Bvh::build::<TrivialHeuristic>(elements.to_vec());
+
+    assert_eq!(grid.elements().len(), elements.len());
+
+    for _ in 0..200 {
+        let target = random_aabb(width).expand(fastrand::f32() * 4.0);
+        assert_eq!(
+            broad_phase_collisions(&grid, target),
+            broad_phase_collisions(&bvh, target)
+        );
+
+        let point = Vec3::new(fastrand::f32(), fastrand::f32(), fastrand::f32()) * width;
+        let (_, grid_dist2) = grid.get_closest(point).unwrap();
+        let (_, bvh_dist2) = bvh.get_closest(point).unwrap();
+        assert_eq!(grid_dist2, bvh_dist2);
+    }
+
+    assert_eq!(
+        broad_phase_collisions(&grid, Aabb::EVERYTHING),
+        broad_phase_collisions(&bvh, Aabb::EVERYTHING)
+    );
+
+    let far = Vec3::splat(width * 10.0);
+    assert_eq!(
+        grid.get_closest(far).unwrap().1,
+        bvh.get_closest(far).unwrap().1
+    );
+
+    let pairs =
+        |pairs: rayon_local::RayonLocal<Vec<(&Aabb, &Aabb)>>| pairs.into_iter().flatten().count();
+    assert_eq!(pairs(grid.par_self_pairs()), pairs(bvh.par_self_pairs()));
+}
+
+#[test]
+fn grid_matches_bvh_uniform() {
+    check_grid_matches_bvh(&create_random_elements_1(5_000, 100.0), 100.0, 1.0);
+}
+
+#[test]
+fn grid_matches_bvh_clustered() {
+    let elements = create_clustered_elements(5_000, 10, 500.0);
+    check_grid_matches_bvh(&elements, 500.0, 1.0);
+    check_grid_matches_bvh(&elements, 500.0, 4.0);
+}
+
+#[test]
+fn grid_matches_bvh_sparse() {
+    check_grid_matches_bvh(&create_random_elements_1(1_000, 10_000.0), 10_000.0, 1.0);
+}
+
+#[test]
+fn grid_finds_elements_larger_than_cells() {
+    let big = Aabb::new(Vec3::ZERO, Vec3::splat(10.0));
+    let grid = SpatialGrid::build(vec![big]);
+
+    let corner = Aabb::new(Vec3::splat(9.5), Vec3::splat(9.6));
+    assert_eq!(broad_phase_collisions(&grid, corner).len(), 1);
+}
+
+#[test]
+fn grid_update_and_clear() {
+    let mut grid = SpatialGrid::new(2.0);
+    grid.rebuild(create_random_elements_1(100, 10.0));
+
+    grid.update(|elem| *elem = elem.move_by(Vec3::splat(1_000.0)));
+
+    let moved = Aabb::new(Vec3::splat(1_000.0), Vec3::splat(1_011.0));
+    assert_eq!(broad_phase_collisions(&grid, moved).len(), 100);
+    assert_eq!(grid.cell_size(), 2.0);
+
+    BroadPhase::clear(&mut grid);
+    assert!(grid.elements().is_empty());
+    assert!(grid.get_closest(Vec3::ZERO).is_none());
+    assert!(broad_phase_collisions(&grid, Aabb::EVERYTHING).is_empty());
+}
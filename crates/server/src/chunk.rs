@@ -1,5 +1,8 @@
 //! Utilities for working with chunks.
 
+use valence_generated::block::{BlockState, PropName, PropValue};
+use valence_server::layer::chunk::{BlockStateContainer, UnloadedChunk};
+
 use crate::bits::BitStorage;
 
 /// Returns the minimum number of bits needed to represent the integer `n`.
@@ -7,23 +10,103 @@ pub const fn ceil_log2(x: u32) -> u32 {
     u32::BITS - x.leading_zeros()
 }
 
-/// Create a heightmap for the highest solid block at each position in the chunk.
-pub fn heightmap(max_height: u32, current_height: u32) -> Vec<u64> {
-    let bits = ceil_log2(max_height + 1);
-    let mut data = BitStorage::new(bits as usize, 16 * 16, None).unwrap();
+/// The `MOTION_BLOCKING` and `WORLD_SURFACE` heightmaps of a chunk column.
+///
+/// Each entry is one more than the y (relative to the bottom of the world) of the highest block
+/// in the column which counts for the heightmap, or `0` if there is no such block.
+pub struct Heightmaps {
+    pub motion_blocking: Vec<u64>,
+    pub world_surface: Vec<u64>,
+}
+
+impl Heightmaps {
+    #[must_use]
+    pub fn new(chunk: &UnloadedChunk) -> Self {
+        let height = chunk.sections.len() * 16;
+        let bits = ceil_log2(height as u32 + 1) as usize;
+
+        let mut motion_blocking = BitStorage::new(bits, 16 * 16, None).unwrap();
+        let mut world_surface = BitStorage::new(bits, 16 * 16, None).unwrap();
+
+        for index in 0_usize..16 * 16 {
+            let mut found_motion_blocking = false;
+            let mut found_world_surface = false;
+
+            for y in (0..height).rev() {
+                let section = &chunk.sections[y / 16];
+                let state = section.block_states.get(index + (y % 16) * 256);
 
-    for x in 0_usize..16 {
-        for z in 0_usize..16 {
-            let index = x + z * 16;
-            data.set(index, u64::from(current_height) + 1);
+                if !found_world_surface && !state.is_air() {
+                    world_surface.set(index, y as u64 + 1);
+                    found_world_surface = true;
+                }
+
+                if !found_motion_blocking && is_motion_blocking(state) {
+                    motion_blocking.set(index, y as u64 + 1);
+                    found_motion_blocking = true;
+                }
+
+                if found_motion_blocking && found_world_surface {
+                    break;
+                }
+            }
+        }
+
+        Self {
+            motion_blocking: motion_blocking.into_data(),
+            world_surface: world_surface.into_data(),
         }
     }
+}
+
+/// Whether the block stops motion or holds a fluid, which is what `MOTION_BLOCKING` tracks.
+fn is_motion_blocking(state: BlockState) -> bool {
+    state.blocks_motion()
+        || state.is_liquid()
+        || state.get(PropName::Waterlogged) == Some(PropValue::True)
+}
+
+/// The number of blocks in a section which are not air.
+#[must_use]
+pub fn non_air_blocks(states: &BlockStateContainer) -> u16 {
+    let count = (0..16 * 16 * 16)
+        .filter(|&idx| !states.get(idx).is_air())
+        .count();
 
-    data.into_data()
+    count as u16
 }
 
 #[cfg(test)]
 mod tests {
+    use valence_generated::block::BlockState;
+    use valence_server::layer::chunk::{Chunk, UnloadedChunk};
+
+    use super::{non_air_blocks, Heightmaps};
+    use crate::bits::BitStorage;
+
+    #[test]
+    fn test_heightmaps() {
+        let mut chunk = UnloadedChunk::with_height(384);
+
+        chunk.set_block_state(3, 70, 5, BlockState::STONE);
+        chunk.set_block_state(3, 80, 5, BlockState::GRASS);
+        chunk.set_block_state(0, 0, 0, BlockState::WATER);
+
+        let maps = Heightmaps::new(&chunk);
+
+        let motion_blocking = BitStorage::new(9, 256, Some(maps.motion_blocking)).unwrap();
+        let world_surface = BitStorage::new(9, 256, Some(maps.world_surface)).unwrap();
+
+        assert_eq!(motion_blocking.get(3 + 5 * 16), 71);
+        assert_eq!(world_surface.get(3 + 5 * 16), 81);
+        assert_eq!(motion_blocking.get(0), 1);
+        assert_eq!(world_surface.get(15 + 15 * 16), 0);
+
+        assert_eq!(non_air_blocks(&chunk.sections[4].block_states), 2);
+        assert_eq!(non_air_blocks(&chunk.sections[5].block_states), 1);
+        assert_eq!(non_air_blocks(&chunk.sections[10].block_states), 0);
+    }
+
     #[test]
     fn test_ceil_log2() {
         assert_eq!(super::ceil_log2(0), 0);
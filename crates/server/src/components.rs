@@ -4,16 +4,30 @@ use bvh_region::aabb::Aabb;
 use derive_more::{Deref, Display, From};
 use evenio::component::Component;
 use glam::{I16Vec2, Vec3};
+use valence_protocol::{
+    packets::play::client_settings_c2s::{DisplayedSkinParts, MainArm},
+    ItemStack,
+};
 use valence_server::entity::EntityKind;
 
 use crate::{
     components::vitals::{Absorption, Regeneration},
+    config::CONFIG,
     global::Global,
 };
 
 pub mod chunks;
+pub mod combat;
+pub mod entity_kind;
+pub mod goals;
+pub mod hit_validation;
+pub mod instance;
+pub mod metadata;
+pub mod navigation;
 pub mod pose;
+pub mod projectile;
 pub mod vitals;
+pub mod world_border;
 
 #[derive(Component, Deref, From, Display, Debug)]
 pub struct InGameName(Box<str>);
@@ -51,6 +65,8 @@ pub enum Vitals {
     Alive {
         /// Measured in half hearts
         health: f32,
+        /// Healing stops at this health, measured in half hearts
+        max_health: f32,
 
         /// The absorption effect
         absorption: Absorption,
@@ -62,11 +78,18 @@ pub enum Vitals {
 }
 
 impl Vitals {
-    pub const ALIVE: Self = Self::Alive {
-        health: 20.0,
-        absorption: Absorption::DEFAULT,
-        regeneration: Regeneration::DEFAULT,
-    };
+    pub const ALIVE: Self = Self::alive(20.0);
+
+    /// Alive with full health.
+    #[must_use]
+    pub const fn alive(max_health: f32) -> Self {
+        Self::Alive {
+            health: max_health,
+            max_health,
+            absorption: Absorption::DEFAULT,
+            regeneration: Regeneration::DEFAULT,
+        }
+    }
 }
 
 #[derive(Component, Debug, Eq, PartialEq, Default)]
@@ -90,12 +113,15 @@ impl Vitals {
         debug_assert!(amount.is_finite());
         debug_assert!(amount > 0.0);
 
-        let Self::Alive { health, .. } = self else {
+        let Self::Alive {
+            health, max_health, ..
+        } = self
+        else {
             return;
         };
 
         *health += amount;
-        *health = health.min(20.0);
+        *health = health.min(*max_health);
     }
 
     /// Hurt the player by a given amount.
@@ -193,6 +219,48 @@ impl ChunkLocation {
     ));
 }
 
+/// The view distance vanilla clients use if the player has not changed it.
+const DEFAULT_VIEW_DISTANCE: i16 = 12;
+
+/// The radius in chunks around a player which is sent to them. NPCs are only spawned for the
+/// player in the chunks they have loaded.
+///
+/// This is the smaller of the view distance the client asked for and the server's
+/// [`Config::view_distance`](crate::config::Config::view_distance).
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ViewDistance(pub i16);
+
+impl ViewDistance {
+    #[must_use]
+    pub fn new(requested: u8) -> Self {
+        Self(i16::from(requested).min(CONFIG.view_distance as i16))
+    }
+}
+
+impl Default for ViewDistance {
+    fn default() -> Self {
+        Self(DEFAULT_VIEW_DISTANCE.min(CONFIG.view_distance as i16))
+    }
+}
+
+/// The settings a client reported in `ClientSettingsC2s`.
+#[derive(Component, Debug)]
+pub struct ClientSettings {
+    pub locale: String,
+    pub main_arm: MainArm,
+    pub displayed_skin_parts: DisplayedSkinParts,
+}
+
+impl Default for ClientSettings {
+    fn default() -> Self {
+        Self {
+            locale: "en_us".to_owned(),
+            main_arm: MainArm::Right,
+            displayed_skin_parts: DisplayedSkinParts::new(),
+        }
+    }
+}
+
 pub const PLAYER_SPAWN_POSITION: Vec3 = Vec3::new(-464.0, -16.0, -60.0);
 
 impl FullEntityPose {
@@ -233,6 +301,21 @@ impl FullEntityPose {
         self.bounding = self.bounding.move_to(pos);
         self.position = pos;
     }
+
+    /// Where the eyes of the entity are, which is at 1.62 blocks for players like vanilla.
+    #[must_use]
+    pub fn eye_position(&self) -> Vec3 {
+        self.position + Vec3::new(0.0, self.bounding.lens().y * 0.9, 0.0)
+    }
+
+    /// The unit vector the entity is looking along.
+    #[must_use]
+    pub fn look_direction(&self) -> Vec3 {
+        let (yaw_sin, yaw_cos) = self.yaw.to_radians().sin_cos();
+        let (pitch_sin, pitch_cos) = self.pitch.to_radians().sin_cos();
+
+        Vec3::new(-yaw_sin * pitch_cos, -pitch_sin, yaw_cos * pitch_cos)
+    }
 }
 
 /// The reaction of an entity, in particular to collisions as calculated in `entity_detect_collisions`.
@@ -250,3 +333,41 @@ pub struct EntityReaction {
     /// The velocity of the entity.
     pub velocity: Vec3,
 }
+
+/// The item a player holds in their main hand, as last set by `event::SetEquipment`.
+#[derive(Component, Debug, Clone, Default)]
+pub struct HeldItem(pub ItemStack);
+
+/// The tick a player started using an item which charges, like a bow.
+#[derive(Component, Debug, Copy, Clone)]
+pub struct UsingItem {
+    pub since: i64,
+}
+
+/// The movement state of an NPC which carries over between ticks, used to simulate it against
+/// the blocks of its instance.
+#[derive(Component, Default, Debug)]
+pub struct Motion {
+    /// How fast the entity is falling, in blocks per tick. This is negative when falling.
+    pub vertical_velocity: f32,
+    /// Whether the entity was standing on a block at the end of the last tick.
+    pub on_ground: bool,
+}
+
+/// How a player moves as reported by their client, which decides whether their attacks are
+/// critical hits or sprint hits.
+#[derive(Component, Default, Debug, Copy, Clone)]
+pub struct PlayerMotion {
+    pub on_ground: bool,
+    /// Whether the player moved down since they last stood on a block.
+    pub falling: bool,
+    pub sprinting: bool,
+}
+
+impl PlayerMotion {
+    /// Updates the state after the player moved up by `delta_y`.
+    pub fn moved(&mut self, delta_y: f32, on_ground: bool) {
+        self.falling = !on_ground && (self.falling || delta_y < 0.0);
+        self.on_ground = on_ground;
+    }
+}
@@ -1,30 +1,62 @@
-use std::{borrow::Cow, cell::RefCell, collections::BTreeMap, io::Write, sync::Arc};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::BTreeMap,
+    io::Write,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use anyhow::Context;
 use bytes::{Bytes, BytesMut};
 use dashmap::{DashMap, DashSet};
 use derive_more::{Deref, DerefMut};
 use evenio::component::Component;
-use fxhash::FxBuildHasher;
-use glam::I16Vec2;
+use fxhash::{FxBuildHasher, FxHashMap, FxHashSet};
+use glam::{I16Vec2, IVec3};
 use itertools::Itertools;
 use libdeflater::{CompressionLvl, Compressor};
-use tokio::{runtime::Runtime, task::JoinHandle};
+use parking_lot::{Mutex, RwLock};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use tokio::{runtime::Runtime, sync::OnceCell, task::JoinHandle};
 use tracing::{error, instrument};
 use valence_anvil::parsing::parse_chunk;
-use valence_generated::block::BlockState;
-use valence_nbt::{compound, List};
-use valence_protocol::{packets::play, ChunkPos, CompressionThreshold, Encode, FixedArray, Ident};
+use valence_generated::block::{BlockEntityKind, BlockState};
+use valence_nbt::{compound, Compound, List};
+use valence_protocol::{
+    packets::play::{self, chunk_data_s2c::ChunkDataBlockEntity},
+    BlockPos, ChunkPos, CompressionThreshold, Encode, Ident,
+};
 use valence_registry::{biome::BiomeId, BiomeRegistry, RegistryIdx};
 use valence_server::layer::chunk::{bit_width, BiomeContainer, BlockStateContainer, UnloadedChunk};
 
 use crate::{
-    bits::BitStorage, chunk::heightmap, components::chunks::loader::Regions, default,
-    event::Scratch, net::encoder::PacketEncoder,
+    blocks::get_nyc_save,
+    chunk::{non_air_blocks, Heightmaps},
+    components::{
+        chunks::{
+            cache::{CacheStats, ChunkCache},
+            collision::SolidColumn,
+            light::{ColumnLight, LightGrid, SECTION_COUNT},
+            loader::Regions,
+        },
+        PLAYER_SPAWN_POSITION,
+    },
+    config::CONFIG,
+    default,
+    event::Scratch,
+    net::{encoder::PacketEncoder, PacketDecoder},
 };
+pub mod cache;
+pub mod collision;
+pub mod light;
 mod loader;
 mod region;
 
+/// The lowest y coordinate of the overworld.
+const MIN_Y: i32 = -64;
+
 pub struct TasksState {
     bytes: BytesMut,
     scratch: Scratch,
@@ -57,51 +89,298 @@ impl Default for Tasks {
     }
 }
 
-#[derive(Debug)]
-pub struct LoadedChunk {
-    pub raw: Bytes,
+/// Where the blocks of a [`Chunks`] come from.
+pub enum ChunkSource {
+    /// The Anvil save in the given directory.
+    Anvil(PathBuf),
+    /// Every chunk is empty.
+    Void,
 }
 
-#[derive(Component)]
+impl ChunkSource {
+    /// The `NewYork` map, which is downloaded if it is not already.
+    pub fn new_york() -> anyhow::Result<Self> {
+        let save = get_nyc_save().context("failed to get anvil data")?;
+        Ok(Self::Anvil(save))
+    }
+}
+
+#[derive(Component, Clone)]
 pub struct Chunks {
     inner: Arc<ChunksInner>,
 }
 
 impl Chunks {
-    pub fn new(registry: &BiomeRegistry) -> anyhow::Result<Self> {
-        let inner = ChunksInner::new(registry)?;
+    pub fn new(registry: &BiomeRegistry, source: ChunkSource) -> anyhow::Result<Self> {
+        let inner = ChunksInner::new(registry, source)?;
         Ok(Self {
             inner: Arc::new(inner),
         })
     }
 }
 
+/// The parsed blocks of a chunk column. This is `None` if the column does not exist in the save.
+type BlockColumn = Option<Arc<RwLock<Arc<UnloadedChunk>>>>;
+
+/// How long to wait before trying to load a chunk again after it failed to load.
+const RETRY_FAILED_AFTER: Duration = Duration::from_secs(30);
+
 pub struct ChunksInner {
-    // todo: impl more efficient (probably lru) cache
-    cache: DashMap<I16Vec2, LoadedChunk, FxBuildHasher>,
+    /// The encoded packets of columns and their blocks, which lighting needs for neighboring
+    /// columns and world mutations need to mutate. Which blocks are solid is built from the
+    /// blocks the first time it is needed for entity collision.
+    cache: ChunkCache<BlockColumn>,
     loading: DashSet<I16Vec2, FxBuildHasher>,
-    regions: Regions,
+    /// Chunks which failed to load and when they failed. These are not cached so they are
+    /// retried, but not before [`RETRY_FAILED_AFTER`].
+    failed: DashMap<I16Vec2, Instant, FxBuildHasher>,
+    /// Columns whose blocks are being parsed, so each is only parsed once at a time.
+    parsing: DashMap<I16Vec2, Arc<OnceCell<BlockColumn>>, FxBuildHasher>,
+    /// How many times blocks of a column have been changed. Columns which were never changed are
+    /// missing.
+    revisions: DashMap<I16Vec2, u64, FxBuildHasher>,
+    /// Columns around changed blocks which need to be relit.
+    dirty: Mutex<FxHashSet<I16Vec2>>,
+    /// Encoded `LightUpdateS2c` packets which are waiting to be broadcast.
+    light_updates: Mutex<Vec<Bytes>>,
+    /// The regions of the save, or `None` if this is a void world.
+    regions: Option<Regions>,
     biome_to_id: BTreeMap<Ident<String>, BiomeId>,
 }
 
-impl ChunksInner {}
-
 impl ChunksInner {
-    pub fn new(biomes: &BiomeRegistry) -> anyhow::Result<Self> {
-        let regions = Regions::new().context("failed to get anvil data")?;
+    pub fn new(biomes: &BiomeRegistry, source: ChunkSource) -> anyhow::Result<Self> {
+        let regions = match source {
+            ChunkSource::Anvil(save) => Some(Regions::new(&save)),
+            ChunkSource::Void => None,
+        };
 
         let biome_to_id = biomes
             .iter()
             .map(|(id, name, _)| (name.to_string_ident(), id))
             .collect();
 
+        let spawn = PLAYER_SPAWN_POSITION.as_ivec3() >> 4;
+        let spawn = I16Vec2::new(spawn.x as i16, spawn.z as i16);
+
+        let cache = ChunkCache::new(
+            CONFIG.chunk_cache_bytes,
+            spawn,
+            CONFIG.protected_spawn_radius as i16,
+        );
+
         Ok(Self {
-            cache: default(),
+            cache,
             loading: default(),
+            failed: default(),
+            parsing: default(),
+            revisions: default(),
+            dirty: default(),
+            light_updates: default(),
             regions,
             biome_to_id,
         })
     }
+
+    /// Gets the blocks of a column, parsing them from the save if they have not been loaded yet.
+    ///
+    /// Returns `Ok(None)` if the column does not exist. Errors are not remembered, so loading the
+    /// column is attempted again on the next call.
+    async fn load_blocks(&self, position: I16Vec2) -> anyhow::Result<Option<Arc<UnloadedChunk>>> {
+        let column = match self.cache.blocks(position) {
+            Some(column) => column,
+            None => {
+                let parsing = self.parsing.entry(position).or_default().clone();
+
+                let column = parsing
+                    .get_or_try_init(|| async {
+                        let chunk = self.parse_blocks(position).await?;
+                        let bytes = chunk.as_ref().map_or(0, block_bytes);
+                        let column = chunk.map(|chunk| Arc::new(RwLock::new(Arc::new(chunk))));
+
+                        self.cache.insert_blocks(position, column.clone(), bytes);
+                        self.parsing.remove(&position);
+
+                        anyhow::Ok(column)
+                    })
+                    .await?;
+
+                column.clone()
+            }
+        };
+
+        Ok(column.map(|blocks| blocks.read().clone()))
+    }
+
+    async fn parse_blocks(&self, position: I16Vec2) -> anyhow::Result<Option<UnloadedChunk>> {
+        let Some(regions) = &self.regions else {
+            return Ok(Some(UnloadedChunk::with_height(SECTION_COUNT as u32 * 16)));
+        };
+
+        let mut decompress_buf = vec![0; 1024 * 1024];
+
+        // https://rust-lang.github.io/rust-clippy/master/index.html#/large_futures
+        let region = regions
+            .get_region_from_chunk(i32::from(position.x), i32::from(position.y))
+            .await;
+
+        let raw_chunk = {
+            let mut region_access = region.lock().await;
+
+            region_access
+                .get_chunk(
+                    i32::from(position.x),
+                    i32::from(position.y),
+                    &mut decompress_buf,
+                    regions.root(),
+                )
+                .await
+        };
+
+        let Some(raw_chunk) = raw_chunk.context("failed to read chunk")? else {
+            return Ok(None);
+        };
+
+        let chunk =
+            parse_chunk(raw_chunk.data, &self.biome_to_id).context("failed to parse chunk")?;
+
+        Ok(Some(chunk))
+    }
+
+    /// Computes the light of a column. The blocks of the neighboring columns are loaded as well
+    /// so light can cross chunk borders. They go through the chunk cache like any other column,
+    /// so relighting usually does not parse them again and they are evicted once unused.
+    async fn light(&self, position: I16Vec2) -> anyhow::Result<ColumnLight> {
+        let mut window: [Option<Arc<UnloadedChunk>>; 9] = default();
+
+        for (i, column) in window.iter_mut().enumerate() {
+            let dx = (i % 3) as i16 - 1;
+            let dz = (i / 3) as i16 - 1;
+            *column = self.load_blocks(position + I16Vec2::new(dx, dz)).await?;
+        }
+
+        let window = window.each_ref().map(Option::as_deref);
+
+        Ok(LightGrid::new(&window).light())
+    }
+
+    /// Loads, lights and encodes a column into the packet cache.
+    ///
+    /// Columns which do not exist are cached as empty packets. Columns which fail to load are not
+    /// cached at all.
+    async fn load_packet(&self, position: I16Vec2) {
+        match self.try_load_packet(position).await {
+            Ok(()) => {
+                self.failed.remove(&position);
+            }
+            Err(err) => {
+                error!("failed to load chunk {position:?}: {err:?}");
+                self.failed.insert(position, Instant::now());
+            }
+        }
+    }
+
+    async fn try_load_packet(&self, position: I16Vec2) -> anyhow::Result<()> {
+        let Some(chunk) = self.load_blocks(position).await? else {
+            self.cache.insert(position, Bytes::new());
+            return Ok(());
+        };
+
+        let light = self.light(position).await?;
+
+        let bytes = STATE.with_borrow_mut(|state| {
+            encode_chunk_packet(&chunk, &light, position, state).map(|bytes| bytes.freeze())
+        })?;
+
+        self.cache.insert(position, bytes);
+
+        Ok(())
+    }
+
+    /// The sum of the revisions of a column and its neighbors, which changes whenever a block the
+    /// light of the column depends on changes.
+    fn light_revision(&self, position: I16Vec2) -> u64 {
+        let mut revision = 0;
+
+        for dz in -1..=1 {
+            for dx in -1..=1 {
+                let neighbor = position + I16Vec2::new(dx, dz);
+                revision += self
+                    .revisions
+                    .get(&neighbor)
+                    .map_or(0, |revision| *revision);
+            }
+        }
+
+        revision
+    }
+
+    /// Relights a column, replaces its cached packet and queues a `LightUpdateS2c` for it.
+    ///
+    /// Relights can finish out of order, so the result is dropped if blocks around the column
+    /// changed while it was computed. The column is marked dirty again by that change, so it is
+    /// relit with the newer blocks.
+    async fn relight(&self, position: I16Vec2) {
+        let revision = self.light_revision(position);
+
+        let packets = async {
+            let Some(chunk) = self.load_blocks(position).await? else {
+                return anyhow::Ok(None);
+            };
+
+            let light = self.light(position).await?;
+
+            STATE.with_borrow_mut(|state| {
+                let chunk = encode_chunk_packet(&chunk, &light, position, state)?.freeze();
+                let light = encode_light_packet(&light, position, state)?.freeze();
+                anyhow::Ok(Some((chunk, light)))
+            })
+        };
+
+        let (chunk, light) = match packets.await {
+            Ok(Some(packets)) => packets,
+            Ok(None) => return,
+            Err(err) => {
+                error!("failed to relight {position:?}: {err:?}");
+                return;
+            }
+        };
+
+        // checked under the lock so a newer result is never overwritten by an older one
+        let mut light_updates = self.light_updates.lock();
+
+        if self.light_revision(position) != revision {
+            return;
+        }
+
+        self.cache.insert(position, chunk);
+        light_updates.push(light);
+    }
+
+    /// Re-encodes the cached packet of a column with its current block entities. Block entities
+    /// never change light, so unlike [`ChunksInner::load_packet`] this keeps the light of the
+    /// cached packet instead of relighting the column.
+    fn update_block_entities(&self, position: I16Vec2) -> anyhow::Result<()> {
+        // columns which are not cached are encoded with their current block entities when they
+        // are loaded again
+        let Some(cached) = self.cache.peek(position) else {
+            return Ok(());
+        };
+
+        let Some(Some(blocks)) = self.cache.blocks(position) else {
+            return Ok(());
+        };
+
+        let chunk = blocks.read().clone();
+
+        let bytes = STATE.with_borrow_mut(|state| {
+            replace_block_entities(&cached, &chunk, state).map(|bytes| bytes.freeze())
+        })?;
+
+        self.cache.insert(position, bytes);
+
+        Ok(())
+    }
 }
 
 thread_local! {
@@ -115,29 +394,40 @@ pub enum ChunkData {
 
 impl Chunks {
     /// todo: doesn't work in loading state
-    #[allow(clippy::missing_panics_doc, reason = "todo use unwrap unchecked")]
     pub fn get_and_wait(&self, position: I16Vec2, tasks: &Tasks) -> anyhow::Result<Option<Bytes>> {
         let result = match self.get_cached_or_load(position, tasks)? {
             None => None,
             Some(ChunkData::Cached(data)) => Some(data),
             Some(ChunkData::Task(handle)) => {
                 tasks.block_on(handle)?;
-                let res = self.inner.cache.get(&position).unwrap().raw.clone();
-                Some(res)
+                // this is `None` if loading failed
+                self.inner.cache.get(position)
             }
         };
 
         Ok(result)
     }
 
+    /// Hit, miss and eviction counters of the chunk packet cache.
+    #[must_use]
+    pub fn cache_stats(&self) -> CacheStats {
+        self.inner.cache.stats()
+    }
+
     #[instrument(skip_all, level = "trace")]
     pub fn get_cached_or_load(
         &self,
         position: I16Vec2,
         tasks: &Tasks,
     ) -> anyhow::Result<Option<ChunkData>> {
-        if let Some(result) = self.inner.cache.get(&position) {
-            return Ok(Some(ChunkData::Cached(result.raw.clone())));
+        if let Some(result) = self.inner.cache.get(position) {
+            return Ok(Some(ChunkData::Cached(result)));
+        }
+
+        if let Some(failed_at) = self.inner.failed.get(&position) {
+            if failed_at.elapsed() < RETRY_FAILED_AFTER {
+                return Ok(None);
+            }
         }
 
         if !self.inner.loading.insert(position) {
@@ -147,94 +437,398 @@ impl Chunks {
         let inner = self.inner.clone();
 
         let handle = tasks.spawn(async move {
-            let mut decompress_buf = vec![0; 1024 * 1024];
-
-            // https://rust-lang.github.io/rust-clippy/master/index.html#/large_futures
-            let region = inner
-                .regions
-                .get_region_from_chunk(i32::from(position.x), i32::from(position.y))
-                .await;
-
-            let raw_chunk = {
-                let mut region_access = region.lock().await;
-
-                region_access
-                    .get_chunk(
-                        i32::from(position.x),
-                        i32::from(position.y),
-                        &mut decompress_buf,
-                        inner.regions.root(),
-                    )
-                    .await
-                    .unwrap()
-                    .unwrap()
-            };
+            inner.load_packet(position).await;
 
-            let Ok(chunk) = parse_chunk(raw_chunk.data, &inner.biome_to_id) else {
-                error!("failed to parse chunk {position:?}");
-                inner
-                    .cache
-                    .insert(position, LoadedChunk { raw: Bytes::new() });
+            let present = inner.loading.remove(&position);
 
-                inner.loading.remove(&position);
+            debug_assert!(present.is_some());
+        });
 
-                return;
-            };
+        Ok(Some(ChunkData::Task(handle)))
+    }
 
-            STATE.with_borrow_mut(|state| {
-                let Ok(Some(bytes)) = encode_chunk_packet(&chunk, position, state) else {
-                    inner
-                        .cache
-                        .insert(position, LoadedChunk { raw: Bytes::new() });
+    /// Sets the block at the given position. The column and its neighbors are marked to be relit
+    /// by the next call to [`Chunks::relight`].
+    ///
+    /// Returns the previous block, or `None` if the column is not loaded.
+    pub fn set_block(&self, position: BlockPos, state: BlockState) -> Option<BlockState> {
+        let y = position.y - MIN_Y;
+        if !(0..SECTION_COUNT as i32 * 16).contains(&y) {
+            return None;
+        }
+
+        let column_pos = column_of(position);
 
-                    inner.loading.remove(&position);
-                    return;
-                };
+        // the column is pinned as changed blocks cannot be loaded from the save again. columns
+        // which do not exist cannot be changed, so they are not pinned.
+        self.inner.cache.blocks(column_pos).flatten()?;
+        let blocks = self.inner.cache.pin_blocks(column_pos).flatten()?;
 
-                inner.cache.insert(position, LoadedChunk {
-                    raw: bytes.freeze(),
-                });
+        let previous = {
+            let mut blocks = blocks.write();
+            let chunk = Arc::make_mut(&mut blocks);
 
-                let present = inner.loading.remove(&position);
+            let (section, idx) = section_index(position, y);
+            let previous = chunk.sections[section].block_states.set(idx, state);
+
+            if state.block_entity_kind().is_none() {
+                chunk.block_entities.remove(&column_index(position, y));
+            }
+
+            previous
+        };
+
+        let (x, y, z) = column_offset(position, y);
+        self.inner.cache.update_solids(column_pos, |solids| {
+            solids.set(x, y, z, state.blocks_motion())
+        });
+
+        if previous == state {
+            return Some(previous);
+        }
+
+        *self.inner.revisions.entry(column_pos).or_default() += 1;
+
+        let mut dirty = self.inner.dirty.lock();
+
+        for dz in -1..=1 {
+            for dx in -1..=1 {
+                dirty.insert(column_pos + I16Vec2::new(dx, dz));
+            }
+        }
+
+        drop(dirty);
+
+        Some(previous)
+    }
+
+    /// Relights the columns around the blocks set since the last call in the background. Each
+    /// column is relit once however many of its blocks changed, and the resulting
+    /// `LightUpdateS2c` packets can be obtained with [`Chunks::drain_light_updates`].
+    pub fn relight(&self, tasks: &Tasks) {
+        let dirty = std::mem::take(&mut *self.inner.dirty.lock());
+
+        for position in dirty {
+            // only columns which have been sent need new light
+            if !self.inner.cache.contains(position) {
+                continue;
+            }
+
+            let inner = self.inner.clone();
+
+            tasks.spawn(async move { inner.relight(position).await });
+        }
+    }
+
+    /// Sets the data of the block entity at the given position. The chunk packet is re-encoded in
+    /// the background so players loading the chunk later see the new data. The column is not
+    /// relit, as block entities do not change light.
+    ///
+    /// Returns the kind of the block entity, or `None` if the column is not loaded or the block
+    /// there cannot have a block entity.
+    pub fn set_block_entity(
+        &self,
+        position: BlockPos,
+        data: Compound,
+        tasks: &Tasks,
+    ) -> Option<BlockEntityKind> {
+        let y = position.y - MIN_Y;
+        if !(0..SECTION_COUNT as i32 * 16).contains(&y) {
+            return None;
+        }
+
+        let column_pos = column_of(position);
+
+        // pinned like in `set_block`, as the data cannot be loaded from the save again
+        self.inner.cache.blocks(column_pos).flatten()?;
+        let blocks = self.inner.cache.pin_blocks(column_pos).flatten()?;
+
+        let kind = {
+            let mut blocks = blocks.write();
+            let chunk = Arc::make_mut(&mut blocks);
+
+            let (section, idx) = section_index(position, y);
+            let kind = chunk.sections[section]
+                .block_states
+                .get(idx)
+                .block_entity_kind()?;
+
+            chunk.block_entities.insert(column_index(position, y), data);
+
+            kind
+        };
 
-                debug_assert!(present.is_some());
+        if self.inner.cache.contains(column_pos) {
+            let inner = self.inner.clone();
+
+            tasks.spawn(async move {
+                if let Err(err) = inner.update_block_entities(column_pos) {
+                    error!("failed to update block entities of {column_pos:?}: {err:?}");
+                }
             });
+        }
+
+        Some(kind)
+    }
+
+    /// Which blocks of a column are solid, or `None` if the blocks of the column have not been
+    /// loaded. This never loads a column and does not count as a use of it.
+    #[must_use]
+    pub fn solid_column(&self, position: I16Vec2) -> Option<Arc<SolidColumn>> {
+        if let Some(solids) = self.inner.cache.peek_solids(position) {
+            return Some(solids);
+        }
+
+        let Some(blocks) = self.inner.cache.peek_blocks(position)? else {
+            // the column does not exist in the save
+            return self
+                .inner
+                .cache
+                .insert_solids(position, SolidColumn::empty(SECTION_COUNT));
+        };
+
+        // the blocks stay locked until the solids are cached, so a block set in the meantime
+        // updates them instead of being missed
+        let chunk = blocks.read();
+
+        let solids = SolidColumn::from_fn(chunk.sections.len(), |section, idx| {
+            chunk.sections[section]
+                .block_states
+                .get(idx)
+                .blocks_motion()
         });
 
-        Ok(Some(ChunkData::Task(handle)))
+        // this is `None` if the column was evicted in the meantime
+        let solids = self.inner.cache.insert_solids(position, solids);
+
+        drop(chunk);
+
+        solids
+    }
+
+    /// How many times blocks of a column have been changed with [`Chunks::set_block`].
+    #[must_use]
+    pub fn revision(&self, position: I16Vec2) -> u64 {
+        self.inner
+            .revisions
+            .get(&position)
+            .map_or(0, |revision| *revision)
+    }
+
+    /// Takes all `LightUpdateS2c` packets that have been encoded since the last call.
+    #[must_use]
+    pub fn drain_light_updates(&self) -> Vec<Bytes> {
+        std::mem::take(&mut *self.inner.light_updates.lock())
+    }
+}
+
+/// Which blocks are solid in the columns around the entities of an instance. This is taken once
+/// per tick, so systems can look up blocks from many threads without locking the chunk cache.
+#[derive(Component, Default)]
+pub struct SolidSnapshot {
+    columns: FxHashMap<I16Vec2, Arc<SolidColumn>>,
+}
+
+impl SolidSnapshot {
+    /// Takes the solid blocks of `columns` from `chunks`. Columns which are not loaded are left
+    /// out.
+    pub fn new(chunks: &Chunks, columns: impl IntoParallelIterator<Item = I16Vec2>) -> Self {
+        let columns = columns
+            .into_par_iter()
+            .filter_map(|position| Some((position, chunks.solid_column(position)?)))
+            .collect();
+
+        Self { columns }
+    }
+
+    /// Whether the column is in the snapshot.
+    #[must_use]
+    pub fn contains(&self, position: I16Vec2) -> bool {
+        self.columns.contains_key(&position)
+    }
+}
+
+/// Looks up whether blocks are solid in a [`SolidSnapshot`], remembering the last column used.
+pub struct SolidBlocks<'a> {
+    snapshot: &'a SolidSnapshot,
+    last: Option<(I16Vec2, Option<&'a SolidColumn>)>,
+    /// Whether blocks in columns which are not in the snapshot count as solid.
+    unloaded: bool,
+}
+
+impl<'a> SolidBlocks<'a> {
+    /// Blocks in columns which are not in the snapshot count as solid so entities do not walk
+    /// into them.
+    #[must_use]
+    pub const fn new(snapshot: &'a SolidSnapshot) -> Self {
+        Self {
+            snapshot,
+            last: None,
+            unloaded: true,
+        }
+    }
+
+    /// Blocks in columns which are not in the snapshot count as air, for things which fly rather
+    /// than walk.
+    #[must_use]
+    pub const fn unloaded_as_air(snapshot: &'a SolidSnapshot) -> Self {
+        Self {
+            snapshot,
+            last: None,
+            unloaded: false,
+        }
+    }
+
+    pub fn is_solid(&mut self, block: IVec3) -> bool {
+        let y = block.y - MIN_Y;
+
+        // the void is not solid, but nothing falls below the bottom of the world
+        if y < 0 {
+            return true;
+        }
+
+        let column = I16Vec2::new((block.x >> 4) as i16, (block.z >> 4) as i16);
+
+        let solids = match self.last {
+            Some((position, solids)) if position == column => solids,
+            _ => {
+                let solids = self.snapshot.columns.get(&column).map(Arc::as_ref);
+                self.last = Some((column, solids));
+                solids
+            }
+        };
+
+        let Some(solids) = solids else {
+            return self.unloaded;
+        };
+
+        let (x, y, z) = column_offset(BlockPos::new(block.x, block.y, block.z), y);
+        solids.get(x, y, z)
+    }
+}
+
+/// The column containing a block.
+const fn column_of(position: BlockPos) -> I16Vec2 {
+    I16Vec2::new((position.x >> 4) as i16, (position.z >> 4) as i16)
+}
+
+/// The section and index within the section of a block, where `y` is relative to the bottom of
+/// the world.
+#[expect(
+    clippy::cast_sign_loss,
+    reason = "all values are checked to be positive"
+)]
+const fn section_index(position: BlockPos, y: i32) -> (usize, usize) {
+    let idx = position.x.rem_euclid(16) + position.z.rem_euclid(16) * 16 + (y % 16) * 256;
+    ((y / 16) as usize, idx as usize)
+}
+
+/// The coordinates of a block within its column, where `y` is relative to the bottom of the
+/// world.
+#[expect(
+    clippy::cast_sign_loss,
+    reason = "all values are checked to be positive"
+)]
+const fn column_offset(position: BlockPos, y: i32) -> (usize, usize, usize) {
+    (
+        position.x.rem_euclid(16) as usize,
+        y as usize,
+        position.z.rem_euclid(16) as usize,
+    )
+}
+
+/// The index of a block within its column, which is how `UnloadedChunk` keys block entities.
+#[expect(
+    clippy::cast_sign_loss,
+    reason = "all values are checked to be positive"
+)]
+const fn column_index(position: BlockPos, y: i32) -> u32 {
+    (position.x.rem_euclid(16) + position.z.rem_euclid(16) * 16 + y * 256) as u32
+}
+
+/// Roughly how many bytes the blocks of a column use. Block states are stored with as few bits
+/// per block as their palette allows, which is also how they are encoded for packets, so the
+/// encoded size is used.
+fn block_bytes(chunk: &UnloadedChunk) -> usize {
+    let mut count = ByteCount::default();
+
+    for section in &chunk.sections {
+        write_block_states(&section.block_states, &mut count).unwrap();
+    }
+
+    std::mem::size_of::<UnloadedChunk>() + std::mem::size_of_val(&*chunk.sections) + count.0
+}
+
+/// A writer which only counts how many bytes are written to it.
+#[derive(Default)]
+struct ByteCount(usize);
+
+impl Write for ByteCount {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
     }
 }
 
+/// The block entities of a chunk in the layout used by `ChunkDataS2c`. Block entities whose
+/// block cannot have one are skipped.
+fn block_entities(chunk: &UnloadedChunk) -> Vec<ChunkDataBlockEntity<'_>> {
+    chunk
+        .block_entities
+        .iter()
+        .filter_map(|(&idx, data)| {
+            let x = idx % 16;
+            let z = (idx / 16) % 16;
+            let y = idx / 256;
+
+            let section = chunk.sections.get(y as usize / 16)?;
+            let state = section
+                .block_states
+                .get((x + z * 16 + (y % 16) * 256) as usize);
+
+            Some(ChunkDataBlockEntity {
+                packed_xz: ((x << 4) | z) as i8,
+                y: y as i16 + MIN_Y as i16,
+                kind: state.block_entity_kind()?,
+                data: Cow::Borrowed(data),
+            })
+        })
+        .collect()
+}
+
 #[instrument(skip_all, level = "trace", fields(location = ?location))]
 fn encode_chunk_packet(
     chunk: &UnloadedChunk,
+    light: &ColumnLight,
     location: I16Vec2,
     state: &mut TasksState,
-) -> anyhow::Result<Option<BytesMut>> {
+) -> anyhow::Result<BytesMut> {
     let encoder = PacketEncoder::new(CompressionThreshold::from(6));
 
-    let section_count = 384 / 16_usize;
-    let dimension_height = 384;
-
-    let map = heightmap(dimension_height, dimension_height - 3);
-    let map = map.into_iter().map(i64::try_from).try_collect()?;
+    let heightmaps = Heightmaps::new(chunk);
+    let motion_blocking = heightmaps
+        .motion_blocking
+        .into_iter()
+        .map(i64::try_from)
+        .try_collect()?;
+    let world_surface = heightmaps
+        .world_surface
+        .into_iter()
+        .map(i64::try_from)
+        .try_collect()?;
 
-    // convert section_count + 2 0b1s into `u64` array
-    let mut bits = BitStorage::new(1, section_count + 2, None).unwrap();
-
-    for i in 0..section_count + 2 {
-        bits.set(i, 1);
-    }
-
-    // 2048 bytes per section -> long count = 2048 / 8 = 256
-    let sky_light_array = FixedArray([0xFF_u8; 2048]);
-    let sky_light_arrays = vec![sky_light_array; section_count + 2];
+    let light = light.data();
 
     let mut section_bytes = Vec::new();
 
     for section in &chunk.sections {
-        let non_air_blocks: u16 = 42;
-        non_air_blocks.encode(&mut section_bytes).unwrap();
+        non_air_blocks(&section.block_states)
+            .encode(&mut section_bytes)
+            .unwrap();
 
         write_block_states(&section.block_states, &mut section_bytes).unwrap();
         write_biomes(&section.biomes, &mut section_bytes).unwrap();
@@ -243,26 +837,82 @@ fn encode_chunk_packet(
     let pkt = play::ChunkDataS2c {
         pos: ChunkPos::new(i32::from(location.x), i32::from(location.y)),
         heightmaps: Cow::Owned(compound! {
-            "MOTION_BLOCKING" => List::Long(map),
+            "MOTION_BLOCKING" => List::Long(motion_blocking),
+            "WORLD_SURFACE" => List::Long(world_surface),
         }),
         blocks_and_biomes: &section_bytes,
-        block_entities: Cow::Borrowed(&[]),
-
-        sky_light_mask: Cow::Owned(bits.into_data()),
-        block_light_mask: Cow::Borrowed(&[]),
-        empty_sky_light_mask: Cow::Borrowed(&[]),
-        empty_block_light_mask: Cow::Borrowed(&[]),
-        sky_light_arrays: Cow::Owned(sky_light_arrays),
-        block_light_arrays: Cow::Borrowed(&[]),
+        block_entities: Cow::Owned(block_entities(chunk)),
+
+        sky_light_mask: Cow::Borrowed(&light.sky_light_mask),
+        block_light_mask: Cow::Borrowed(&light.block_light_mask),
+        empty_sky_light_mask: Cow::Borrowed(&light.empty_sky_light_mask),
+        empty_block_light_mask: Cow::Borrowed(&light.empty_block_light_mask),
+        sky_light_arrays: Cow::Borrowed(&light.sky_light_arrays),
+        block_light_arrays: Cow::Borrowed(&light.block_light_arrays),
     };
 
     let buf = &mut state.bytes;
     let scratch = &mut state.scratch;
     let compressor = &mut state.compressor;
 
-    let result = encoder.append_packet(&pkt, buf, scratch, compressor)?;
+    encoder.append_packet(&pkt, buf, scratch, compressor)
+}
 
-    Ok(Some(result))
+/// Replaces the block entities of an encoded `ChunkDataS2c` packet with those of `chunk`, keeping
+/// everything else of the packet.
+fn replace_block_entities(
+    encoded: &[u8],
+    chunk: &UnloadedChunk,
+    state: &mut TasksState,
+) -> anyhow::Result<BytesMut> {
+    let threshold = CompressionThreshold::from(6);
+
+    let mut decoder = PacketDecoder::default();
+    decoder.set_compression(threshold);
+    decoder.queue_slice(encoded);
+
+    let frame = decoder
+        .try_next_packet(&mut state.scratch)?
+        .context("incomplete chunk packet")?;
+
+    let mut pkt: play::ChunkDataS2c<'_> = frame.decode()?;
+    pkt.block_entities = Cow::Owned(block_entities(chunk));
+
+    let encoder = PacketEncoder::new(threshold);
+
+    let buf = &mut state.bytes;
+    let scratch = &mut state.scratch;
+    let compressor = &mut state.compressor;
+
+    encoder.append_packet(&pkt, buf, scratch, compressor)
+}
+
+#[instrument(skip_all, level = "trace", fields(location = ?location))]
+fn encode_light_packet(
+    light: &ColumnLight,
+    location: I16Vec2,
+    state: &mut TasksState,
+) -> anyhow::Result<BytesMut> {
+    let encoder = PacketEncoder::new(CompressionThreshold::from(6));
+
+    let light = light.data();
+
+    let pkt = play::LightUpdateS2c {
+        chunk_x: i32::from(location.x).into(),
+        chunk_z: i32::from(location.y).into(),
+        sky_light_mask: Cow::Borrowed(&light.sky_light_mask),
+        block_light_mask: Cow::Borrowed(&light.block_light_mask),
+        empty_sky_light_mask: Cow::Borrowed(&light.empty_sky_light_mask),
+        empty_block_light_mask: Cow::Borrowed(&light.empty_block_light_mask),
+        sky_light_arrays: Cow::Borrowed(&light.sky_light_arrays),
+        block_light_arrays: Cow::Borrowed(&light.block_light_arrays),
+    };
+
+    let buf = &mut state.bytes;
+    let scratch = &mut state.scratch;
+    let compressor = &mut state.compressor;
+
+    encoder.append_packet(&pkt, buf, scratch, compressor)
 }
 
 fn write_block_states(states: &BlockStateContainer, writer: &mut impl Write) -> anyhow::Result<()> {
@@ -286,3 +936,94 @@ fn write_biomes(biomes: &BiomeContainer, writer: &mut impl Write) -> anyhow::Res
     )?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::decode::PacketFrame;
+    use valence_server::layer::chunk::Chunk;
+
+    use super::*;
+    use crate::util::sign::sign;
+
+    /// The index of the sign placed by [`with_sign`].
+    const SIGN: u32 = 3 + 5 * 16 + 70 * 256;
+
+    /// A column with a sign at x 3, z 5 and 70 blocks above the bottom of the world.
+    fn with_sign() -> UnloadedChunk {
+        let mut chunk = UnloadedChunk::with_height(SECTION_COUNT as u32 * 16);
+        chunk.set_block_state(3, 70, 5, BlockState::OAK_SIGN);
+        chunk
+    }
+
+    fn decode_frame(encoded: &[u8], state: &mut TasksState) -> PacketFrame {
+        let mut decoder = PacketDecoder::default();
+        decoder.set_compression(CompressionThreshold::from(6));
+        decoder.queue_slice(encoded);
+        decoder
+            .try_next_packet(&mut state.scratch)
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn unloaded_columns_are_only_solid_for_walking() {
+        let snapshot = SolidSnapshot::default();
+        let block = IVec3::new(0, 100, 0);
+
+        assert!(SolidBlocks::new(&snapshot).is_solid(block));
+        assert!(!SolidBlocks::unloaded_as_air(&snapshot).is_solid(block));
+    }
+
+    #[test]
+    fn block_entities_are_encoded_with_their_position() {
+        let mut chunk = with_sign();
+
+        let data = sign(["a", "b", "c", "d"]);
+        chunk.block_entities.insert(SIGN, data.clone());
+
+        // air cannot have a block entity
+        chunk.block_entities.insert(0, Compound::new());
+
+        let entities = block_entities(&chunk);
+
+        assert_eq!(entities.len(), 1);
+        assert_eq!(entities[0].packed_xz, (3 << 4) | 5);
+        assert_eq!(entities[0].y, 70 + MIN_Y as i16);
+        assert_eq!(entities[0].kind, BlockEntityKind::Sign);
+        assert_eq!(*entities[0].data, data);
+    }
+
+    #[test]
+    fn block_entities_are_replaced_in_encoded_packets() {
+        let mut state = TasksState::default();
+        let mut chunk = with_sign();
+
+        let light = LightGrid::empty().light();
+        let encoded = encode_chunk_packet(&chunk, &light, I16Vec2::new(2, -3), &mut state)
+            .unwrap()
+            .freeze();
+
+        let data = sign(["", "hello", "", ""]);
+        chunk.block_entities.insert(SIGN, data.clone());
+
+        let replaced = replace_block_entities(&encoded, &chunk, &mut state).unwrap();
+
+        let before = decode_frame(&encoded, &mut state);
+        let before: play::ChunkDataS2c<'_> = before.decode().unwrap();
+        let after = decode_frame(&replaced, &mut state);
+        let after: play::ChunkDataS2c<'_> = after.decode().unwrap();
+
+        assert!(before.block_entities.is_empty());
+        assert_eq!(after.block_entities.len(), 1);
+        assert_eq!(*after.block_entities[0].data, data);
+
+        // everything else, including the light, is kept
+        assert_eq!(after.pos, before.pos);
+        assert_eq!(after.heightmaps, before.heightmaps);
+        assert_eq!(after.blocks_and_biomes, before.blocks_and_biomes);
+        assert_eq!(after.sky_light_mask, before.sky_light_mask);
+        assert_eq!(after.block_light_mask, before.block_light_mask);
+        assert_eq!(after.sky_light_arrays, before.sky_light_arrays);
+        assert_eq!(after.block_light_arrays, before.block_light_arrays);
+    }
+}
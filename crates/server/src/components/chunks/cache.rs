@@ -0,0 +1,526 @@
+//! A bounded cache of chunk columns.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::BuildHasher,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use bytes::Bytes;
+use fxhash::FxBuildHasher;
+use glam::I16Vec2;
+use parking_lot::Mutex;
+
+use super::collision::SolidColumn;
+
+/// The number of shards. Each shard has its own lock and its own share of the byte budget.
+const SHARD_COUNT: usize = 32;
+
+/// Everything cached of one column. The parts are evicted together, so a column is either fully
+/// loaded or has to be loaded again.
+struct Entry<B> {
+    /// The encoded `ChunkDataS2c` packet.
+    packet: Option<Bytes>,
+    /// The decoded blocks and how many bytes they use.
+    blocks: Option<(B, usize)>,
+    /// Which blocks are solid, built from `blocks`.
+    solids: Option<Arc<SolidColumn>>,
+    /// When the entry was last used. This is also its key in [`Shard::recency`] unless the entry
+    /// is not evictable.
+    last_used: u64,
+    /// Whether the entry was pinned with [`ChunkCache::pin_blocks`].
+    pinned: bool,
+}
+
+impl<B> Entry<B> {
+    const fn new(last_used: u64) -> Self {
+        Self {
+            packet: None,
+            blocks: None,
+            solids: None,
+            last_used,
+            pinned: false,
+        }
+    }
+
+    const fn is_empty(&self) -> bool {
+        self.packet.is_none() && self.blocks.is_none() && self.solids.is_none() && !self.pinned
+    }
+
+    fn bytes(&self) -> usize {
+        self.packet.as_ref().map_or(0, Bytes::len)
+            + self.blocks.as_ref().map_or(0, |(_, bytes)| *bytes)
+            + self.solids.as_ref().map_or(0, |solids| solids.bytes())
+    }
+}
+
+struct Shard<B> {
+    entries: HashMap<I16Vec2, Entry<B>, FxBuildHasher>,
+    /// Evictable entries from least to most recently used.
+    recency: BTreeMap<u64, I16Vec2>,
+    /// The bytes used by evictable entries.
+    bytes: usize,
+}
+
+impl<B> Default for Shard<B> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::default(),
+            recency: BTreeMap::new(),
+            bytes: 0,
+        }
+    }
+}
+
+/// Counters describing how well the cache is doing.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub entries: usize,
+    /// The bytes used by all entries, including the ones which are never evicted.
+    pub bytes: usize,
+}
+
+/// A sharded, concurrent cache of chunk columns which evicts the least recently used columns
+/// once the byte budget is exceeded.
+///
+/// For each column this holds the encoded packet, the decoded blocks `B` and which blocks are
+/// solid, and all of them count towards the budget. Columns close to spawn are protected: they
+/// are never evicted and do not count towards the budget, as every joining player needs them.
+/// Pinned columns are treated the same way.
+pub struct ChunkCache<B> {
+    shards: Box<[Mutex<Shard<B>>]>,
+    shard_budget: usize,
+    spawn: I16Vec2,
+    spawn_radius: i16,
+    clock: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl<B: Clone> ChunkCache<B> {
+    #[must_use]
+    pub fn new(budget: usize, spawn: I16Vec2, spawn_radius: i16) -> Self {
+        let shards = (0..SHARD_COUNT).map(|_| Mutex::default()).collect();
+
+        Self {
+            shards,
+            shard_budget: budget / SHARD_COUNT,
+            spawn,
+            spawn_radius,
+            clock: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    fn shard(&self, position: I16Vec2) -> &Mutex<Shard<B>> {
+        let idx = FxBuildHasher::default().hash_one(position) as usize % SHARD_COUNT;
+        &self.shards[idx]
+    }
+
+    fn is_protected(&self, position: I16Vec2) -> bool {
+        let offset = (position - self.spawn).abs();
+        offset.x <= self.spawn_radius && offset.y <= self.spawn_radius
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Calls `read` with the entry of a column and marks it as recently used. `read` is called
+    /// with `None` if the column is not cached.
+    fn read<T>(&self, position: I16Vec2, read: impl FnOnce(Option<&Entry<B>>) -> T) -> T {
+        let mut guard = self.shard(position).lock();
+        let shard = &mut *guard;
+
+        let entry = shard.entries.get_mut(&position).map(|entry| {
+            if shard.recency.remove(&entry.last_used).is_some() {
+                entry.last_used = self.tick();
+                shard.recency.insert(entry.last_used, position);
+            }
+
+            &*entry
+        });
+
+        let result = read(entry);
+
+        drop(guard);
+
+        result
+    }
+
+    /// Calls `update` with the entry of a column, creating it if needed, and then evicts the
+    /// least recently used entries if the budget is exceeded.
+    fn update<T>(&self, position: I16Vec2, update: impl FnOnce(&mut Entry<B>) -> T) -> T {
+        let protected = self.is_protected(position);
+        let last_used = self.tick();
+
+        let mut evictions = 0;
+
+        let mut guard = self.shard(position).lock();
+        let shard = &mut *guard;
+
+        let mut entry = shard
+            .entries
+            .remove(&position)
+            .unwrap_or_else(|| Entry::new(last_used));
+
+        if shard.recency.remove(&entry.last_used).is_some() {
+            shard.bytes -= entry.bytes();
+        }
+
+        let result = update(&mut entry);
+
+        if entry.is_empty() {
+            drop(guard);
+            return result;
+        }
+
+        if !protected && !entry.pinned {
+            entry.last_used = last_used;
+            shard.recency.insert(last_used, position);
+            shard.bytes += entry.bytes();
+        }
+
+        shard.entries.insert(position, entry);
+
+        while shard.bytes > self.shard_budget {
+            // the entry just updated is the most recent, so this never evicts it unless it is
+            // larger than the whole budget of the shard
+            let Some((_, evicted)) = shard.recency.pop_first() else {
+                break;
+            };
+
+            if let Some(entry) = shard.entries.remove(&evicted) {
+                shard.bytes -= entry.bytes();
+                evictions += 1;
+            }
+        }
+
+        drop(guard);
+
+        self.evictions.fetch_add(evictions, Ordering::Relaxed);
+
+        result
+    }
+
+    /// Gets a chunk packet and marks its column as recently used.
+    pub fn get(&self, position: I16Vec2) -> Option<Bytes> {
+        let raw = self.read(position, |entry| entry?.packet.clone());
+
+        let counter = if raw.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        raw
+    }
+
+    /// Gets a chunk packet. Unlike [`ChunkCache::get`], this does not count as a use.
+    pub fn peek(&self, position: I16Vec2) -> Option<Bytes> {
+        self.shard(position)
+            .lock()
+            .entries
+            .get(&position)?
+            .packet
+            .clone()
+    }
+
+    /// Whether a chunk packet is cached. Unlike [`ChunkCache::get`], this does not count as a use.
+    pub fn contains(&self, position: I16Vec2) -> bool {
+        self.shard(position)
+            .lock()
+            .entries
+            .get(&position)
+            .is_some_and(|entry| entry.packet.is_some())
+    }
+
+    /// Inserts a chunk packet, evicting the least recently used columns if the budget is
+    /// exceeded.
+    pub fn insert(&self, position: I16Vec2, raw: Bytes) {
+        self.update(position, |entry| entry.packet = Some(raw));
+    }
+
+    /// Gets the decoded blocks of a column and marks it as recently used.
+    pub fn blocks(&self, position: I16Vec2) -> Option<B> {
+        self.read(position, |entry| Some(entry?.blocks.as_ref()?.0.clone()))
+    }
+
+    /// Gets the decoded blocks of a column. Unlike [`ChunkCache::blocks`], this does not count as
+    /// a use.
+    pub fn peek_blocks(&self, position: I16Vec2) -> Option<B> {
+        let shard = self.shard(position).lock();
+        Some(shard.entries.get(&position)?.blocks.as_ref()?.0.clone())
+    }
+
+    /// Inserts the decoded blocks of a column, which use `bytes` bytes.
+    pub fn insert_blocks(&self, position: I16Vec2, blocks: B, bytes: usize) {
+        self.update(position, |entry| entry.blocks = Some((blocks, bytes)));
+    }
+
+    /// Gets the decoded blocks of a column and makes sure the column is never evicted again, so
+    /// changes to the blocks are not lost. Returns `None` without pinning anything if the blocks
+    /// are not cached.
+    pub fn pin_blocks(&self, position: I16Vec2) -> Option<B> {
+        let mut guard = self.shard(position).lock();
+        let shard = &mut *guard;
+
+        let blocks = shard.entries.get_mut(&position).and_then(|entry| {
+            let (blocks, _) = entry.blocks.as_ref()?;
+            let blocks = blocks.clone();
+
+            if shard.recency.remove(&entry.last_used).is_some() {
+                shard.bytes -= entry.bytes();
+            }
+
+            entry.pinned = true;
+
+            Some(blocks)
+        });
+
+        drop(guard);
+
+        blocks
+    }
+
+    /// Gets which blocks of a column are solid and marks it as recently used.
+    pub fn solids(&self, position: I16Vec2) -> Option<Arc<SolidColumn>> {
+        self.read(position, |entry| entry?.solids.clone())
+    }
+
+    /// Gets which blocks of a column are solid. Unlike [`ChunkCache::solids`], this does not count
+    /// as a use.
+    pub fn peek_solids(&self, position: I16Vec2) -> Option<Arc<SolidColumn>> {
+        self.shard(position)
+            .lock()
+            .entries
+            .get(&position)?
+            .solids
+            .clone()
+    }
+
+    /// Inserts which blocks of a column are solid unless they are already cached, which is
+    /// returned instead. Does nothing and returns `None` if the blocks of the column are not
+    /// cached, as the solids would otherwise outlive what they were built from.
+    pub fn insert_solids(
+        &self,
+        position: I16Vec2,
+        solids: SolidColumn,
+    ) -> Option<Arc<SolidColumn>> {
+        self.update(position, |entry| {
+            entry.blocks.as_ref()?;
+            Some(entry.solids.get_or_insert_with(|| Arc::new(solids)).clone())
+        })
+    }
+
+    /// Changes which blocks of a column are solid if they are cached.
+    pub fn update_solids(&self, position: I16Vec2, update: impl FnOnce(&mut SolidColumn)) {
+        let mut guard = self.shard(position).lock();
+        let shard = &mut *guard;
+
+        if let Some(entry) = shard.entries.get_mut(&position) {
+            let evictable = shard.recency.contains_key(&entry.last_used);
+
+            if evictable {
+                shard.bytes -= entry.bytes();
+            }
+
+            if let Some(solids) = &mut entry.solids {
+                update(Arc::make_mut(solids));
+            }
+
+            if evictable {
+                shard.bytes += entry.bytes();
+            }
+        }
+
+        drop(guard);
+    }
+
+    #[must_use]
+    pub fn stats(&self) -> CacheStats {
+        let mut stats = CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            ..CacheStats::default()
+        };
+
+        for shard in &*self.shards {
+            let shard = shard.lock();
+            stats.entries += shard.entries.len();
+            stats.bytes += shard.entries.values().map(Entry::bytes).sum::<usize>();
+        }
+
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(len: usize) -> Bytes {
+        Bytes::from(vec![0; len])
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let cache = ChunkCache::<()>::new(SHARD_COUNT * 100, I16Vec2::new(1000, 1000), 0);
+
+        // find three chunks in the same shard so they compete for one budget
+        let first = I16Vec2::new(0, 0);
+        let same_shard = (1..)
+            .map(|x| I16Vec2::new(x, 0))
+            .filter(|&pos| std::ptr::eq(cache.shard(pos), cache.shard(first)));
+        let [second, third] = same_shard.take(2).collect::<Vec<_>>()[..] else {
+            unreachable!()
+        };
+
+        cache.insert(first, chunk(40));
+        cache.insert(second, chunk(40));
+        assert!(cache.get(first).is_some());
+
+        cache.insert(third, chunk(40));
+
+        assert!(cache.contains(first));
+        assert!(!cache.contains(second));
+        assert!(cache.contains(third));
+
+        let stats = cache.stats();
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.bytes, 80);
+    }
+
+    #[test]
+    fn spawn_is_never_evicted() {
+        let cache = ChunkCache::<()>::new(0, I16Vec2::ZERO, 2);
+
+        cache.insert(I16Vec2::new(1, -2), chunk(10));
+        cache.insert(I16Vec2::new(3, 0), chunk(10));
+
+        assert!(cache.get(I16Vec2::new(1, -2)).is_some());
+        assert!(cache.get(I16Vec2::new(3, 0)).is_none());
+        assert_eq!(cache.stats().misses, 1);
+    }
+
+    #[test]
+    fn blocks_and_solids_count_towards_the_budget() {
+        let budget = 1024 * 1024;
+        let cache = ChunkCache::new(budget, I16Vec2::new(1000, 1000), 0);
+
+        // half of the sections have some solid blocks, like a typical column
+        let solids = || SolidColumn::from_fn(24, |section, idx| section < 12 && idx % 3 == 0);
+
+        for x in 0..64 {
+            for z in 0..64 {
+                let position = I16Vec2::new(x, z);
+
+                cache.insert_blocks(position, x, 1500);
+                cache.insert_solids(position, solids());
+                cache.insert(position, chunk(500));
+
+                assert!(cache.stats().bytes <= budget);
+            }
+        }
+
+        let stats = cache.stats();
+        assert!(stats.evictions > 0);
+        assert!(stats.entries < 64 * 64);
+
+        // the parts of a column are evicted together
+        assert!(cache.blocks(I16Vec2::ZERO).is_none());
+        assert!(cache.solids(I16Vec2::ZERO).is_none());
+        assert!(!cache.contains(I16Vec2::ZERO));
+
+        let last = I16Vec2::new(63, 63);
+        assert_eq!(cache.blocks(last), Some(63));
+        assert!(cache.solids(last).is_some());
+        assert!(cache.contains(last));
+    }
+
+    #[test]
+    fn pinned_blocks_are_kept() {
+        let cache = ChunkCache::new(SHARD_COUNT * 250, I16Vec2::new(1000, 1000), 0);
+
+        let first = I16Vec2::ZERO;
+        let same_shard = (1..)
+            .map(|x| I16Vec2::new(x, 0))
+            .filter(|&pos| std::ptr::eq(cache.shard(pos), cache.shard(first)));
+        let [second, third, fourth] = same_shard.take(3).collect::<Vec<_>>()[..] else {
+            unreachable!()
+        };
+
+        assert_eq!(cache.pin_blocks(first), None);
+
+        cache.insert_blocks(first, 'a', 100);
+        assert_eq!(cache.pin_blocks(first), Some('a'));
+
+        // the packet of a pinned column is kept as well, and neither counts towards the budget
+        cache.insert(first, chunk(100));
+        cache.insert_blocks(second, 'b', 100);
+        cache.insert_blocks(third, 'c', 100);
+        cache.insert_blocks(fourth, 'd', 100);
+
+        assert_eq!(cache.blocks(first), Some('a'));
+        assert!(cache.contains(first));
+        assert_eq!(cache.blocks(second), None);
+        assert_eq!(cache.blocks(third), Some('c'));
+        assert_eq!(cache.blocks(fourth), Some('d'));
+    }
+
+    #[test]
+    fn peeking_is_not_a_use() {
+        let cache = ChunkCache::new(SHARD_COUNT * 250, I16Vec2::new(1000, 1000), 0);
+
+        let first = I16Vec2::ZERO;
+        let same_shard = (1..)
+            .map(|x| I16Vec2::new(x, 0))
+            .filter(|&pos| std::ptr::eq(cache.shard(pos), cache.shard(first)));
+        let [second, third] = same_shard.take(2).collect::<Vec<_>>()[..] else {
+            unreachable!()
+        };
+
+        cache.insert_blocks(first, 'a', 100);
+        cache.insert_solids(first, SolidColumn::empty(1));
+        cache.insert_blocks(second, 'b', 100);
+
+        assert_eq!(cache.peek_blocks(first), Some('a'));
+        assert!(cache.peek_solids(first).is_some());
+
+        // the first column is still the least recently used one
+        cache.insert_blocks(third, 'c', 100);
+
+        assert_eq!(cache.peek_blocks(first), None);
+        assert!(cache.peek_solids(first).is_none());
+        assert_eq!(cache.peek_blocks(second), Some('b'));
+    }
+
+    #[test]
+    fn solids_need_blocks() {
+        let cache = ChunkCache::<()>::new(usize::MAX, I16Vec2::ZERO, 0);
+
+        assert!(cache
+            .insert_solids(I16Vec2::ZERO, SolidColumn::empty(1))
+            .is_none());
+        assert!(cache.solids(I16Vec2::ZERO).is_none());
+
+        cache.insert_blocks(I16Vec2::ZERO, (), 0);
+        let solids = cache.insert_solids(I16Vec2::ZERO, SolidColumn::empty(1));
+        assert!(solids.is_some());
+
+        cache.update_solids(I16Vec2::ZERO, |solids| solids.set(0, 0, 0, true));
+        assert!(cache.solids(I16Vec2::ZERO).unwrap().get(0, 0, 0));
+    }
+}
@@ -0,0 +1,408 @@
+//! Collision of entities with blocks.
+//!
+//! Every block which blocks motion is treated as a full cube. Slabs, fences and the like are not
+//! exact, but this keeps a block lookup to a single bit so hundreds of thousands of entities can
+//! be simulated every tick.
+
+use bvh_region::aabb::Aabb;
+use glam::{IVec3, Vec3};
+
+/// How far up an entity walks onto a ledge without jumping.
+pub const STEP_HEIGHT: f32 = 1.0;
+
+/// Entities touching a block face are not colliding with it.
+const EPSILON: f32 = 1e-5;
+
+/// Which blocks of a 16×16×16 section are solid.
+#[derive(Clone, Debug)]
+enum SolidSection {
+    Empty,
+    Full,
+    /// One bit per block, indexed like block states.
+    Mixed(Box<[u64; 64]>),
+}
+
+/// Which blocks of a chunk column are solid.
+#[derive(Clone, Debug)]
+pub struct SolidColumn {
+    sections: Box<[SolidSection]>,
+}
+
+impl SolidColumn {
+    /// A column without any solid blocks.
+    #[must_use]
+    pub fn empty(sections: usize) -> Self {
+        Self {
+            sections: vec![SolidSection::Empty; sections].into_boxed_slice(),
+        }
+    }
+
+    /// Creates a column from whether the block at `idx` in section `section` is solid, where
+    /// blocks are indexed like block states.
+    pub fn from_fn(sections: usize, mut is_solid: impl FnMut(usize, usize) -> bool) -> Self {
+        let sections = (0..sections)
+            .map(|section| {
+                let mut bits = [0_u64; 64];
+
+                for idx in 0..16 * 16 * 16 {
+                    if is_solid(section, idx) {
+                        bits[idx / 64] |= 1 << (idx % 64);
+                    }
+                }
+
+                if bits.iter().all(|&word| word == 0) {
+                    SolidSection::Empty
+                } else if bits.iter().all(|&word| word == u64::MAX) {
+                    SolidSection::Full
+                } else {
+                    SolidSection::Mixed(Box::new(bits))
+                }
+            })
+            .collect();
+
+        Self { sections }
+    }
+
+    /// Whether the block at the given coordinates within the column is solid, where `y` is
+    /// relative to the bottom of the world. Blocks above the column are never solid.
+    #[must_use]
+    pub fn get(&self, x: usize, y: usize, z: usize) -> bool {
+        let Some(section) = self.sections.get(y / 16) else {
+            return false;
+        };
+
+        match section {
+            SolidSection::Empty => false,
+            SolidSection::Full => true,
+            SolidSection::Mixed(bits) => {
+                let idx = block_index(x, y, z);
+                bits[idx / 64] & (1 << (idx % 64)) != 0
+            }
+        }
+    }
+
+    /// Marks a block as solid or not. Blocks outside of the column are ignored.
+    pub fn set(&mut self, x: usize, y: usize, z: usize, solid: bool) {
+        let Some(section) = self.sections.get_mut(y / 16) else {
+            return;
+        };
+
+        let bits = match section {
+            SolidSection::Empty if !solid => return,
+            SolidSection::Full if solid => return,
+            SolidSection::Empty => section.insert_bits(0),
+            SolidSection::Full => section.insert_bits(u64::MAX),
+            SolidSection::Mixed(bits) => bits,
+        };
+
+        let idx = block_index(x, y, z);
+
+        if solid {
+            bits[idx / 64] |= 1 << (idx % 64);
+        } else {
+            bits[idx / 64] &= !(1 << (idx % 64));
+        }
+    }
+
+    /// How many bytes the column uses.
+    #[must_use]
+    pub fn bytes(&self) -> usize {
+        let mixed = self
+            .sections
+            .iter()
+            .filter(|section| matches!(section, SolidSection::Mixed(_)))
+            .count();
+
+        std::mem::size_of::<Self>()
+            + std::mem::size_of_val(&*self.sections)
+            + mixed * std::mem::size_of::<[u64; 64]>()
+    }
+}
+
+impl SolidSection {
+    /// Turns the section into [`SolidSection::Mixed`] with every word set to `fill`.
+    fn insert_bits(&mut self, fill: u64) -> &mut [u64; 64] {
+        *self = Self::Mixed(Box::new([fill; 64]));
+
+        let Self::Mixed(bits) = self else {
+            unreachable!()
+        };
+
+        bits
+    }
+}
+
+const fn block_index(x: usize, y: usize, z: usize) -> usize {
+    x + z * 16 + (y % 16) * 256
+}
+
+/// The result of moving an entity with [`collide`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Collision {
+    /// How far the entity can actually move.
+    pub offset: Vec3,
+    /// Whether the entity is standing on a block after moving.
+    pub on_ground: bool,
+    /// Whether a block stopped the entity moving up or down.
+    pub vertical: bool,
+}
+
+/// Moves `aabb` by `movement` as far as it can go without entering a solid block, stepping up
+/// ledges of up to [`STEP_HEIGHT`] if the entity was on the ground.
+///
+/// Blocks the entity is already inside of are ignored so it can get out of them.
+pub fn collide(
+    aabb: &Aabb,
+    movement: Vec3,
+    on_ground: bool,
+    mut is_solid: impl FnMut(IVec3) -> bool,
+) -> Collision {
+    let mut offset = sweep(aabb, movement, &mut is_solid);
+
+    #[expect(clippy::float_cmp, reason = "unchanged if nothing was hit")]
+    let blocked = offset.x != movement.x || offset.z != movement.z;
+    let landed = movement.y < 0.0 && offset.y > movement.y;
+
+    if blocked && (on_ground || landed) {
+        let up = sweep(aabb, Vec3::new(0.0, STEP_HEIGHT, 0.0), &mut is_solid).y;
+        let raised = aabb.move_by(Vec3::new(0.0, up, 0.0));
+
+        let horizontal = Vec3::new(movement.x, 0.0, movement.z);
+        let horizontal = sweep(&raised, horizontal, &mut is_solid);
+        let moved = raised.move_by(horizontal);
+
+        let down = Vec3::new(0.0, movement.y.min(0.0) - up, 0.0);
+        let down = sweep(&moved, down, &mut is_solid).y;
+
+        let stepped = Vec3::new(horizontal.x, up + down, horizontal.z);
+
+        let distance = |offset: Vec3| offset.x.mul_add(offset.x, offset.z * offset.z);
+
+        if distance(stepped) > distance(offset) {
+            offset = stepped;
+        }
+    }
+
+    #[expect(clippy::float_cmp, reason = "unchanged if nothing was hit")]
+    let vertical = offset.y != movement.y;
+
+    Collision {
+        offset,
+        on_ground: movement.y < 0.0 && offset.y > movement.y,
+        vertical,
+    }
+}
+
+/// How far `aabb` can move by `movement` without entering a solid block, moving along the y, x
+/// and z axes in turn like vanilla. Unlike [`collide`], this never steps up.
+pub fn sweep(aabb: &Aabb, movement: Vec3, is_solid: &mut impl FnMut(IVec3) -> bool) -> Vec3 {
+    let mut aabb = *aabb;
+    let mut offset = Vec3::ZERO;
+
+    for axis in [1, 0, 2] {
+        let distance = clip(&aabb, axis, movement[axis], is_solid);
+
+        let mut step = Vec3::ZERO;
+        step[axis] = distance;
+
+        aabb = aabb.move_by(step);
+        offset[axis] = distance;
+    }
+
+    offset
+}
+
+/// How far `aabb` can move along `axis` before hitting a solid block.
+fn clip(aabb: &Aabb, axis: usize, distance: f32, is_solid: &mut impl FnMut(IVec3) -> bool) -> f32 {
+    if distance == 0.0 {
+        return 0.0;
+    }
+
+    let mut swept = *aabb;
+    if distance > 0.0 {
+        swept.max[axis] += distance;
+    } else {
+        swept.min[axis] += distance;
+    }
+
+    let from = (swept.min + EPSILON).floor().as_ivec3();
+    let to = (swept.max - EPSILON).floor().as_ivec3();
+
+    let mut distance = distance;
+
+    for x in from.x..=to.x {
+        for y in from.y..=to.y {
+            for z in from.z..=to.z {
+                let block = IVec3::new(x, y, z);
+
+                if !is_solid(block) {
+                    continue;
+                }
+
+                let low = block[axis] as f32;
+                let high = low + 1.0;
+
+                if distance > 0.0 && low >= aabb.max[axis] - EPSILON {
+                    distance = distance.min((low - aabb.max[axis]).max(0.0));
+                } else if distance < 0.0 && high <= aabb.min[axis] + EPSILON {
+                    distance = distance.max((high - aabb.min[axis]).min(0.0));
+                }
+            }
+        }
+    }
+
+    distance
+}
+
+/// Whether no solid block is between `from` and `to`, walking through every block the line
+/// between them crosses. The blocks the two points are in are not checked, so a point resting on
+/// or slightly inside of a block can still be seen.
+pub fn line_of_sight(from: Vec3, to: Vec3, is_solid: &mut impl FnMut(IVec3) -> bool) -> bool {
+    let delta = to - from;
+
+    let mut block = from.floor().as_ivec3();
+    let end = to.floor().as_ivec3();
+
+    let mut step = IVec3::ZERO;
+    // how far along the line the next block boundary of each axis is, from 0 to 1
+    let mut next = Vec3::INFINITY;
+    // how far along the line one block along each axis is
+    let mut across = Vec3::INFINITY;
+
+    for axis in 0..3 {
+        let distance = delta[axis];
+        let start = block[axis] as f32;
+
+        if distance > 0.0 {
+            step[axis] = 1;
+            next[axis] = (start + 1.0 - from[axis]) / distance;
+            across[axis] = distance.recip();
+        } else if distance < 0.0 {
+            step[axis] = -1;
+            next[axis] = (start - from[axis]) / distance;
+            across[axis] = -distance.recip();
+        }
+    }
+
+    let crossings = (end - block).abs().element_sum();
+
+    for _ in 0..crossings {
+        let axis = if next.x < next.y && next.x < next.z {
+            0
+        } else if next.y < next.z {
+            1
+        } else {
+            2
+        };
+
+        // rounding can put the end a little further than the last boundary
+        if next[axis] > 1.0 {
+            break;
+        }
+
+        block[axis] += step[axis];
+        next[axis] += across[axis];
+
+        if block == end {
+            break;
+        }
+
+        if is_solid(block) {
+            return false;
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A floor at y = 0 with a one block wall at x = 2 and a two block wall at z = 2.
+    fn world(block: IVec3) -> bool {
+        block.y < 0 || (block.x == 2 && block.y == 0) || (block.z == 2 && block.y <= 1)
+    }
+
+    fn entity(feet: Vec3) -> Aabb {
+        Aabb::create(feet, 0.6, 1.95)
+    }
+
+    #[test]
+    fn falls_onto_the_floor() {
+        let collision = collide(
+            &entity(Vec3::new(0.5, 0.5, 0.5)),
+            Vec3::new(0.0, -2.0, 0.0),
+            false,
+            world,
+        );
+
+        assert!((collision.offset.y + 0.5).abs() < 1e-4);
+        assert!(collision.on_ground);
+        assert!(collision.vertical);
+    }
+
+    #[test]
+    fn steps_up_one_block() {
+        let collision = collide(
+            &entity(Vec3::new(1.5, 0.0, 0.5)),
+            Vec3::new(0.5, -0.08, 0.0),
+            true,
+            world,
+        );
+
+        assert!((collision.offset.x - 0.5).abs() < 1e-4);
+        assert!((collision.offset.y - 1.0).abs() < 1e-4);
+        assert!(collision.on_ground);
+    }
+
+    #[test]
+    fn stopped_by_high_walls() {
+        let collision = collide(
+            &entity(Vec3::new(0.5, 0.0, 1.5)),
+            Vec3::new(0.0, -0.08, 0.5),
+            true,
+            world,
+        );
+
+        assert!((collision.offset.z - 0.2).abs() < 1e-4);
+        assert!(collision.offset.y.abs() < 1e-4);
+    }
+
+    #[test]
+    fn column_bits() {
+        let mut column = SolidColumn::from_fn(2, |section, idx| section == 0 && idx % 2 == 0);
+
+        assert!(column.get(0, 0, 0));
+        assert!(!column.get(1, 0, 0));
+        assert!(!column.get(0, 16, 0));
+        assert!(!column.get(0, 100, 0));
+
+        column.set(0, 16, 0, true);
+        column.set(0, 0, 0, false);
+
+        assert!(column.get(0, 16, 0));
+        assert!(!column.get(0, 0, 0));
+    }
+
+    #[test]
+    fn line_of_sight_through_blocks() {
+        let mut is_solid = world;
+
+        let eye = Vec3::new(0.5, 0.5, 0.5);
+
+        assert!(!line_of_sight(eye, Vec3::new(4.5, 0.5, 0.5), &mut is_solid));
+        assert!(!line_of_sight(Vec3::new(4.5, 0.5, 0.5), eye, &mut is_solid));
+        assert!(!line_of_sight(eye, Vec3::new(0.7, 1.2, 4.5), &mut is_solid));
+
+        // over the walls
+        let high = Vec3::new(0.5, 2.5, 0.5);
+        assert!(line_of_sight(high, Vec3::new(4.5, 2.5, 0.5), &mut is_solid));
+        assert!(line_of_sight(high, Vec3::new(0.5, 2.5, 4.5), &mut is_solid));
+        assert!(line_of_sight(eye, Vec3::new(1.9, 0.5, 0.5), &mut is_solid));
+
+        // the blocks of the two ends do not matter
+        assert!(line_of_sight(eye, Vec3::new(2.5, 0.5, 0.5), &mut is_solid));
+        assert!(line_of_sight(eye, eye, &mut is_solid));
+    }
+}
@@ -0,0 +1,385 @@
+//! Sky light and block light for chunk columns.
+//!
+//! Light is computed with a breadth-first flood fill over a 3×3 window of columns. Light never
+//! travels further than 15 blocks, so a window one column wide on every side is enough to account
+//! for light crossing chunk borders. Only the light of the center column is kept.
+
+use std::collections::VecDeque;
+
+use valence_generated::block::BlockState;
+use valence_protocol::FixedArray;
+use valence_server::layer::chunk::UnloadedChunk;
+
+/// The number of block sections in a column.
+pub const SECTION_COUNT: usize = 384 / 16;
+
+/// The number of light sections in a column. There is one extra section below and above the world.
+pub const LIGHT_SECTION_COUNT: usize = SECTION_COUNT + 2;
+
+/// The maximum light level.
+const MAX_LIGHT: u8 = 15;
+
+/// The width (and depth) of the lighting window in blocks.
+const WINDOW: usize = 16 * 3;
+
+/// The height of the lighting window in blocks.
+const HEIGHT: usize = SECTION_COUNT * 16;
+
+/// A nibble array holding the light of one 16×16×16 section.
+pub type LightArray = FixedArray<u8, 2048>;
+
+const EMPTY_ARRAY: LightArray = FixedArray([0; 2048]);
+const FULL_ARRAY: LightArray = FixedArray([0xFF; 2048]);
+
+/// How much light is lost when passing through the given block.
+fn opacity(state: BlockState) -> u8 {
+    if state.is_opaque() {
+        MAX_LIGHT
+    } else {
+        u8::from(state.is_liquid())
+    }
+}
+
+/// The opacity and emission of every block in a 3×3 window of columns.
+pub struct LightGrid {
+    opacity: Vec<u8>,
+    emission: Vec<u8>,
+}
+
+const fn idx(x: usize, y: usize, z: usize) -> usize {
+    (y * WINDOW + z) * WINDOW + x
+}
+
+impl LightGrid {
+    /// Builds the grid from a 3×3 window of columns in row-major order (`dz * 3 + dx`). The center
+    /// column is at index 4. Columns which are not loaded are treated as empty.
+    #[must_use]
+    pub fn new(columns: &[Option<&UnloadedChunk>; 9]) -> Self {
+        let mut grid = Self::empty();
+
+        for (i, column) in columns.iter().enumerate() {
+            let Some(column) = column else {
+                continue;
+            };
+
+            let base_x = (i % 3) * 16;
+            let base_z = (i / 3) * 16;
+
+            for (section_y, section) in column.sections.iter().enumerate().take(SECTION_COUNT) {
+                for local in 0..16 * 16 * 16 {
+                    let state = section.block_states.get(local);
+
+                    if state.is_air() {
+                        continue;
+                    }
+
+                    let x = base_x + local % 16;
+                    let z = base_z + (local / 16) % 16;
+                    let y = section_y * 16 + local / 256;
+
+                    grid.set(x, y, z, opacity(state), state.luminance());
+                }
+            }
+        }
+
+        grid
+    }
+
+    /// A grid where every block is air.
+    #[must_use]
+    pub fn empty() -> Self {
+        Self {
+            opacity: vec![0; WINDOW * WINDOW * HEIGHT],
+            emission: vec![0; WINDOW * WINDOW * HEIGHT],
+        }
+    }
+
+    /// Sets the opacity and emission of the block at the given window coordinates.
+    pub fn set(&mut self, x: usize, y: usize, z: usize, opacity: u8, emission: u8) {
+        let idx = idx(x, y, z);
+        self.opacity[idx] = opacity;
+        self.emission[idx] = emission;
+    }
+
+    /// Propagates light from every queued position. A neighbor is only updated if doing so raises
+    /// its light level.
+    fn flood(&self, light: &mut [u8], mut queue: VecDeque<u32>) {
+        while let Some(on) = queue.pop_front() {
+            let on = on as usize;
+            let level = light[on];
+
+            if level <= 1 {
+                continue;
+            }
+
+            let x = on % WINDOW;
+            let z = (on / WINDOW) % WINDOW;
+            let y = on / (WINDOW * WINDOW);
+
+            let mut visit = |neighbor: usize| {
+                let next = level.saturating_sub(self.opacity[neighbor].max(1));
+                if next > light[neighbor] {
+                    light[neighbor] = next;
+                    queue.push_back(neighbor as u32);
+                }
+            };
+
+            if x > 0 {
+                visit(on - 1);
+            }
+            if x + 1 < WINDOW {
+                visit(on + 1);
+            }
+            if z > 0 {
+                visit(on - WINDOW);
+            }
+            if z + 1 < WINDOW {
+                visit(on + WINDOW);
+            }
+            if y > 0 {
+                visit(on - WINDOW * WINDOW);
+            }
+            if y + 1 < HEIGHT {
+                visit(on + WINDOW * WINDOW);
+            }
+        }
+    }
+
+    /// Computes the block light of the whole window.
+    fn block_light(&self) -> Vec<u8> {
+        let mut light = self.emission.clone();
+
+        let queue = light
+            .iter()
+            .enumerate()
+            .filter(|(_, level)| **level > 0)
+            .map(|(idx, _)| idx as u32)
+            .collect();
+
+        self.flood(&mut light, queue);
+
+        light
+    }
+
+    /// The lowest y of every column in the window that still receives direct sky light.
+    fn sky_heights(&self) -> Vec<usize> {
+        let mut heights = vec![0; WINDOW * WINDOW];
+
+        for z in 0..WINDOW {
+            for x in 0..WINDOW {
+                let mut height = HEIGHT;
+                while height > 0 && self.opacity[idx(x, height - 1, z)] == 0 {
+                    height -= 1;
+                }
+                heights[z * WINDOW + x] = height;
+            }
+        }
+
+        heights
+    }
+
+    /// Computes the sky light of the whole window.
+    fn sky_light(&self) -> Vec<u8> {
+        let heights = self.sky_heights();
+        let mut light = vec![0; WINDOW * WINDOW * HEIGHT];
+        let mut queue = VecDeque::new();
+
+        for z in 0..WINDOW {
+            for x in 0..WINDOW {
+                let height = heights[z * WINDOW + x];
+
+                for y in height..HEIGHT {
+                    light[idx(x, y, z)] = MAX_LIGHT;
+                }
+
+                // only blocks next to a shadowed column (or directly above the first blocking
+                // block) can spread sky light any further
+                let mut spread_to = height + 1;
+                if x > 0 {
+                    spread_to = spread_to.max(heights[z * WINDOW + x - 1]);
+                }
+                if x + 1 < WINDOW {
+                    spread_to = spread_to.max(heights[z * WINDOW + x + 1]);
+                }
+                if z > 0 {
+                    spread_to = spread_to.max(heights[(z - 1) * WINDOW + x]);
+                }
+                if z + 1 < WINDOW {
+                    spread_to = spread_to.max(heights[(z + 1) * WINDOW + x]);
+                }
+
+                for y in height..spread_to.min(HEIGHT) {
+                    queue.push_back(idx(x, y, z) as u32);
+                }
+            }
+        }
+
+        self.flood(&mut light, queue);
+
+        light
+    }
+
+    /// Computes the light of the center column of the window.
+    #[must_use]
+    pub fn light(&self) -> ColumnLight {
+        let sky = self.sky_light();
+        let block = self.block_light();
+
+        let mut column = ColumnLight {
+            sky: vec![EMPTY_ARRAY; LIGHT_SECTION_COUNT],
+            block: vec![EMPTY_ARRAY; LIGHT_SECTION_COUNT],
+        };
+
+        // the section above the world is always fully lit by the sky
+        column.sky[LIGHT_SECTION_COUNT - 1] = FULL_ARRAY;
+
+        for y in 0..HEIGHT {
+            let section = y / 16 + 1;
+
+            for z in 0..16 {
+                for x in 0..16 {
+                    let window_idx = idx(x + 16, y, z + 16);
+                    let nibble_idx = (y % 16) * 256 + z * 16 + x;
+
+                    set_nibble(&mut column.sky[section], nibble_idx, sky[window_idx]);
+                    set_nibble(&mut column.block[section], nibble_idx, block[window_idx]);
+                }
+            }
+        }
+
+        column
+    }
+}
+
+fn set_nibble(array: &mut LightArray, idx: usize, level: u8) {
+    let byte = &mut array.0[idx / 2];
+
+    if idx % 2 == 0 {
+        *byte = (*byte & 0xF0) | level;
+    } else {
+        *byte = (*byte & 0x0F) | (level << 4);
+    }
+}
+
+/// The light of every light section in a column, including the sections below and above the world.
+pub struct ColumnLight {
+    sky: Vec<LightArray>,
+    block: Vec<LightArray>,
+}
+
+/// Light masks and arrays in the layout used by `ChunkDataS2c` and `LightUpdateS2c`.
+pub struct LightData {
+    pub sky_light_mask: Vec<u64>,
+    pub block_light_mask: Vec<u64>,
+    pub empty_sky_light_mask: Vec<u64>,
+    pub empty_block_light_mask: Vec<u64>,
+    pub sky_light_arrays: Vec<LightArray>,
+    pub block_light_arrays: Vec<LightArray>,
+}
+
+/// Splits arrays into a mask of non-empty sections (which are sent) and a mask of empty sections.
+fn masks(arrays: &[LightArray]) -> (u64, u64, Vec<LightArray>) {
+    let mut mask = 0;
+    let mut empty_mask = 0;
+    let mut sent = Vec::new();
+
+    for (i, array) in arrays.iter().enumerate() {
+        if array.0.iter().all(|&byte| byte == 0) {
+            empty_mask |= 1 << i;
+        } else {
+            mask |= 1 << i;
+            sent.push(*array);
+        }
+    }
+
+    (mask, empty_mask, sent)
+}
+
+impl ColumnLight {
+    /// The sky light level at the given column-local position, where `y` is relative to the
+    /// bottom of the world.
+    #[must_use]
+    pub fn sky(&self, x: usize, y: usize, z: usize) -> u8 {
+        get_nibble(&self.sky[y / 16 + 1], (y % 16) * 256 + z * 16 + x)
+    }
+
+    /// The block light level at the given column-local position, where `y` is relative to the
+    /// bottom of the world.
+    #[must_use]
+    pub fn block(&self, x: usize, y: usize, z: usize) -> u8 {
+        get_nibble(&self.block[y / 16 + 1], (y % 16) * 256 + z * 16 + x)
+    }
+
+    #[must_use]
+    pub fn data(&self) -> LightData {
+        let (sky_light_mask, empty_sky_light_mask, sky_light_arrays) = masks(&self.sky);
+        let (block_light_mask, empty_block_light_mask, block_light_arrays) = masks(&self.block);
+
+        LightData {
+            sky_light_mask: vec![sky_light_mask],
+            block_light_mask: vec![block_light_mask],
+            empty_sky_light_mask: vec![empty_sky_light_mask],
+            empty_block_light_mask: vec![empty_block_light_mask],
+            sky_light_arrays,
+            block_light_arrays,
+        }
+    }
+}
+
+const fn get_nibble(array: &LightArray, idx: usize) -> u8 {
+    let byte = array.0[idx / 2];
+
+    if idx % 2 == 0 {
+        byte & 0x0F
+    } else {
+        byte >> 4
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_sky_is_fully_lit() {
+        let light = LightGrid::empty().light();
+
+        assert_eq!(light.sky(0, 0, 0), 15);
+        assert_eq!(light.sky(15, HEIGHT - 1, 15), 15);
+        assert_eq!(light.block(8, 100, 8), 0);
+    }
+
+    #[test]
+    fn roof_shadows_and_light_leaks_sideways() {
+        let mut grid = LightGrid::empty();
+
+        // a 16×16 roof over the center column at y = 100
+        for z in 16..32 {
+            for x in 16..32 {
+                grid.set(x, 100, z, 15, 0);
+            }
+        }
+
+        let light = grid.light();
+
+        assert_eq!(light.sky(8, 101, 8), 15);
+        // the center of the roof is 8 blocks from the closest edge
+        assert_eq!(light.sky(8, 99, 8), 15 - 8);
+        // right below the edge of the roof
+        assert_eq!(light.sky(0, 99, 8), 14);
+    }
+
+    #[test]
+    fn block_light_crosses_chunk_borders() {
+        let mut grid = LightGrid::empty();
+
+        // a torch in the neighboring column to the west, one block from the border
+        grid.set(14, 50, 20, 0, 14);
+
+        let light = grid.light();
+
+        // center column x = 0 is window x = 16, two blocks away
+        assert_eq!(light.block(0, 50, 4), 12);
+        assert_eq!(light.block(13, 50, 4), 0);
+    }
+}
@@ -10,7 +10,7 @@ use tokio::{
     sync::{Notify, RwLock},
 };
 
-use crate::{blocks::get_nyc_save, components::chunks::region::Region};
+use crate::components::chunks::region::Region;
 
 enum RegionState {
     Pending(Weak<Notify>),
@@ -23,13 +23,11 @@ pub struct Regions {
 }
 
 impl Regions {
-    pub fn new() -> anyhow::Result<Self> {
-        let save = get_nyc_save()?;
-
-        Ok(Self {
+    pub fn new(save: &Path) -> Self {
+        Self {
             root: save.join("region"),
             regions: RwLock::new(HashMap::new()),
-        })
+        }
     }
 
     pub fn root(&self) -> &Path {
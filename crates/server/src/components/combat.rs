@@ -0,0 +1,618 @@
+//! Melee combat like vanilla.
+//!
+//! The damage of a melee attack depends on the item the attacker holds and how far their attack
+//! cooldown recharged. Attacks while falling are critical hits, attacks while sprinting knock the
+//! target back further, and the armor of the target absorbs some of the damage. Each of these is a
+//! rule in [`CombatRules`] which game modes can turn off.
+
+use evenio::component::Component;
+use glam::{Vec2, Vec3};
+use valence_nbt::{List, Value};
+use valence_protocol::ItemStack;
+use valence_server::ItemKind;
+
+/// Critical hits deal this much more damage.
+pub const CRITICAL_MULTIPLIER: f32 = 1.5;
+
+/// Armor can absorb at most this many of 25 parts of the damage.
+const MAX_ARMOR: f32 = 20.0;
+
+/// Protection can absorb at most this many of 25 parts of the damage.
+const MAX_PROTECTION: u32 = 20;
+
+/// Which parts of vanilla combat are used. This is a singleton which game modes can change.
+#[derive(Component, Debug, Clone, PartialEq)]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "each rule is turned on and off on its own"
+)]
+pub struct CombatRules {
+    /// Melee damage scales with how far the attack cooldown recharged. Without it, every click
+    /// deals full damage like before 1.9.
+    pub attack_cooldown: bool,
+    /// Melee attacks while falling deal [`CRITICAL_MULTIPLIER`] times the damage.
+    pub critical_hits: bool,
+    /// Melee attacks while sprinting knock the target back further and stop the sprint.
+    pub sprint_knockback: bool,
+    /// Armor and its toughness absorb damage and netherite resists knockback.
+    pub armor: bool,
+    /// Sharpness, Protection and Knockback have an effect.
+    pub enchantments: bool,
+    /// How strongly every hit pushes the target away.
+    pub knockback: f32,
+}
+
+impl Default for CombatRules {
+    fn default() -> Self {
+        Self {
+            attack_cooldown: true,
+            critical_hits: true,
+            sprint_knockback: true,
+            armor: true,
+            enchantments: true,
+            knockback: 0.4,
+        }
+    }
+}
+
+/// The tick the attack cooldown of a player was last reset, which happens when they attack or
+/// change the item in their hand.
+#[derive(Component, Debug, Copy, Clone, Default)]
+pub struct AttackCooldown {
+    pub since: i64,
+}
+
+/// The armor an entity wears, as last set by `event::SetEquipment`.
+#[derive(Component, Debug, Clone, Default)]
+pub struct Armor {
+    /// Indexed by equipment slot minus 2, so feet, legs, chest and head.
+    pub slots: [ItemStack; 4],
+}
+
+impl Armor {
+    /// The equipment slot of the feet. The slots above it are the legs, chest and head.
+    const FIRST_SLOT: i8 = 2;
+
+    /// Puts `item` into the equipment `slot`. Returns `false` if the slot is not an armor slot.
+    pub fn set(&mut self, slot: i8, item: ItemStack) -> bool {
+        let Some(index) = slot
+            .checked_sub(Self::FIRST_SLOT)
+            .and_then(|index| usize::try_from(index).ok())
+        else {
+            return false;
+        };
+
+        let Some(current) = self.slots.get_mut(index) else {
+            return false;
+        };
+
+        *current = item;
+        true
+    }
+
+    /// The combined stats of every piece.
+    #[must_use]
+    pub fn stats(&self) -> ArmorStats {
+        self.slots
+            .iter()
+            .map(|piece| ArmorStats::of(piece.item))
+            .fold(ArmorStats::default(), |total, piece| ArmorStats {
+                armor: total.armor + piece.armor,
+                toughness: total.toughness + piece.toughness,
+                knockback_resistance: total.knockback_resistance + piece.knockback_resistance,
+            })
+    }
+
+    /// The combined Protection level of every piece.
+    #[must_use]
+    pub fn protection(&self) -> u32 {
+        self.slots
+            .iter()
+            .map(|piece| enchantment_level(piece, "minecraft:protection"))
+            .sum()
+    }
+}
+
+/// The attributes an item gives while held in the main hand.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WeaponStats {
+    /// Measured in half hearts, including the 1 a fist deals.
+    pub damage: f32,
+    /// How many full strength attacks can be made per second.
+    pub speed: f32,
+}
+
+impl WeaponStats {
+    pub const FIST: Self = Self::new(1.0, 4.0);
+
+    const fn new(damage: f32, speed: f32) -> Self {
+        Self { damage, speed }
+    }
+
+    /// The stats of `item`, which are the ones of a fist for anything which is not a weapon or
+    /// tool.
+    #[must_use]
+    pub const fn of(item: ItemKind) -> Self {
+        match item {
+            ItemKind::WoodenSword | ItemKind::GoldenSword => Self::new(4.0, 1.6),
+            ItemKind::StoneSword => Self::new(5.0, 1.6),
+            ItemKind::IronSword => Self::new(6.0, 1.6),
+            ItemKind::DiamondSword => Self::new(7.0, 1.6),
+            ItemKind::NetheriteSword => Self::new(8.0, 1.6),
+
+            ItemKind::WoodenAxe => Self::new(7.0, 0.8),
+            ItemKind::GoldenAxe => Self::new(7.0, 1.0),
+            ItemKind::StoneAxe => Self::new(9.0, 0.8),
+            ItemKind::IronAxe => Self::new(9.0, 0.9),
+            ItemKind::DiamondAxe => Self::new(9.0, 1.0),
+            ItemKind::NetheriteAxe => Self::new(10.0, 1.0),
+
+            ItemKind::WoodenPickaxe | ItemKind::GoldenPickaxe => Self::new(2.0, 1.2),
+            ItemKind::StonePickaxe => Self::new(3.0, 1.2),
+            ItemKind::IronPickaxe => Self::new(4.0, 1.2),
+            ItemKind::DiamondPickaxe => Self::new(5.0, 1.2),
+            ItemKind::NetheritePickaxe => Self::new(6.0, 1.2),
+
+            ItemKind::WoodenShovel | ItemKind::GoldenShovel => Self::new(2.5, 1.0),
+            ItemKind::StoneShovel => Self::new(3.5, 1.0),
+            ItemKind::IronShovel => Self::new(4.5, 1.0),
+            ItemKind::DiamondShovel => Self::new(5.5, 1.0),
+            ItemKind::NetheriteShovel => Self::new(6.5, 1.0),
+
+            ItemKind::WoodenHoe | ItemKind::GoldenHoe => Self::new(1.0, 1.0),
+            ItemKind::StoneHoe => Self::new(1.0, 2.0),
+            ItemKind::IronHoe => Self::new(1.0, 3.0),
+            ItemKind::DiamondHoe | ItemKind::NetheriteHoe => Self::new(1.0, 4.0),
+
+            ItemKind::Trident => Self::new(9.0, 1.1),
+
+            _ => Self::FIST,
+        }
+    }
+}
+
+/// The attributes a piece of armor gives while worn.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct ArmorStats {
+    pub armor: f32,
+    pub toughness: f32,
+    /// The fraction of knockback which is ignored.
+    pub knockback_resistance: f32,
+}
+
+impl ArmorStats {
+    const fn new(armor: f32, toughness: f32, knockback_resistance: f32) -> Self {
+        Self {
+            armor,
+            toughness,
+            knockback_resistance,
+        }
+    }
+
+    /// The stats of `item`, which are zero for anything which is not armor.
+    #[must_use]
+    pub const fn of(item: ItemKind) -> Self {
+        match item {
+            ItemKind::LeatherHelmet
+            | ItemKind::LeatherBoots
+            | ItemKind::ChainmailBoots
+            | ItemKind::GoldenBoots => Self::new(1.0, 0.0, 0.0),
+            ItemKind::LeatherLeggings
+            | ItemKind::ChainmailHelmet
+            | ItemKind::IronHelmet
+            | ItemKind::IronBoots
+            | ItemKind::GoldenHelmet
+            | ItemKind::TurtleHelmet => Self::new(2.0, 0.0, 0.0),
+            ItemKind::LeatherChestplate | ItemKind::GoldenLeggings => Self::new(3.0, 0.0, 0.0),
+            ItemKind::ChainmailLeggings => Self::new(4.0, 0.0, 0.0),
+            ItemKind::ChainmailChestplate | ItemKind::IronLeggings | ItemKind::GoldenChestplate => {
+                Self::new(5.0, 0.0, 0.0)
+            }
+            ItemKind::IronChestplate => Self::new(6.0, 0.0, 0.0),
+
+            ItemKind::DiamondHelmet | ItemKind::DiamondBoots => Self::new(3.0, 2.0, 0.0),
+            ItemKind::DiamondLeggings => Self::new(6.0, 2.0, 0.0),
+            ItemKind::DiamondChestplate => Self::new(8.0, 2.0, 0.0),
+
+            ItemKind::NetheriteHelmet | ItemKind::NetheriteBoots => Self::new(3.0, 3.0, 0.1),
+            ItemKind::NetheriteLeggings => Self::new(6.0, 3.0, 0.1),
+            ItemKind::NetheriteChestplate => Self::new(8.0, 3.0, 0.1),
+
+            _ => Self::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+/// Damage types clients know about, numbered like `minecraft:damage_type` in the registry sent
+/// when they join.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(i32)]
+pub enum DamageType {
+    Arrow = 0,
+    MobAttack = 25,
+    PlayerAttack = 31,
+    Thrown = 39,
+    Trident = 40,
+}
+
+impl DamageType {
+    /// The id sent in `EntityDamageS2c`.
+    #[must_use]
+    pub const fn id(self) -> i32 {
+        self as i32
+    }
+
+    /// The name of the damage type in the registry.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Arrow => "minecraft:arrow",
+            Self::MobAttack => "minecraft:mob_attack",
+            Self::PlayerAttack => "minecraft:player_attack",
+            Self::Thrown => "minecraft:thrown",
+            Self::Trident => "minecraft:trident",
+        }
+    }
+}
+
+/// The level of the enchantment `id`, like `minecraft:sharpness`, on `item`, or 0 if it does not
+/// have it.
+#[must_use]
+pub fn enchantment_level(item: &ItemStack, id: &str) -> u32 {
+    let enchantments = item.nbt.as_ref().and_then(|nbt| nbt.get("Enchantments"));
+
+    let Some(Value::List(List::Compound(enchantments))) = enchantments else {
+        return 0;
+    };
+
+    enchantments
+        .iter()
+        .filter(
+            |enchantment| matches!(enchantment.get("id"), Some(Value::String(name)) if name == id),
+        )
+        .map(|enchantment| match enchantment.get("lvl") {
+            Some(&Value::Short(level)) => u32::try_from(level).unwrap_or(0),
+            Some(&Value::Int(level)) => u32::try_from(level).unwrap_or(0),
+            _ => 0,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// How far the attack cooldown recharged after `ticks` with a weapon of `attack_speed`, from 0 to
+/// 1.
+#[must_use]
+pub fn attack_strength(ticks: i64, attack_speed: f32) -> f32 {
+    let ticks = ticks as f32;
+
+    // vanilla checks halfway through the tick
+    ((ticks + 0.5) * attack_speed / 20.0).clamp(0.0, 1.0)
+}
+
+/// A melee attack by a player.
+#[derive(Debug, Clone, Copy)]
+pub struct Swing<'a> {
+    /// What the attacker holds in their main hand.
+    pub item: &'a ItemStack,
+    /// How many ticks ago the attack cooldown was reset.
+    pub ticks: i64,
+    pub falling: bool,
+    pub sprinting: bool,
+}
+
+/// The outcome of a [`Swing`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MeleeHit {
+    /// The damage before the target's armor absorbs any of it.
+    pub damage: f32,
+    pub critical: bool,
+    /// Whether enchantments added damage, which clients show with magic critical particles.
+    pub enchanted: bool,
+    /// How strongly the target is pushed along the direction the attacker looks, on top of the
+    /// knockback of every hit.
+    pub knockback: f32,
+    /// Whether the attack stops the sprint of the attacker.
+    pub stops_sprint: bool,
+}
+
+impl CombatRules {
+    /// Works out the damage and knockback of `swing` like `Player::attack` in vanilla.
+    #[must_use]
+    pub fn melee(&self, swing: Swing<'_>) -> MeleeHit {
+        let stats = WeaponStats::of(swing.item.item);
+
+        let strength = if self.attack_cooldown {
+            attack_strength(swing.ticks, stats.speed)
+        } else {
+            1.0
+        };
+
+        let (sharpness, knockback_level) = if self.enchantments {
+            (
+                enchantment_level(swing.item, "minecraft:sharpness"),
+                enchantment_level(swing.item, "minecraft:knockback"),
+            )
+        } else {
+            (0, 0)
+        };
+
+        let mut damage = stats.damage * strength.mul_add(strength * 0.8, 0.2);
+        let bonus = sharpness_bonus(sharpness) * strength;
+
+        let charged = strength > 0.9;
+
+        let sprint_hit = self.sprint_knockback && swing.sprinting && charged;
+        let critical = self.critical_hits && charged && swing.falling && !swing.sprinting;
+
+        if critical {
+            damage *= CRITICAL_MULTIPLIER;
+        }
+
+        let knockback_level = knockback_level + u32::from(sprint_hit);
+
+        let knockback = knockback_level as f32 * 0.5;
+
+        MeleeHit {
+            damage: damage + bonus,
+            critical,
+            enchanted: bonus > 0.0,
+            knockback,
+            stops_sprint: knockback_level > 0,
+        }
+    }
+
+    /// The damage left of `damage` after `armor` absorbs some of it.
+    #[must_use]
+    pub fn defend(&self, damage: f32, armor: &Armor) -> f32 {
+        let mut damage = damage;
+
+        if self.armor {
+            let stats = armor.stats();
+            damage = damage_after_armor(damage, stats.armor, stats.toughness);
+        }
+
+        if self.enchantments {
+            damage = damage_after_protection(damage, armor.protection());
+        }
+
+        damage
+    }
+
+    /// The fraction of knockback which is ignored by an entity wearing `armor`.
+    #[must_use]
+    pub fn knockback_resistance(&self, armor: &Armor) -> f32 {
+        if self.armor {
+            armor.stats().knockback_resistance.min(1.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+/// The damage Sharpness adds.
+#[must_use]
+pub fn sharpness_bonus(level: u32) -> f32 {
+    if level == 0 {
+        return 0.0;
+    }
+
+    let level = level as f32;
+
+    level.mul_add(0.5, 0.5)
+}
+
+/// The damage left after armor with `armor` points and `toughness` absorbs some of it. Tougher
+/// armor absorbs more of large hits.
+#[must_use]
+pub fn damage_after_armor(damage: f32, armor: f32, toughness: f32) -> f32 {
+    let toughness = 2.0 + toughness / 4.0;
+    let absorbed = (armor - damage / toughness).clamp(armor * 0.2, MAX_ARMOR);
+
+    damage * (1.0 - absorbed / 25.0)
+}
+
+/// The damage left after armor with a combined Protection level of `protection` absorbs some of
+/// it.
+#[must_use]
+pub fn damage_after_protection(damage: f32, protection: u32) -> f32 {
+    let protection = protection.min(MAX_PROTECTION) as f32;
+
+    damage * (1.0 - protection / 25.0)
+}
+
+/// Pushes an entity moving at `velocity` with `strength` along the horizontal `direction`, like
+/// `LivingEntity::knockback` in vanilla for an entity on the ground.
+pub fn knockback(velocity: &mut Vec3, strength: f32, direction: Vec2) {
+    if strength <= 0.0 {
+        return;
+    }
+
+    let push = direction.normalize_or_zero() * strength;
+
+    velocity.x = velocity.x / 2.0 + push.x;
+    velocity.y = (velocity.y / 2.0 + strength).min(0.4);
+    velocity.z = velocity.z / 2.0 + push.y;
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_nbt::compound;
+
+    use super::*;
+
+    fn enchanted(item: ItemKind, enchantments: &[(&str, i16)]) -> ItemStack {
+        let enchantments = enchantments
+            .iter()
+            .map(|&(id, level)| compound! { "id" => id, "lvl" => level })
+            .collect();
+
+        let nbt = compound! { "Enchantments" => List::Compound(enchantments) };
+
+        ItemStack::new(item, 1, Some(nbt))
+    }
+
+    fn swing(item: &ItemStack) -> Swing<'_> {
+        Swing {
+            item,
+            ticks: 100,
+            falling: false,
+            sprinting: false,
+        }
+    }
+
+    #[test]
+    fn damage_types_match_registry() {
+        let registry: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../system/paper-registry.json")).unwrap();
+
+        let damage_types = registry["minecraft:damage_type"]["value"]
+            .as_array()
+            .unwrap();
+
+        for damage_type in [
+            DamageType::Arrow,
+            DamageType::MobAttack,
+            DamageType::PlayerAttack,
+            DamageType::Thrown,
+            DamageType::Trident,
+        ] {
+            let entry = damage_types
+                .iter()
+                .find(|entry| entry["name"] == damage_type.name())
+                .unwrap();
+
+            assert_eq!(entry["id"], damage_type.id());
+        }
+    }
+
+    #[test]
+    fn cooldown_scales_damage() {
+        let sword = ItemStack::new(ItemKind::DiamondSword, 1, None);
+        let rules = CombatRules::default();
+
+        let full = rules.melee(swing(&sword));
+        assert!((full.damage - 7.0).abs() < 1e-5);
+
+        // spam clicking deals a fifth of the damage
+        let spammed = rules.melee(Swing {
+            ticks: 0,
+            ..swing(&sword)
+        });
+        assert!(spammed.damage < 7.0 * 0.25);
+
+        let rules = CombatRules {
+            attack_cooldown: false,
+            ..CombatRules::default()
+        };
+        let spammed = rules.melee(Swing {
+            ticks: 0,
+            ..swing(&sword)
+        });
+        assert!((spammed.damage - 7.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn critical_hits_while_falling() {
+        let sword = ItemStack::new(ItemKind::IronSword, 1, None);
+        let rules = CombatRules::default();
+
+        let falling = Swing {
+            falling: true,
+            ..swing(&sword)
+        };
+
+        let hit = rules.melee(falling);
+        assert!(hit.critical);
+        assert!((hit.damage - 9.0).abs() < 1e-5);
+
+        // sprinting hits are never critical, but knock back further
+        let hit = rules.melee(Swing {
+            sprinting: true,
+            ..falling
+        });
+        assert!(!hit.critical);
+        assert!((hit.knockback - 0.5).abs() < 1e-5);
+        assert!(hit.stops_sprint);
+
+        let rules = CombatRules {
+            critical_hits: false,
+            ..CombatRules::default()
+        };
+        assert!(!rules.melee(falling).critical);
+    }
+
+    #[test]
+    fn enchantments_add_damage_and_knockback() {
+        let sword = enchanted(ItemKind::DiamondSword, &[
+            ("minecraft:sharpness", 5),
+            ("minecraft:knockback", 2),
+        ]);
+
+        assert_eq!(enchantment_level(&sword, "minecraft:sharpness"), 5);
+        assert_eq!(enchantment_level(&sword, "minecraft:protection"), 0);
+
+        let hit = CombatRules::default().melee(swing(&sword));
+        assert!((hit.damage - 10.0).abs() < 1e-5);
+        assert!(hit.enchanted);
+        assert!((hit.knockback - 1.0).abs() < 1e-5);
+
+        let rules = CombatRules {
+            enchantments: false,
+            ..CombatRules::default()
+        };
+        let hit = rules.melee(swing(&sword));
+        assert!((hit.damage - 7.0).abs() < 1e-5);
+        assert!(!hit.enchanted);
+        assert!(hit.knockback.abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn armor_absorbs_damage() {
+        let mut armor = Armor::default();
+
+        for (slot, item) in [
+            (2, ItemKind::DiamondBoots),
+            (3, ItemKind::DiamondLeggings),
+            (4, ItemKind::DiamondChestplate),
+            (5, ItemKind::DiamondHelmet),
+        ] {
+            assert!(armor.set(slot, ItemStack::new(item, 1, None)));
+        }
+
+        assert!(!armor.set(0, ItemStack::new(ItemKind::DiamondSword, 1, None)));
+
+        let stats = armor.stats();
+        assert!((stats.armor - 20.0).abs() < f32::EPSILON);
+        assert!((stats.toughness - 8.0).abs() < f32::EPSILON);
+
+        let rules = CombatRules::default();
+
+        // full diamond absorbs most of a small hit and less of a large one
+        assert!((rules.defend(5.0, &armor) - 1.25).abs() < 1e-5);
+        assert!((rules.defend(30.0, &armor) - 15.0).abs() < 1e-5);
+
+        armor.slots[2] = enchanted(ItemKind::DiamondChestplate, &[("minecraft:protection", 4)]);
+        assert!((rules.defend(5.0, &armor) - 1.05).abs() < 1e-5);
+
+        let rules = CombatRules {
+            armor: false,
+            enchantments: false,
+            ..CombatRules::default()
+        };
+        assert!((rules.defend(5.0, &armor) - 5.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn knockback_pushes_away() {
+        let mut velocity = Vec3::new(0.2, 0.0, 0.0);
+        knockback(&mut velocity, 0.4, Vec2::new(0.0, -2.0));
+
+        assert!((velocity.x - 0.1).abs() < 1e-5);
+        assert!((velocity.y - 0.4).abs() < 1e-5);
+        assert!((velocity.z + 0.4).abs() < 1e-5);
+
+        let mut velocity = Vec3::ONE;
+        knockback(&mut velocity, 0.0, Vec2::X);
+        assert_eq!(velocity, Vec3::ONE);
+    }
+}
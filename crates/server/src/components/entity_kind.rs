@@ -0,0 +1,267 @@
+//! Properties of each [`EntityKind`] which the server needs to simulate it, taken from the vanilla
+//! 1.20.1 entity data.
+//!
+//! The table is kept by hand:
+//! - the hitbox sizes are the `sized(width, height)` of each type in `EntityType`
+//! - the health and speed are the `MAX_HEALTH` and `MOVEMENT_SPEED` each mob sets in its
+//!   `createAttributes`, or the defaults of `Mob` and `LivingEntity` if it does not
+//!
+//! Every kind of the protocol version has to be in it, which is checked by a test. Update them
+//! together with the protocol version.
+
+use bvh_region::aabb::Aabb;
+use glam::Vec3;
+use valence_server::entity::EntityKind;
+
+/// How far an NPC walks per tick for each point of its movement speed attribute. This is tuned for
+/// the server rather than taken from vanilla.
+const BLOCKS_PER_SPEED: f32 = 0.43;
+
+/// The hitbox and default attributes of an [`EntityKind`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct KindInfo {
+    /// The width and depth of the hitbox.
+    pub width: f32,
+    /// The height of the hitbox.
+    pub height: f32,
+    /// Measured in half hearts.
+    pub max_health: f32,
+    /// The vanilla movement speed attribute.
+    pub movement_speed: f32,
+}
+
+impl KindInfo {
+    const fn new(width: f32, height: f32, max_health: f32, movement_speed: f32) -> Self {
+        Self {
+            width,
+            height,
+            max_health,
+            movement_speed,
+        }
+    }
+
+    /// The properties of `kind`. Kinds which are not in the table are treated like a generic
+    /// humanoid mob, which only happens for kinds of a newer protocol version.
+    #[must_use]
+    pub const fn of(kind: EntityKind) -> Self {
+        match Self::lookup(kind) {
+            Some(info) => info,
+            None => Self::new(0.6, 1.95, 20.0, 0.25),
+        }
+    }
+
+    /// The properties of `kind` if it is in the table.
+    #[expect(
+        clippy::match_same_arms,
+        clippy::too_many_lines,
+        reason = "the table has every kind, grouped by what they are rather than by their values"
+    )]
+    const fn lookup(kind: EntityKind) -> Option<Self> {
+        let info = match kind {
+            EntityKind::PLAYER => Self::new(0.6, 1.8, 20.0, 0.1),
+
+            EntityKind::ZOMBIE
+            | EntityKind::HUSK
+            | EntityKind::DROWNED
+            | EntityKind::ZOMBIE_VILLAGER
+            | EntityKind::ZOMBIFIED_PIGLIN => Self::new(0.6, 1.95, 20.0, 0.23),
+            EntityKind::GIANT => Self::new(3.6, 12.0, 100.0, 0.5),
+            EntityKind::SKELETON | EntityKind::STRAY => Self::new(0.6, 1.99, 20.0, 0.25),
+            EntityKind::WITHER_SKELETON => Self::new(0.7, 2.4, 20.0, 0.25),
+            EntityKind::CREEPER => Self::new(0.6, 1.7, 20.0, 0.25),
+            EntityKind::SPIDER => Self::new(1.4, 0.9, 16.0, 0.3),
+            EntityKind::CAVE_SPIDER => Self::new(0.7, 0.5, 12.0, 0.3),
+            // the smallest size, which clients assume until told otherwise
+            EntityKind::SLIME => Self::new(0.52, 0.52, 1.0, 0.3),
+            EntityKind::MAGMA_CUBE => Self::new(0.52, 0.52, 1.0, 0.2),
+            EntityKind::ENDERMAN => Self::new(0.6, 2.9, 40.0, 0.3),
+            EntityKind::WITCH => Self::new(0.6, 1.95, 26.0, 0.25),
+            EntityKind::BLAZE => Self::new(0.6, 1.8, 20.0, 0.23),
+            EntityKind::SILVERFISH | EntityKind::ENDERMITE => Self::new(0.4, 0.3, 8.0, 0.25),
+            EntityKind::PILLAGER | EntityKind::VINDICATOR => Self::new(0.6, 1.95, 24.0, 0.35),
+            EntityKind::EVOKER => Self::new(0.6, 1.95, 24.0, 0.5),
+            EntityKind::ILLUSIONER => Self::new(0.6, 1.95, 32.0, 0.5),
+            EntityKind::RAVAGER => Self::new(1.95, 2.2, 100.0, 0.3),
+            EntityKind::VEX => Self::new(0.4, 0.8, 14.0, 0.7),
+            EntityKind::PIGLIN => Self::new(0.6, 1.95, 16.0, 0.35),
+            EntityKind::PIGLIN_BRUTE => Self::new(0.6, 1.95, 50.0, 0.35),
+            EntityKind::HOGLIN | EntityKind::ZOGLIN => Self::new(1.3965, 1.4, 40.0, 0.3),
+            EntityKind::GHAST => Self::new(4.0, 4.0, 10.0, 0.7),
+            EntityKind::PHANTOM => Self::new(0.9, 0.5, 20.0, 0.7),
+            EntityKind::SHULKER => Self::new(1.0, 1.0, 30.0, 0.7),
+            EntityKind::GUARDIAN => Self::new(0.85, 0.85, 30.0, 0.5),
+            EntityKind::ELDER_GUARDIAN => Self::new(1.9975, 1.9975, 80.0, 0.3),
+            EntityKind::WARDEN => Self::new(0.9, 2.9, 500.0, 0.3),
+            EntityKind::WITHER => Self::new(0.9, 3.5, 300.0, 0.6),
+            EntityKind::ENDER_DRAGON => Self::new(16.0, 8.0, 200.0, 0.7),
+
+            EntityKind::VILLAGER => Self::new(0.6, 1.95, 20.0, 0.5),
+            EntityKind::WANDERING_TRADER => Self::new(0.6, 1.95, 20.0, 0.7),
+            EntityKind::IRON_GOLEM => Self::new(1.4, 2.7, 100.0, 0.25),
+            EntityKind::SNOW_GOLEM => Self::new(0.7, 1.9, 4.0, 0.2),
+            EntityKind::ALLAY => Self::new(0.35, 0.6, 20.0, 0.1),
+            EntityKind::WOLF => Self::new(0.6, 0.85, 8.0, 0.3),
+            EntityKind::CAT | EntityKind::OCELOT | EntityKind::FOX => {
+                Self::new(0.6, 0.7, 10.0, 0.3)
+            }
+            EntityKind::COW | EntityKind::MOOSHROOM => Self::new(0.9, 1.4, 10.0, 0.2),
+            EntityKind::PIG => Self::new(0.9, 0.9, 10.0, 0.25),
+            EntityKind::SHEEP => Self::new(0.9, 1.3, 8.0, 0.23),
+            EntityKind::GOAT => Self::new(0.9, 1.3, 10.0, 0.2),
+            EntityKind::CHICKEN => Self::new(0.4, 0.7, 4.0, 0.25),
+            EntityKind::RABBIT => Self::new(0.4, 0.5, 3.0, 0.3),
+            EntityKind::PARROT => Self::new(0.5, 0.9, 6.0, 0.2),
+            EntityKind::BAT => Self::new(0.5, 0.9, 6.0, 0.7),
+            EntityKind::BEE => Self::new(0.7, 0.6, 10.0, 0.3),
+            EntityKind::PANDA => Self::new(1.3, 1.25, 20.0, 0.15),
+            EntityKind::POLAR_BEAR => Self::new(1.4, 1.4, 30.0, 0.25),
+            EntityKind::STRIDER => Self::new(0.9, 1.7, 20.0, 0.175),
+            EntityKind::CAMEL => Self::new(1.7, 2.375, 32.0, 0.09),
+            EntityKind::SNIFFER => Self::new(1.9, 1.75, 14.0, 0.1),
+            // vanilla randomizes the health and speed of each horse starting from these
+            EntityKind::HORSE => Self::new(1.3965, 1.6, 53.0, 0.225),
+            EntityKind::DONKEY => Self::new(1.3965, 1.5, 53.0, 0.175),
+            EntityKind::MULE => Self::new(1.3965, 1.6, 53.0, 0.175),
+            EntityKind::LLAMA | EntityKind::TRADER_LLAMA => Self::new(0.9, 1.87, 53.0, 0.175),
+            EntityKind::SKELETON_HORSE | EntityKind::ZOMBIE_HORSE => {
+                Self::new(1.3965, 1.6, 15.0, 0.2)
+            }
+
+            EntityKind::SQUID | EntityKind::GLOW_SQUID => Self::new(0.8, 0.8, 10.0, 0.7),
+            EntityKind::DOLPHIN => Self::new(0.9, 0.6, 10.0, 1.2),
+            EntityKind::TURTLE => Self::new(1.2, 0.4, 30.0, 0.25),
+            EntityKind::AXOLOTL => Self::new(0.75, 0.42, 14.0, 1.0),
+            EntityKind::FROG => Self::new(0.5, 0.5, 10.0, 1.0),
+            EntityKind::TADPOLE => Self::new(0.4, 0.3, 6.0, 1.0),
+            EntityKind::COD => Self::new(0.5, 0.3, 3.0, 0.7),
+            EntityKind::SALMON => Self::new(0.7, 0.4, 3.0, 0.7),
+            EntityKind::PUFFERFISH => Self::new(0.7, 0.7, 3.0, 0.7),
+            EntityKind::TROPICAL_FISH => Self::new(0.5, 0.4, 3.0, 0.7),
+
+            EntityKind::ARMOR_STAND => Self::new(0.5, 1.975, 20.0, 0.0),
+
+            // the rest are not alive, so they only have a hitbox
+            EntityKind::ARROW | EntityKind::SPECTRAL_ARROW | EntityKind::TRIDENT => {
+                Self::new(0.5, 0.5, 1.0, 0.0)
+            }
+            EntityKind::SNOWBALL
+            | EntityKind::EGG
+            | EntityKind::ENDER_PEARL
+            | EntityKind::EXPERIENCE_BOTTLE
+            | EntityKind::POTION
+            | EntityKind::EYE_OF_ENDER
+            | EntityKind::FIREWORK_ROCKET
+            | EntityKind::LLAMA_SPIT
+            | EntityKind::FISHING_BOBBER
+            | EntityKind::ITEM => Self::new(0.25, 0.25, 1.0, 0.0),
+            EntityKind::SMALL_FIREBALL | EntityKind::WITHER_SKULL | EntityKind::SHULKER_BULLET => {
+                Self::new(0.3125, 0.3125, 1.0, 0.0)
+            }
+            EntityKind::FIREBALL | EntityKind::DRAGON_FIREBALL => Self::new(1.0, 1.0, 1.0, 0.0),
+            EntityKind::EXPERIENCE_ORB => Self::new(0.5, 0.5, 1.0, 0.0),
+            EntityKind::EVOKER_FANGS => Self::new(0.5, 0.8, 1.0, 0.0),
+            EntityKind::END_CRYSTAL => Self::new(2.0, 2.0, 1.0, 0.0),
+            EntityKind::AREA_EFFECT_CLOUD => Self::new(6.0, 0.5, 1.0, 0.0),
+            EntityKind::FALLING_BLOCK | EntityKind::TNT => Self::new(0.98, 0.98, 1.0, 0.0),
+            EntityKind::BOAT | EntityKind::CHEST_BOAT => Self::new(1.375, 0.5625, 1.0, 0.0),
+            EntityKind::MINECART
+            | EntityKind::CHEST_MINECART
+            | EntityKind::COMMAND_BLOCK_MINECART
+            | EntityKind::FURNACE_MINECART
+            | EntityKind::HOPPER_MINECART
+            | EntityKind::SPAWNER_MINECART
+            | EntityKind::TNT_MINECART => Self::new(0.98, 0.7, 1.0, 0.0),
+            EntityKind::ITEM_FRAME | EntityKind::GLOW_ITEM_FRAME | EntityKind::PAINTING => {
+                Self::new(0.5, 0.5, 1.0, 0.0)
+            }
+            EntityKind::LEASH_KNOT => Self::new(0.375, 0.5, 1.0, 0.0),
+            EntityKind::BLOCK_DISPLAY
+            | EntityKind::ITEM_DISPLAY
+            | EntityKind::TEXT_DISPLAY
+            | EntityKind::INTERACTION
+            | EntityKind::MARKER
+            | EntityKind::LIGHTNING_BOLT => Self::new(0.0, 0.0, 1.0, 0.0),
+
+            _ => return None,
+        };
+
+        Some(info)
+    }
+
+    /// The hitbox of the entity standing at `feet`.
+    #[must_use]
+    pub fn bounding(&self, feet: Vec3) -> Aabb {
+        Aabb::create(feet, self.width, self.height)
+    }
+
+    /// How many blocks the entity walks per tick.
+    #[must_use]
+    pub fn blocks_per_tick(&self) -> f32 {
+        self.movement_speed * BLOCKS_PER_SPEED
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).abs().max_element() < 1e-5, "{a} != {b}");
+    }
+
+    /// The bounding boxes vanilla gives entities of each kind spawned at the origin.
+    #[test]
+    fn hitboxes_match_vanilla() {
+        for (kind, max) in [
+            (EntityKind::PLAYER, Vec3::new(0.3, 1.8, 0.3)),
+            (EntityKind::ZOMBIE, Vec3::new(0.3, 1.95, 0.3)),
+            (EntityKind::SKELETON, Vec3::new(0.3, 1.99, 0.3)),
+            (EntityKind::SPIDER, Vec3::new(0.7, 0.9, 0.7)),
+            (EntityKind::ENDERMAN, Vec3::new(0.3, 2.9, 0.3)),
+            (EntityKind::ARMOR_STAND, Vec3::new(0.25, 1.975, 0.25)),
+        ] {
+            let aabb = KindInfo::of(kind).bounding(Vec3::ZERO);
+
+            assert_close(aabb.min, Vec3::new(-max.x, 0.0, -max.z));
+            assert_close(aabb.max, max);
+        }
+    }
+
+    #[test]
+    fn attributes_match_vanilla() {
+        let zombie = KindInfo::of(EntityKind::ZOMBIE);
+        assert!((zombie.max_health - 20.0).abs() < f32::EPSILON);
+        assert!((zombie.movement_speed - 0.23).abs() < f32::EPSILON);
+
+        let spider = KindInfo::of(EntityKind::SPIDER);
+        assert!((spider.max_health - 16.0).abs() < f32::EPSILON);
+        assert!((spider.movement_speed - 0.3).abs() < f32::EPSILON);
+
+        let iron_golem = KindInfo::of(EntityKind::IRON_GOLEM);
+        assert!((iron_golem.max_health - 100.0).abs() < f32::EPSILON);
+        assert!((iron_golem.movement_speed - 0.25).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn every_kind_is_in_the_table() {
+        let kinds: Vec<_> = (0..i32::MAX)
+            .map(EntityKind::new)
+            .take_while(|kind| kind.translation_key().is_some())
+            .collect();
+
+        // the 1.20.1 entity registry
+        assert_eq!(kinds.len(), 124);
+
+        for kind in kinds {
+            assert!(KindInfo::lookup(kind).is_some(), "{kind:?} is missing");
+        }
+    }
+
+    #[test]
+    fn unknown_kinds_are_humanoid() {
+        assert_eq!(
+            KindInfo::of(EntityKind::new(-1)).bounding(Vec3::ZERO),
+            KindInfo::of(EntityKind::ZOMBIE).bounding(Vec3::ZERO)
+        );
+    }
+}
@@ -0,0 +1,260 @@
+//! Goals decide where NPCs want to go. Every tick, the goal with the highest priority which
+//! applies sets the [`Steering`] of the NPC, which `entity_move_logic` then follows.
+//!
+//! NPCs with behaviour that cannot be described with [`Goal`]s can leave out [`Goals`] and set
+//! their [`Steering`] from their own `Gametick` handler instead.
+
+use evenio::{component::Component, entity::EntityId};
+use glam::Vec3;
+
+/// Which entity a goal is about.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TargetSelector {
+    /// The closest entity with [`crate::components::AiTargetable`].
+    Closest,
+    /// A specific entity.
+    Entity(EntityId),
+}
+
+/// Something an NPC wants to do.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Goal {
+    /// Walk to random places within `radius` blocks.
+    Wander { radius: f32 },
+    /// Chase the target while it is within `range` blocks.
+    Chase { target: TargetSelector, range: f32 },
+    /// Run away from the target while it is within `range` blocks.
+    Flee { target: TargetSelector, range: f32 },
+    /// Chase the target while it is within `radius` blocks of `center`, otherwise go back to
+    /// `center`.
+    Guard {
+        center: Vec3,
+        radius: f32,
+        target: TargetSelector,
+    },
+    /// Stay within `distance` blocks of `leader`.
+    Follow { leader: EntityId, distance: f32 },
+}
+
+impl Goal {
+    /// The specific entity this goal needs the position of, if any.
+    #[must_use]
+    pub const fn entity(&self) -> Option<EntityId> {
+        match *self {
+            Self::Chase {
+                target: TargetSelector::Entity(id),
+                ..
+            }
+            | Self::Flee {
+                target: TargetSelector::Entity(id),
+                ..
+            }
+            | Self::Guard {
+                target: TargetSelector::Entity(id),
+                ..
+            }
+            | Self::Follow { leader: id, .. } => Some(id),
+            _ => None,
+        }
+    }
+}
+
+/// Where an NPC wants to go this tick.
+#[derive(Component, Debug, Copy, Clone, Default, PartialEq)]
+pub enum Steering {
+    /// Stand still.
+    #[default]
+    Idle,
+    /// Walk to `point`. If `towards` is a player, their navigation flow field is followed.
+    Seek {
+        point: Vec3,
+        towards: Option<EntityId>,
+    },
+    /// Walk directly away from `point`.
+    Flee { point: Vec3 },
+}
+
+/// The goals of an NPC, from highest to lowest priority.
+#[derive(Component, Debug, Clone, Default)]
+pub struct Goals {
+    goals: Vec<(u8, Goal)>,
+    /// Where [`Goal::Wander`] is currently walking to.
+    wander_point: Option<Vec3>,
+}
+
+impl Goals {
+    /// Adds a goal. Goals with a higher priority are considered first; goals with the same
+    /// priority are considered in the order they were added.
+    #[must_use]
+    pub fn with(mut self, priority: u8, goal: Goal) -> Self {
+        let idx = self
+            .goals
+            .partition_point(|&(existing, _)| existing >= priority);
+        self.goals.insert(idx, (priority, goal));
+        self
+    }
+
+    /// The goals from highest to lowest priority.
+    pub fn iter(&self) -> impl Iterator<Item = &Goal> {
+        self.goals.iter().map(|(_, goal)| goal)
+    }
+
+    /// Decides where to go from `position`. `resolve` finds the id and position of a target,
+    /// or `None` if there is none.
+    pub fn evaluate(
+        &mut self,
+        position: Vec3,
+        resolve: impl Fn(TargetSelector) -> Option<(EntityId, Vec3)>,
+    ) -> Steering {
+        let Self {
+            goals,
+            wander_point,
+        } = self;
+
+        for &(_, goal) in &*goals {
+            let steering = match goal {
+                Goal::Wander { radius } => Some(wander(wander_point, position, radius)),
+                Goal::Chase { target, range } => resolve(target)
+                    .filter(|(_, point)| point.distance_squared(position) <= range * range)
+                    .map(|(id, point)| Steering::Seek {
+                        point,
+                        towards: Some(id),
+                    }),
+                Goal::Flee { target, range } => resolve(target)
+                    .filter(|(_, point)| point.distance_squared(position) <= range * range)
+                    .map(|(_, point)| Steering::Flee { point }),
+                Goal::Guard {
+                    center,
+                    radius,
+                    target,
+                } => {
+                    let intruder = resolve(target)
+                        .filter(|(_, point)| point.distance_squared(center) <= radius * radius);
+
+                    match intruder {
+                        Some((id, point)) => Some(Steering::Seek {
+                            point,
+                            towards: Some(id),
+                        }),
+                        // lower priority goals apply once back at the post
+                        None if center.distance_squared(position) > 1.0 => Some(Steering::Seek {
+                            point: center,
+                            towards: None,
+                        }),
+                        None => None,
+                    }
+                }
+                Goal::Follow { leader, distance } => {
+                    resolve(TargetSelector::Entity(leader)).map(|(id, point)| {
+                        if point.distance_squared(position) > distance * distance {
+                            Steering::Seek {
+                                point,
+                                towards: Some(id),
+                            }
+                        } else {
+                            Steering::Idle
+                        }
+                    })
+                }
+            };
+
+            if let Some(steering) = steering {
+                return steering;
+            }
+        }
+
+        Steering::Idle
+    }
+}
+
+/// Walks to a random point within `radius` blocks, picking a new one once it is reached.
+fn wander(wander_point: &mut Option<Vec3>, position: Vec3, radius: f32) -> Steering {
+    let point = match *wander_point {
+        Some(point) if point.distance_squared(position) >= 1.0 => point,
+        _ => {
+            let angle = fastrand::f32() * std::f32::consts::TAU;
+            let distance = fastrand::f32() * radius;
+
+            position + Vec3::new(angle.cos(), 0.0, angle.sin()) * distance
+        }
+    };
+
+    *wander_point = Some(point);
+
+    Steering::Seek {
+        point,
+        towards: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAYER: Vec3 = Vec3::new(10.0, 0.0, 0.0);
+
+    fn closest(target: TargetSelector) -> Option<(EntityId, Vec3)> {
+        (target == TargetSelector::Closest).then_some((EntityId::NULL, PLAYER))
+    }
+
+    #[test]
+    fn highest_priority_first() {
+        let mut goals = Goals::default()
+            .with(0, Goal::Chase {
+                target: TargetSelector::Closest,
+                range: 100.0,
+            })
+            .with(1, Goal::Flee {
+                target: TargetSelector::Closest,
+                range: 5.0,
+            });
+
+        // too far away to flee from, so the chase applies
+        assert_eq!(goals.evaluate(Vec3::ZERO, closest), Steering::Seek {
+            point: PLAYER,
+            towards: Some(EntityId::NULL),
+        });
+
+        assert_eq!(
+            goals.evaluate(Vec3::new(8.0, 0.0, 0.0), closest),
+            Steering::Flee { point: PLAYER }
+        );
+    }
+
+    #[test]
+    fn guards_return_to_their_post() {
+        let center = Vec3::new(-20.0, 0.0, 0.0);
+
+        let mut goals = Goals::default()
+            .with(1, Goal::Guard {
+                center,
+                radius: 8.0,
+                target: TargetSelector::Closest,
+            })
+            .with(0, Goal::Wander { radius: 4.0 });
+
+        assert_eq!(goals.evaluate(Vec3::ZERO, closest), Steering::Seek {
+            point: center,
+            towards: None,
+        });
+
+        // at the post, so it wanders
+        let Steering::Seek { point, towards } = goals.evaluate(center, closest) else {
+            panic!("expected to wander");
+        };
+
+        assert!(point.distance(center) <= 4.0);
+        assert_eq!(towards, None);
+    }
+
+    #[test]
+    fn idle_without_goals() {
+        let mut goals = Goals::default().with(0, Goal::Follow {
+            leader: EntityId::NULL,
+            distance: 3.0,
+        });
+
+        // the leader is gone
+        assert_eq!(goals.evaluate(Vec3::ZERO, |_| None), Steering::Idle);
+    }
+}
@@ -0,0 +1,275 @@
+//! Checks of the melee attacks clients claim to make, so hacked clients cannot hit entities they
+//! could not reach.
+//!
+//! Attacks which fail a check are dropped and add to the violation score of the attacker. The
+//! score goes down again over time, so the occasional attack which fails because of latency is
+//! forgiven, while players whose score keeps growing can be kicked.
+
+use std::fmt;
+
+use bvh_region::aabb::Aabb;
+use evenio::component::Component;
+use glam::{IVec3, Vec3};
+
+use crate::components::chunks::collision::line_of_sight;
+
+/// How many ticks the attack rate of a player is counted over.
+const RATE_WINDOW_TICKS: i64 = 20;
+
+/// The limits attacks are checked against. This is a singleton which game modes can change.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct HitValidation {
+    /// How far the hitbox of the target may be from the eyes of the attacker. Vanilla clients
+    /// reach 3 blocks, the rest is leeway for latency.
+    pub max_reach: f32,
+    /// Whether attacks through solid blocks are rejected.
+    pub line_of_sight: bool,
+    /// How many attacks a player may make in a second.
+    pub max_attacks_per_second: u32,
+    /// How much the violation score of a player goes down every tick.
+    pub forgiveness: f32,
+    /// Players whose violation score reaches this are kicked. `None` never kicks.
+    pub kick_score: Option<f32>,
+}
+
+impl Default for HitValidation {
+    fn default() -> Self {
+        Self {
+            max_reach: 4.0,
+            line_of_sight: true,
+            max_attacks_per_second: 20,
+            forgiveness: 0.05,
+            kick_score: Some(20.0),
+        }
+    }
+}
+
+/// Why an attack was rejected.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Violation {
+    /// The target is the attacker, cannot be hurt, is dead or is in another instance.
+    InvalidTarget,
+    /// The attacker attacked more often than [`HitValidation::max_attacks_per_second`].
+    Rate { attacks: u32 },
+    /// The hitbox of the target is further away than [`HitValidation::max_reach`].
+    Reach { distance: f32 },
+    /// Solid blocks are between the attacker and the target.
+    LineOfSight,
+    /// The target is invisible or in a chunk the attacker has not loaded.
+    Hidden,
+}
+
+impl Violation {
+    /// How much the violation adds to the violation score. Violations which happen to honest
+    /// players with a bad connection weigh less.
+    #[must_use]
+    pub const fn weight(self) -> f32 {
+        match self {
+            Self::InvalidTarget | Self::Hidden => 0.25,
+            Self::Rate { .. } | Self::LineOfSight => 0.5,
+            Self::Reach { .. } => 1.0,
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidTarget => write!(f, "attacked an invalid target"),
+            Self::Rate { attacks } => write!(f, "attacked {attacks} times in a second"),
+            Self::Reach { distance } => write!(f, "attacked from {distance:.2} blocks away"),
+            Self::LineOfSight => write!(f, "attacked through blocks"),
+            Self::Hidden => write!(f, "attacked a target it could not see"),
+        }
+    }
+}
+
+/// How many attacks a player made in the current window of [`RATE_WINDOW_TICKS`].
+#[derive(Component, Debug, Copy, Clone, Default)]
+pub struct AttackRate {
+    window_start: i64,
+    attacks: u32,
+}
+
+impl AttackRate {
+    /// Counts an attack made on `tick`. Returns how many attacks were made in the window so far.
+    pub fn record(&mut self, tick: i64) -> u32 {
+        if tick - self.window_start >= RATE_WINDOW_TICKS {
+            self.window_start = tick;
+            self.attacks = 0;
+        }
+
+        self.attacks += 1;
+        self.attacks
+    }
+}
+
+/// How suspicious the attacks of a player have been.
+#[derive(Component, Debug, Copy, Clone, Default)]
+pub struct Violations {
+    score: f32,
+    /// The tick the score was last changed, which it has gone down from since.
+    updated: i64,
+}
+
+impl Violations {
+    /// The score on `tick`.
+    #[must_use]
+    pub fn score(&self, tick: i64, forgiveness: f32) -> f32 {
+        let forgiven = (tick - self.updated) as f32 * forgiveness;
+        (self.score - forgiven).max(0.0)
+    }
+
+    /// Adds `violation` on `tick`. Returns the new score.
+    pub fn add(&mut self, violation: Violation, tick: i64, forgiveness: f32) -> f32 {
+        self.score = self.score(tick, forgiveness) + violation.weight();
+        self.updated = tick;
+        self.score
+    }
+}
+
+impl HitValidation {
+    /// Checks that eyes at `eye` can reach the hitbox `target`. `is_solid` tells which blocks are
+    /// in the way.
+    pub fn check_reach(
+        &self,
+        eye: Vec3,
+        target: &Aabb,
+        mut is_solid: impl FnMut(IVec3) -> bool,
+    ) -> Result<(), Violation> {
+        let distance = target.dist2(eye).sqrt();
+
+        if distance > self.max_reach {
+            return Err(Violation::Reach { distance });
+        }
+
+        if !self.line_of_sight {
+            return Ok(());
+        }
+
+        // the nearest point may be hidden behind a corner while the middle is not
+        let nearest = eye.clamp(target.min, target.max);
+
+        if line_of_sight(eye, nearest, &mut is_solid)
+            || line_of_sight(eye, target.mid(), &mut is_solid)
+        {
+            Ok(())
+        } else {
+            Err(Violation::LineOfSight)
+        }
+    }
+
+    /// Counts an attack on `tick` in `rate` and checks it is not one too many.
+    pub fn check_rate(&self, rate: &mut AttackRate, tick: i64) -> Result<(), Violation> {
+        let attacks = rate.record(tick);
+
+        if attacks > self.max_attacks_per_second {
+            return Err(Violation::Rate { attacks });
+        }
+
+        Ok(())
+    }
+
+    /// Whether a player with a violation score of `score` is kicked.
+    #[must_use]
+    pub fn kicks(&self, score: f32) -> bool {
+        self.kick_score
+            .is_some_and(|kick_score| score >= kick_score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A wall at x = 2 which is two blocks high.
+    fn wall(block: IVec3) -> bool {
+        block.x == 2 && (0..2).contains(&block.y)
+    }
+
+    fn player(feet: Vec3) -> Aabb {
+        Aabb::create(feet, 0.6, 1.8)
+    }
+
+    #[test]
+    fn reach_is_measured_to_the_hitbox() {
+        let rules = HitValidation::default();
+        let eye = Vec3::new(0.5, 1.62, 0.5);
+
+        // the middle is further than the reach, but the side of the hitbox is not
+        let target = player(Vec3::new(0.5, 0.0, 4.6));
+        assert_eq!(rules.check_reach(eye, &target, wall), Ok(()));
+
+        let target = player(Vec3::new(0.5, 0.0, 10.0));
+        assert!(matches!(
+            rules.check_reach(eye, &target, wall),
+            Err(Violation::Reach { distance }) if (distance - 9.2).abs() < 1e-4
+        ));
+    }
+
+    #[test]
+    fn blocks_hide_targets() {
+        let rules = HitValidation::default();
+        let eye = Vec3::new(0.5, 1.62, 0.5);
+
+        let behind = player(Vec3::new(3.5, 0.0, 0.5));
+        assert_eq!(
+            rules.check_reach(eye, &behind, wall),
+            Err(Violation::LineOfSight)
+        );
+
+        // the nearest point is behind the wall, but the middle sticks out over it
+        let raised = player(Vec3::new(3.5, 2.0, 0.5));
+        assert_eq!(rules.check_reach(eye, &raised, wall), Ok(()));
+
+        let rules = HitValidation {
+            line_of_sight: false,
+            ..HitValidation::default()
+        };
+        assert_eq!(rules.check_reach(eye, &behind, wall), Ok(()));
+    }
+
+    #[test]
+    fn rate_is_counted_per_second() {
+        let rules = HitValidation {
+            max_attacks_per_second: 3,
+            ..HitValidation::default()
+        };
+
+        let mut rate = AttackRate::default();
+
+        for tick in 0..3 {
+            assert_eq!(rules.check_rate(&mut rate, tick), Ok(()));
+        }
+
+        assert_eq!(
+            rules.check_rate(&mut rate, 10),
+            Err(Violation::Rate { attacks: 4 })
+        );
+
+        // a new second
+        assert_eq!(rules.check_rate(&mut rate, 20), Ok(()));
+    }
+
+    #[test]
+    fn violations_are_forgiven() {
+        let rules = HitValidation::default();
+        let mut violations = Violations::default();
+
+        let mut score = 0.0;
+        for _ in 0..20 {
+            score = violations.add(Violation::Reach { distance: 5.0 }, 100, rules.forgiveness);
+        }
+
+        assert!(rules.kicks(score));
+
+        assert!((violations.score(200, rules.forgiveness) - 15.0).abs() < 1e-4);
+        assert!(violations.score(1_000, rules.forgiveness).abs() < f32::EPSILON);
+
+        let rules = HitValidation {
+            kick_score: None,
+            ..HitValidation::default()
+        };
+        assert!(!rules.kicks(score));
+    }
+}
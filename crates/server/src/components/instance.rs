@@ -0,0 +1,63 @@
+//! Instances are separate worlds running in the same process, such as lobbies and arenas.
+//!
+//! An instance is an entity with an [`Instance`], a [`Chunks`], a [`SolidSnapshot`], a
+//! [`WorldBorder`] and an [`InstanceBroadcast`].
+//! Players and NPCs belong to exactly one instance through [`InInstance`].
+
+use anyhow::Context;
+use derive_more::{Deref, DerefMut};
+use evenio::{entity::EntityId, prelude::*};
+use valence_protocol::Ident;
+use valence_registry::BiomeRegistry;
+
+use crate::{
+    components::{
+        chunks::{ChunkSource, Chunks, SolidSnapshot},
+        world_border::WorldBorder,
+    },
+    net::{buffers::BufferAllocator, Broadcast},
+};
+
+/// The dimension of an instance.
+#[derive(Component, Debug)]
+pub struct Instance {
+    /// The name of the world, such as `hyperion:lobby`. The client treats every name as a separate
+    /// world, so moving between instances with different names clears all chunks and entities.
+    pub name: Ident<String>,
+    /// The dimension type from the registry codec, such as `minecraft:overworld`.
+    pub dimension_type: Ident<String>,
+}
+
+/// The instance an entity is in.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq, Deref)]
+pub struct InInstance(pub EntityId);
+
+/// Packets which are sent to every player in one instance.
+#[derive(Component, Deref, DerefMut)]
+pub struct InstanceBroadcast(pub Broadcast);
+
+/// Spawns a new instance and returns its entity. `buffers` is the entity holding the
+/// [`BufferAllocator`].
+pub fn spawn_instance(
+    world: &mut World,
+    buffers: EntityId,
+    biomes: &BiomeRegistry,
+    instance: Instance,
+    source: ChunkSource,
+) -> anyhow::Result<EntityId> {
+    let chunks = Chunks::new(biomes, source)?;
+
+    let buffers = world
+        .get_mut::<BufferAllocator>(buffers)
+        .context("buffer allocator is missing")?;
+    let broadcast = InstanceBroadcast(Broadcast::new(buffers)?);
+
+    let id = world.spawn();
+    world.insert(id, instance);
+    world.insert(id, chunks);
+    world.insert(id, SolidSnapshot::default());
+    world.insert(id, broadcast);
+    world.insert(id, WorldBorder::default());
+
+    Ok(id)
+}
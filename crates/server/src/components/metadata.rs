@@ -0,0 +1,199 @@
+//! Tracked data of entities, which clients use to show things like sneaking, glowing and names.
+//!
+//! See <https://wiki.vg/Entity_metadata>.
+
+use bitfield_struct::bitfield;
+use evenio::component::Component;
+use valence_protocol::{packets::play, Encode, RawBytes, VarInt};
+use valence_server::entity::EntityKind;
+use valence_text::Text;
+
+use crate::event::Pose;
+
+/// The flags every entity has at index 0.
+#[bitfield(u8)]
+#[derive(PartialEq, Eq)]
+pub struct EntityFlags {
+    pub on_fire: bool,
+    pub sneaking: bool,
+    __riding: bool,
+    pub sprinting: bool,
+    pub swimming: bool,
+    pub invisible: bool,
+    pub glowing: bool,
+    pub fall_flying: bool,
+}
+
+/// The tracked data of an entity. Changes are sent to the players in the same instance by
+/// `sync_metadata` once per tick.
+#[derive(Component, Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    pub flags: EntityFlags,
+    /// The name shown above the entity.
+    pub custom_name: Option<Text>,
+    /// Whether the name is shown without looking at the entity.
+    pub custom_name_visible: bool,
+    pub pose: Pose,
+    /// Only used by kinds which have a baby variant. Armor stands are made small instead.
+    pub baby: bool,
+    /// Only used by players.
+    pub skin_parts: u8,
+}
+
+/// The type ids of metadata values.
+mod value_type {
+    pub const BYTE: i32 = 0;
+    pub const OPTIONAL_TEXT: i32 = 6;
+    pub const BOOLEAN: i32 = 8;
+    pub const POSE: i32 = 20;
+}
+
+/// The index of the baby flag of `kind`, or `None` if it has no baby variant.
+const fn baby_index(kind: EntityKind) -> Option<u8> {
+    match kind {
+        // index 16 is whether it is immune to zombification
+        EntityKind::PIGLIN => Some(17),
+        EntityKind::ZOMBIE
+        | EntityKind::HUSK
+        | EntityKind::DROWNED
+        | EntityKind::ZOMBIE_VILLAGER
+        | EntityKind::ZOMBIFIED_PIGLIN
+        | EntityKind::HOGLIN
+        | EntityKind::ZOGLIN
+        | EntityKind::VILLAGER
+        | EntityKind::WOLF
+        | EntityKind::COW
+        | EntityKind::PIG
+        | EntityKind::SHEEP
+        | EntityKind::CHICKEN => Some(16),
+        _ => None,
+    }
+}
+
+/// The flag of the armor stand flags at index 15 which makes it small.
+const ARMOR_STAND_SMALL: u8 = 0x01;
+
+fn entry(buf: &mut Vec<u8>, index: u8, value_type: i32, value: &impl Encode) {
+    buf.push(index);
+    VarInt(value_type).encode(&mut *buf).unwrap();
+    value.encode(&mut *buf).unwrap();
+}
+
+impl Metadata {
+    /// Writes the fields which differ from `previous` for an entity displayed as `kind`,
+    /// followed by the end marker. Returns `false` if nothing differs.
+    pub fn encode_changes(&self, previous: &Self, kind: EntityKind, buf: &mut Vec<u8>) -> bool {
+        let start = buf.len();
+
+        if self.flags != previous.flags {
+            entry(buf, 0, value_type::BYTE, &self.flags.into_bits());
+        }
+
+        if self.custom_name != previous.custom_name {
+            entry(buf, 2, value_type::OPTIONAL_TEXT, &self.custom_name);
+        }
+
+        if self.custom_name_visible != previous.custom_name_visible {
+            entry(buf, 3, value_type::BOOLEAN, &self.custom_name_visible);
+        }
+
+        if self.pose != previous.pose {
+            entry(buf, 6, value_type::POSE, &VarInt(self.pose as i32));
+        }
+
+        if self.baby != previous.baby {
+            if let Some(index) = baby_index(kind) {
+                entry(buf, index, value_type::BOOLEAN, &self.baby);
+            } else if kind == EntityKind::ARMOR_STAND {
+                let flags = if self.baby { ARMOR_STAND_SMALL } else { 0 };
+                entry(buf, 15, value_type::BYTE, &flags);
+            }
+        }
+
+        if kind == EntityKind::PLAYER && self.skin_parts != previous.skin_parts {
+            entry(buf, 17, value_type::BYTE, &self.skin_parts);
+        }
+
+        if buf.len() == start {
+            return false;
+        }
+
+        buf.push(0xff);
+        true
+    }
+
+    /// The packet for an entity which was just spawned, or `None` if everything is the default.
+    pub fn initial_packet<'a>(
+        &self,
+        id: VarInt,
+        kind: EntityKind,
+        buf: &'a mut Vec<u8>,
+    ) -> Option<play::EntityTrackerUpdateS2c<'a>> {
+        if !self.encode_changes(&Self::default(), kind, buf) {
+            return None;
+        }
+
+        Some(play::EntityTrackerUpdateS2c {
+            entity_id: id,
+            tracked_values: RawBytes(buf),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(metadata: &Metadata, kind: EntityKind) -> Vec<u8> {
+        let mut buf = Vec::new();
+        metadata.encode_changes(&Metadata::default(), kind, &mut buf);
+        buf
+    }
+
+    fn baby() -> Metadata {
+        Metadata {
+            baby: true,
+            ..Metadata::default()
+        }
+    }
+
+    #[test]
+    fn zombies_are_babies_at_index_16() {
+        assert_eq!(encode(&baby(), EntityKind::ZOMBIE), [16, 8, 1, 0xff]);
+
+        let burning = Metadata {
+            flags: EntityFlags::new().with_on_fire(true),
+            ..baby()
+        };
+        assert_eq!(encode(&burning, EntityKind::ZOMBIE), [
+            0, 0, 0x01, 16, 8, 1, 0xff
+        ]);
+    }
+
+    #[test]
+    fn piglins_are_babies_at_index_17() {
+        assert_eq!(encode(&baby(), EntityKind::PIGLIN), [17, 8, 1, 0xff]);
+    }
+
+    #[test]
+    fn armor_stands_are_made_small() {
+        assert_eq!(encode(&baby(), EntityKind::ARMOR_STAND), [
+            15, 0, 0x01, 0xff
+        ]);
+
+        let mut buf = Vec::new();
+        assert!(Metadata::default().encode_changes(&baby(), EntityKind::ARMOR_STAND, &mut buf));
+        assert_eq!(buf, [15, 0, 0, 0xff]);
+    }
+
+    #[test]
+    fn unchanged_metadata_is_not_encoded() {
+        let mut buf = Vec::new();
+        assert!(!baby().encode_changes(&baby(), EntityKind::ZOMBIE, &mut buf));
+        assert!(buf.is_empty());
+
+        // kinds without a baby variant ignore the flag
+        assert!(!baby().encode_changes(&Metadata::default(), EntityKind::CREEPER, &mut buf));
+        assert!(buf.is_empty());
+    }
+}
@@ -0,0 +1,193 @@
+//! Navigation of NPCs through the blocks of an instance.
+//!
+//! Instead of every NPC searching for a path on its own, each player has a [`FlowField`]: a
+//! breadth-first search outwards from where the player stands which records, for every reachable
+//! block, the next block to walk to. Any number of NPCs chasing the player then only need one
+//! lookup per tick.
+
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, VecDeque},
+};
+
+use evenio::component::Component;
+use fxhash::FxBuildHasher;
+use glam::{I16Vec2, IVec3};
+
+/// How far an NPC can drop down without taking a detour.
+const MAX_DROP: i32 = 3;
+
+/// How far down from the target to look for ground, so a jumping player still has a field.
+const MAX_GROUND_SEARCH: i32 = 4;
+
+const DIRECTIONS: [IVec3; 4] = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z];
+
+/// The next step towards a target from every block which can reach it, where a block is the
+/// space an NPC stands in.
+#[derive(Debug, Clone, Default)]
+pub struct FlowField {
+    target: IVec3,
+    next: HashMap<IVec3, IVec3, FxBuildHasher>,
+    /// The revision of every column the field covers when it was computed.
+    revisions: Vec<(I16Vec2, u64)>,
+}
+
+/// The flow field NPCs follow to chase a player.
+#[derive(Component, Debug, Default)]
+pub struct Navigation {
+    /// This is `None` until it is first computed.
+    pub field: Option<FlowField>,
+    /// The tick the field was computed.
+    pub computed_at: i64,
+    /// The block the player was in when the field was computed.
+    pub computed_from: IVec3,
+}
+
+/// Whether an NPC can stand in `block`: it needs two free blocks and ground below.
+fn standable(block: IVec3, is_solid: &mut impl FnMut(IVec3) -> bool) -> bool {
+    !is_solid(block) && !is_solid(block + IVec3::Y) && is_solid(block - IVec3::Y)
+}
+
+impl FlowField {
+    /// Searches outwards from `target` up to `radius` blocks horizontally.
+    ///
+    /// `revision` gives the revision of a column, which is stored so the field can be recomputed
+    /// once any of its blocks change.
+    pub fn compute(
+        target: IVec3,
+        radius: i32,
+        mut is_solid: impl FnMut(IVec3) -> bool,
+        revision: impl Fn(I16Vec2) -> u64,
+    ) -> Self {
+        let target = (0..MAX_GROUND_SEARCH)
+            .map(|depth| target - IVec3::Y * depth)
+            .find(|&block| standable(block, &mut is_solid))
+            .unwrap_or(target);
+
+        let mut next = HashMap::default();
+        next.insert(target, target);
+
+        let mut queue = VecDeque::from([target]);
+
+        while let Some(to) = queue.pop_front() {
+            for direction in DIRECTIONS {
+                let column = to + direction;
+
+                let offset = column - target;
+                if offset.x.abs() > radius || offset.z.abs() > radius {
+                    continue;
+                }
+
+                // every block an NPC could come from to reach `to`: dropping down onto it or
+                // stepping up by one
+                for from_y in (to.y - 1..=to.y + MAX_DROP).rev() {
+                    let from = IVec3::new(column.x, from_y, column.z);
+
+                    if next.contains_key(&from) || !standable(from, &mut is_solid) {
+                        continue;
+                    }
+
+                    let clear = match from_y.cmp(&to.y) {
+                        // the column being dropped through has to be free
+                        Ordering::Greater => {
+                            (to.y + 2..=from_y + 1).all(|y| !is_solid(IVec3::new(to.x, y, to.z)))
+                        }
+                        // room to jump up
+                        Ordering::Less => !is_solid(from + IVec3::Y * 2),
+                        Ordering::Equal => true,
+                    };
+
+                    if clear {
+                        next.insert(from, to);
+                        queue.push_back(from);
+                    }
+                }
+            }
+        }
+
+        let columns = |block: &IVec3| I16Vec2::new((block.x >> 4) as i16, (block.z >> 4) as i16);
+
+        let mut revisions: Vec<_> = next.keys().map(columns).map(|column| (column, 0)).collect();
+        revisions.sort_unstable_by_key(|&(column, _)| (column.x, column.y));
+        revisions.dedup();
+
+        for (column, rev) in &mut revisions {
+            *rev = revision(*column);
+        }
+
+        Self {
+            target,
+            next,
+            revisions,
+        }
+    }
+
+    /// The block the field leads to.
+    #[must_use]
+    pub const fn target(&self) -> IVec3 {
+        self.target
+    }
+
+    /// The next block to walk to from `block`, or `None` if the target cannot be reached from
+    /// there. Blocks just above a reachable block also work, for NPCs which are mid-jump.
+    #[must_use]
+    pub fn next_step(&self, block: IVec3) -> Option<IVec3> {
+        (0..=2)
+            .map(|depth| block - IVec3::Y * depth)
+            .find_map(|block| self.next.get(&block).copied())
+    }
+
+    /// Whether none of the columns the field covers have changed since it was computed.
+    pub fn is_current(&self, revision: impl Fn(I16Vec2) -> u64) -> bool {
+        self.revisions
+            .iter()
+            .all(|&(column, rev)| revision(column) == rev)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A floor at y = 0 with a wall at x = 3 from z = -5 to z = 5.
+    fn world(block: IVec3) -> bool {
+        block.y < 0 || (block.x == 3 && (-5..=5).contains(&block.z) && block.y <= 2)
+    }
+
+    #[test]
+    fn walks_around_walls() {
+        let field = FlowField::compute(IVec3::new(6, 0, 0), 16, world, |_| 0);
+
+        let mut block = IVec3::new(0, 0, 0);
+        let mut steps = 0;
+
+        while block != field.target() {
+            let next = field.next_step(block).unwrap();
+            assert!(!world(next));
+
+            block = next;
+            steps += 1;
+            assert!(steps < 100);
+        }
+
+        // around the end of the wall and back
+        assert!(steps > 6);
+    }
+
+    #[test]
+    fn tracks_revisions() {
+        let field = FlowField::compute(IVec3::ZERO, 4, world, |_| 1);
+
+        assert!(field.is_current(|_| 1));
+        assert!(!field.is_current(|column| u64::from(column == I16Vec2::ZERO) + 1));
+    }
+
+    #[test]
+    fn unreachable_blocks() {
+        let field = FlowField::compute(IVec3::ZERO, 4, world, |_| 0);
+
+        assert!(field.next_step(IVec3::new(100, 0, 0)).is_none());
+        // inside the wall
+        assert!(field.next_step(IVec3::new(3, 0, 0)).is_none());
+    }
+}
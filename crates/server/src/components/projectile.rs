@@ -0,0 +1,135 @@
+//! Projectiles like arrows, snowballs and tridents.
+//!
+//! Projectiles move too fast to check for collisions at the end of each tick, so the path they
+//! travel during a tick is swept against blocks and entities instead.
+
+use evenio::{component::Component, entity::EntityId};
+use glam::Vec3;
+use valence_server::entity::EntityKind;
+
+use crate::components::combat::DamageType;
+
+/// Projectiles which are stuck in a block despawn after this many ticks, like vanilla.
+pub const STUCK_DESPAWN_TICKS: i64 = 1200;
+
+/// The owner cannot be hit by their own projectile for this many ticks so it does not hit them
+/// while leaving their hitbox.
+pub const OWNER_GRACE_TICKS: u32 = 5;
+
+/// How much of its velocity a projectile keeps each tick.
+pub const DRAG: f32 = 0.99;
+
+/// The knockback of a hit on top of the knockback of every hit, along the direction the
+/// projectile flew. This is as strong as one level of the knockback enchantment.
+pub const KNOCKBACK: f32 = 0.5;
+
+/// The kinds of projectiles, which differ in how they fly and what they do on impact.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ProjectileKind {
+    Arrow,
+    Snowball,
+    Trident,
+}
+
+impl ProjectileKind {
+    /// The kind of entity shown to clients.
+    #[must_use]
+    pub const fn entity_kind(self) -> EntityKind {
+        match self {
+            Self::Arrow => EntityKind::ARROW,
+            Self::Snowball => EntityKind::SNOWBALL,
+            Self::Trident => EntityKind::TRIDENT,
+        }
+    }
+
+    /// Blocks per tick squared.
+    #[must_use]
+    pub const fn gravity(self) -> f32 {
+        match self {
+            Self::Arrow | Self::Trident => 0.05,
+            Self::Snowball => 0.03,
+        }
+    }
+
+    /// How clients show that an entity was hit by the projectile.
+    #[must_use]
+    pub const fn damage_type(self) -> DamageType {
+        match self {
+            Self::Arrow => DamageType::Arrow,
+            Self::Snowball => DamageType::Thrown,
+            Self::Trident => DamageType::Trident,
+        }
+    }
+
+    /// Whether the projectile sticks in blocks instead of breaking.
+    #[must_use]
+    pub const fn sticks(self) -> bool {
+        matches!(self, Self::Arrow | Self::Trident)
+    }
+
+    /// The damage dealt by hitting an entity at `velocity`, measured in half hearts.
+    #[must_use]
+    pub fn damage(self, velocity: Vec3) -> f32 {
+        match self {
+            // like vanilla, faster arrows deal more damage
+            Self::Arrow => (velocity.length() * 2.0).ceil(),
+            Self::Snowball => 0.0,
+            Self::Trident => 8.0,
+        }
+    }
+}
+
+/// How strongly a bow which was drawn for `ticks` shoots, from 0 to 1.
+#[must_use]
+pub fn bow_power(ticks: i64) -> f32 {
+    let seconds = ticks as f32 / 20.0;
+    let power = seconds.mul_add(seconds, seconds * 2.0) / 3.0;
+
+    power.min(1.0)
+}
+
+/// An entity which flies until it hits something.
+#[derive(Component, Debug, Copy, Clone)]
+pub struct Projectile {
+    pub kind: ProjectileKind,
+    /// The entity which launched the projectile.
+    pub owner: EntityId,
+    /// Blocks per tick.
+    pub velocity: Vec3,
+    /// The tick the projectile got stuck in a block, if it did.
+    pub stuck_since: Option<i64>,
+    /// How many ticks the projectile has been flying.
+    pub age: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use bvh_region::aabb::Aabb;
+
+    use super::*;
+
+    #[test]
+    fn sweeps_through_entities() {
+        let arrow = Aabb::create(Vec3::ZERO, 0.5, 0.5);
+        let zombie = Aabb::create(Vec3::new(5.0, 0.0, 0.0), 0.6, 1.95);
+
+        let (hit, normal) = arrow.sweep(Vec3::new(10.0, 0.0, 0.0), &zombie).unwrap();
+        assert!((hit - 0.445).abs() < 1e-4);
+        assert_eq!(normal, Vec3::NEG_X);
+
+        // too short
+        assert!(arrow.sweep(Vec3::new(2.0, 0.0, 0.0), &zombie).is_none());
+
+        // passes above
+        let above = arrow.move_by(Vec3::new(0.0, 3.0, 0.0));
+        assert!(above.sweep(Vec3::new(10.0, 0.0, 0.0), &zombie).is_none());
+    }
+
+    #[test]
+    fn bow_charges_in_a_second() {
+        assert!(bow_power(0).abs() < f32::EPSILON);
+        assert!(bow_power(10) < 1.0);
+        assert!((bow_power(20) - 1.0).abs() < f32::EPSILON);
+        assert!((bow_power(100) - 1.0).abs() < f32::EPSILON);
+    }
+}
@@ -0,0 +1,176 @@
+//! The world border of an instance, which can shrink or grow over time.
+
+use evenio::component::Component;
+use glam::{DVec2, Vec3};
+use serde::{Deserialize, Serialize};
+use valence_protocol::packets::play;
+
+/// The largest diameter the client supports. This is the default in vanilla.
+pub const MAX_DIAMETER: f64 = 59_999_968.0;
+
+/// What happens to entities which are outside the world border.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BorderAction {
+    /// Nothing; the border is only shown to clients.
+    #[default]
+    None,
+    /// Move them back just inside the border.
+    Push,
+    /// Hurt them more the farther outside they are, like vanilla.
+    Damage,
+    /// Move them to the center of the border, keeping their height.
+    Teleport,
+}
+
+/// The world border of an instance. Every instance has one, which is [`MAX_DIAMETER`] wide unless
+/// it is changed with [`crate::event::SetWorldBorder`].
+///
+/// The diameter is interpolated linearly between ticks, the same way the client animates it.
+#[derive(Component, Debug, Copy, Clone)]
+pub struct WorldBorder {
+    /// The center on the x and z axes.
+    pub center: DVec2,
+    pub action: BorderAction,
+    /// How much damage an entity takes per block it is beyond the safe zone, with
+    /// [`BorderAction::Damage`].
+    pub damage_per_block: f32,
+    /// How many blocks outside the border are not damaged, with [`BorderAction::Damage`].
+    pub safe_zone: f64,
+    old_diameter: f64,
+    new_diameter: f64,
+    /// The tick the current interpolation started.
+    start_tick: i64,
+    /// How many ticks the current interpolation lasts.
+    duration_ticks: i64,
+}
+
+impl Default for WorldBorder {
+    fn default() -> Self {
+        Self::new(DVec2::ZERO, MAX_DIAMETER, BorderAction::default())
+    }
+}
+
+impl WorldBorder {
+    #[must_use]
+    pub const fn new(center: DVec2, diameter: f64, action: BorderAction) -> Self {
+        Self {
+            center,
+            action,
+            damage_per_block: 0.2,
+            safe_zone: 5.0,
+            old_diameter: diameter,
+            new_diameter: diameter,
+            start_tick: 0,
+            duration_ticks: 0,
+        }
+    }
+
+    /// The diameter at the given tick.
+    #[must_use]
+    pub fn diameter(&self, tick: i64) -> f64 {
+        let elapsed = tick - self.start_tick;
+
+        if elapsed >= self.duration_ticks {
+            return self.new_diameter;
+        }
+
+        let progress = elapsed.max(0) as f64 / self.duration_ticks as f64;
+        (self.new_diameter - self.old_diameter).mul_add(progress, self.old_diameter)
+    }
+
+    /// The diameter the border is moving towards.
+    #[must_use]
+    pub const fn target_diameter(&self) -> f64 {
+        self.new_diameter
+    }
+
+    /// How many ticks are left until the border reaches its target diameter.
+    #[must_use]
+    pub fn remaining_ticks(&self, tick: i64) -> i64 {
+        (self.start_tick + self.duration_ticks - tick).max(0)
+    }
+
+    /// Moves the border from its current diameter to `diameter` over `duration_ticks` ticks.
+    pub fn interpolate(&mut self, tick: i64, diameter: f64, duration_ticks: i64) {
+        self.old_diameter = self.diameter(tick);
+        self.new_diameter = diameter;
+        self.start_tick = tick;
+        self.duration_ticks = duration_ticks.max(0);
+    }
+
+    /// How far `position` is outside the border. This is negative if it is inside.
+    #[must_use]
+    pub fn distance_outside(&self, tick: i64, position: Vec3) -> f64 {
+        let half = self.diameter(tick) / 2.0;
+        let offset = (DVec2::new(f64::from(position.x), f64::from(position.z)) - self.center).abs();
+
+        offset.max_element() - half
+    }
+
+    /// The closest position to `position` which is inside the border.
+    #[must_use]
+    pub fn clamp(&self, tick: i64, position: Vec3) -> Vec3 {
+        // stay a little inside so rounding does not leave the entity on the edge
+        let half = (self.diameter(tick) / 2.0 - 0.5).max(0.0);
+
+        let min = self.center - half;
+        let max = self.center + half;
+
+        let x = f64::from(position.x).clamp(min.x, max.x);
+        let z = f64::from(position.z).clamp(min.y, max.y);
+
+        Vec3::new(x as f32, position.y, z as f32)
+    }
+
+    /// The packet which tells a client about the whole border, including any interpolation in
+    /// progress.
+    #[must_use]
+    pub fn initialize_packet(&self, tick: i64) -> play::WorldBorderInitializeS2c {
+        play::WorldBorderInitializeS2c {
+            x: self.center.x,
+            z: self.center.y,
+            old_diameter: self.diameter(tick),
+            new_diameter: self.new_diameter,
+            duration_millis: (self.remaining_ticks(tick) * 50).into(),
+            portal_teleport_boundary: 29_999_984.into(),
+            warning_blocks: 50.into(),
+            warning_time: 200.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolates_linearly() {
+        let mut border = WorldBorder::new(DVec2::ZERO, 100.0, BorderAction::Push);
+
+        border.interpolate(10, 50.0, 20);
+
+        assert!((border.diameter(10) - 100.0).abs() < f64::EPSILON);
+        assert!((border.diameter(20) - 75.0).abs() < f64::EPSILON);
+        assert!((border.diameter(30) - 50.0).abs() < f64::EPSILON);
+        assert!((border.diameter(100) - 50.0).abs() < f64::EPSILON);
+        assert_eq!(border.remaining_ticks(25), 5);
+
+        // changing the target midway starts from where the border is
+        border.interpolate(20, 100.0, 10);
+        assert!((border.diameter(20) - 75.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn clamps_inside() {
+        let border = WorldBorder::new(DVec2::new(10.0, -10.0), 20.0, BorderAction::Push);
+
+        let outside = Vec3::new(30.0, 64.0, -10.0);
+        assert!(border.distance_outside(0, outside) > 0.0);
+
+        let clamped = border.clamp(0, outside);
+        assert!(border.distance_outside(0, clamped) < 0.0);
+        assert!((clamped.y - 64.0).abs() < f32::EPSILON);
+        assert!((clamped.z + 10.0).abs() < f32::EPSILON);
+    }
+}
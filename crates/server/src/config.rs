@@ -6,6 +6,8 @@ use serde::{Deserialize, Serialize};
 use spin::lazy::Lazy;
 use tracing::{info, instrument, warn};
 
+use crate::components::world_border::BorderAction;
+
 /// The configuration for the server.
 ///
 /// todo: remove static and make this an `Arc` to prevent weird behavior with multiple `Game`s
@@ -14,22 +16,47 @@ pub static CONFIG: Lazy<Config> = Lazy::new(|| Config::load("run/config.toml").u
 /// The configuration for the server representing a `toml` file.
 #[allow(clippy::missing_docs_in_private_items, reason = "self-explanatory")]
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub struct Config {
     pub border_diameter: Option<f64>,
+    /// What happens to players and NPCs outside the world border. Nothing happens by default, so
+    /// a border only confines players once this is set.
+    pub border_action: BorderAction,
     pub max_players: i32,
     pub view_distance: i32,
     pub simulation_distance: i32,
     pub server_desc: String,
+    /// How many bytes the chunk cache may use for the packets, blocks and solid blocks of
+    /// columns.
+    pub chunk_cache_bytes: usize,
+    /// Chunks within this many chunks of spawn are never evicted from the chunk cache.
+    pub protected_spawn_radius: i32,
+    /// How many bytes of chunks may be sent to all players combined in one tick.
+    pub chunk_bytes_per_tick: usize,
+    /// How many bytes of chunks may be sent to a single player in one tick.
+    pub chunk_bytes_per_player_tick: usize,
+    /// How many chunks may be sent to a single player in one tick.
+    pub chunks_per_player_tick: usize,
+    /// How many blocks away from a player NPCs find their way to them instead of walking straight
+    /// at them.
+    pub navigation_radius: i32,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             border_diameter: Some(100.0),
+            border_action: BorderAction::None,
             max_players: 10_000,
             view_distance: 32,
             simulation_distance: 10,
             server_desc: "Hyperion Test Server".to_owned(),
+            chunk_cache_bytes: 1024 * 1024 * 1024,
+            protected_spawn_radius: 8,
+            chunk_bytes_per_tick: 64 * 1024 * 1024,
+            chunk_bytes_per_player_tick: 512 * 1024,
+            chunks_per_player_tick: 32,
+            navigation_radius: 32,
         }
     }
 }
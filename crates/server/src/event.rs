@@ -3,17 +3,22 @@ use std::{alloc::Allocator, borrow::Cow, cell::RefCell, fmt::Debug};
 use bumpalo::Bump;
 use derive_more::{Deref, DerefMut};
 use evenio::{component::Component, entity::EntityId, event::Event};
-use glam::Vec3;
+use glam::{Vec2, Vec3};
 use rayon_local::RayonLocal;
 use valence_generated::{block::BlockState, status_effects::StatusEffect};
+use valence_nbt::Compound;
 use valence_protocol::{
-    packets::play::entity_equipment_update_s2c::EquipmentEntry, BlockPos, Hand,
+    packets::play::{
+        client_settings_c2s::{DisplayedSkinParts, MainArm},
+        entity_equipment_update_s2c::EquipmentEntry,
+    },
+    BlockPos, Hand,
 };
 use valence_server::entity::EntityKind;
 use valence_text::Text;
 
 use crate::{
-    components::FullEntityPose,
+    components::{goals::Goals, projectile::ProjectileKind, FullEntityPose},
     net::{Server, MAX_PACKET_SIZE},
     util::player_skin::PlayerSkin,
 };
@@ -23,7 +28,12 @@ use crate::{
 pub struct InitEntity {
     /// The pose of the entity.
     pub pose: FullEntityPose,
+    /// The kind of entity, which also decides its hitbox, health and speed.
     pub display: EntityKind,
+    /// The instance to spawn the entity in, or `None` for the default instance.
+    pub instance: Option<EntityId>,
+    /// What the entity does, or `None` to chase the closest player.
+    pub goals: Option<Goals>,
 }
 
 #[derive(Event)]
@@ -67,9 +77,10 @@ pub struct KickPlayer {
     pub reason: String,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
 #[repr(i32)]
 pub enum Pose {
+    #[default]
     Standing = 0,
     FallFlying = 1,
     Sleeping = 2,
@@ -115,6 +126,11 @@ pub struct HurtEntity {
 pub enum AttackType {
     Shove,
     Melee,
+    Projectile {
+        kind: ProjectileKind,
+        /// The projectile entity, which is despawned once it hits.
+        projectile: EntityId,
+    },
 }
 
 #[derive(Event)]
@@ -125,7 +141,10 @@ pub struct AttackEntity {
     /// The location of the player that is hitting.
     pub from_pos: Vec3,
     pub from: EntityId,
+    /// Measured in half hearts, before the armor of the target absorbs any of it.
     pub damage: f32,
+    /// Knockback on top of the knockback of every hit, whose length is its strength.
+    pub knockback: Vec2,
     pub source: AttackType,
 }
 
@@ -148,6 +167,53 @@ pub struct Teleport {
     pub position: Vec3,
 }
 
+/// Changes the world border of an instance. See [`crate::components::world_border`].
+#[derive(Event, Debug)]
+pub struct SetWorldBorder {
+    /// The instance the border belongs to.
+    #[event(target)]
+    pub instance: EntityId,
+    pub diameter: f64,
+    /// How many ticks the border takes to reach the diameter. Zero changes it right away.
+    pub duration_ticks: i64,
+}
+
+/// Moves a player to another instance. See [`crate::components::instance`].
+#[derive(Event, Debug)]
+pub struct ChangeInstance {
+    #[event(target)]
+    pub target: EntityId,
+    /// The entity of the instance to move to.
+    pub instance: EntityId,
+    pub position: Vec3,
+}
+
+/// Sent when a player starts using the item in their hand, like drawing a bow.
+#[derive(Event)]
+pub struct UseItem {
+    #[event(target)]
+    pub target: EntityId,
+    pub hand: Hand,
+}
+
+/// Sent when a player stops using an item, like releasing a bow.
+#[derive(Event)]
+pub struct ReleaseItem {
+    #[event(target)]
+    pub target: EntityId,
+}
+
+/// Launches a projectile from the eyes of an entity, which becomes its owner. See
+/// [`crate::components::projectile`].
+#[derive(Event, Debug)]
+pub struct LaunchProjectile {
+    #[event(target)]
+    pub shooter: EntityId,
+    pub kind: ProjectileKind,
+    /// Blocks per tick.
+    pub velocity: Vec3,
+}
+
 /// i.e., when zombies bump into another player
 #[derive(Debug)]
 pub struct Shoved {
@@ -244,13 +310,38 @@ pub struct BlockFinishBreak {
     pub sequence: i32,
 }
 
+/// Sets a block in an instance.
 #[derive(Event, Debug)]
 pub struct UpdateBlock {
+    /// The instance the block is in.
+    #[event(target)]
+    pub instance: EntityId,
     pub position: BlockPos,
     pub id: BlockState,
     pub sequence: i32,
 }
 
+/// Sent when a client reports its settings, which happens on join and whenever they change.
+#[derive(Event, Debug)]
+pub struct ClientSettingsUpdate {
+    #[event(target)]
+    pub target: EntityId,
+    pub view_distance: u8,
+    pub locale: String,
+    pub main_arm: MainArm,
+    pub displayed_skin_parts: DisplayedSkinParts,
+}
+
+/// Sets the data of a block entity, such as the text of a sign.
+#[derive(Event, Debug)]
+pub struct UpdateBlockEntity {
+    /// The instance the block entity is in.
+    #[event(target)]
+    pub instance: EntityId,
+    pub position: BlockPos,
+    pub data: Compound,
+}
+
 #[derive(Event)]
 pub struct ChatMessage {
     #[event(target)]
@@ -32,6 +32,7 @@ use std::{
 use anyhow::Context;
 use derive_more::From;
 use evenio::prelude::*;
+use glam::DVec2;
 use humansize::{SizeFormatter, BINARY};
 use libc::{getrlimit, setrlimit, RLIMIT_NOFILE};
 use libdeflater::CompressionLvl;
@@ -40,20 +41,25 @@ use signal_hook::iterator::Signals;
 use singleton::bounding_box;
 use spin::Lazy;
 use tracing::{error, info, instrument, warn};
-use valence_protocol::CompressionThreshold;
+use valence_protocol::{ident, CompressionThreshold};
 pub use valence_server;
 
 use crate::{
     components::{
-        chunks::{Chunks, Tasks},
-        Vitals,
+        chunks::{ChunkSource, Tasks},
+        combat::CombatRules,
+        hit_validation::HitValidation,
+        instance::{spawn_instance, Instance},
+        world_border::WorldBorder,
+        Vitals, PLAYER_SPAWN_POSITION,
     },
     event::{Egress, Gametick, Scratches, Stats},
     global::Global,
     net::{buffers::BufferAllocator, Broadcast, Compressors, Server, ServerDef, S2C_BUFFER_SIZE},
     singleton::{
-        fd_lookup::FdLookup, player_aabb_lookup::PlayerBoundingBoxes,
-        player_id_lookup::EntityIdLookup, player_uuid_lookup::PlayerUuidLookup,
+        default_instance::DefaultInstance, fd_lookup::FdLookup, npc_chunks::NpcChunks,
+        player_aabb_lookup::PlayerBoundingBoxes, player_id_lookup::EntityIdLookup,
+        player_uuid_lookup::PlayerUuidLookup,
     },
     system::{generate_biome_registry, generate_ingress_events},
 };
@@ -203,6 +209,8 @@ pub struct Hyperion {
     tick_on: u64,
 
     server: Server,
+    /// The entity holding the [`BufferAllocator`].
+    buffers: EntityId,
 }
 
 impl Hyperion {
@@ -221,6 +229,25 @@ impl Hyperion {
         &mut self.world
     }
 
+    /// Creates a new instance, such as a lobby or an arena, and returns its entity. Players can be
+    /// moved to it with [`event::ChangeInstance`].
+    pub fn create_instance(
+        &mut self,
+        instance: Instance,
+        source: ChunkSource,
+    ) -> anyhow::Result<EntityId> {
+        let biome_registry =
+            generate_biome_registry().context("failed to generate biome registry")?;
+
+        spawn_instance(
+            &mut self.world,
+            self.buffers,
+            &biome_registry,
+            instance,
+            source,
+        )
+    }
+
     /// # Panics
     /// This function will panic if the game is already shutdown.
     pub const fn shutdown(&self) {
@@ -320,15 +347,22 @@ impl Hyperion {
 
         world.add_handler(system::chunks::generate_chunk_changes);
         world.add_handler(system::chunks::send_updates);
+        world.add_handler(system::chunks::send_light_updates);
+        world.add_handler(system::visibility::update_visible_entities);
 
         world.add_handler(system::init_player);
         world.add_handler(system::despawn_player);
         world.add_handler(system::player_join_world);
         world.add_handler(system::player_kick);
         world.add_handler(system::init_entity);
+        world.add_handler(system::snapshot_solids);
+        world.add_handler(system::update_navigation);
+        world.add_handler(system::evaluate_goals);
         world.add_handler(system::entity_move_logic);
         world.add_handler(system::entity_detect_collisions);
+        world.add_handler(system::projectile::simulate);
         world.add_handler(system::sync_entity_position);
+        world.add_handler(system::sync_metadata);
         world.add_handler(system::recalculate_bounding_boxes);
         world.add_handler(system::update_time);
         world.add_handler(system::send_time);
@@ -339,6 +373,12 @@ impl Hyperion {
 
         world.add_handler(system::equipment::set);
 
+        world.add_handler(system::projectile::use_item);
+        world.add_handler(system::projectile::release_item);
+        world.add_handler(system::projectile::launch);
+
+        world.add_handler(system::validate_attack);
+        world.add_handler(system::melee_damage);
         world.add_handler(system::check_immunity);
         world.add_handler(system::pkt_attack_player);
         world.add_handler(system::pkt_attack_entity);
@@ -346,11 +386,16 @@ impl Hyperion {
         world.add_handler(system::compass);
 
         world.add_handler(system::block_update);
+        world.add_handler(system::block_entity_update);
         world.add_handler(system::chat_message);
         world.add_handler(system::disguise_player);
         world.add_handler(system::teleport);
+        world.add_handler(system::change_instance);
+        world.add_handler(system::world_border::set);
+        world.add_handler(system::world_border::enforce);
         world.add_handler(system::shoved_reaction);
         world.add_handler(system::pose_update);
+        world.add_handler(system::client_settings);
 
         world.add_handler(system::effect::display);
         world.add_handler(system::effect::speed);
@@ -371,16 +416,46 @@ impl Hyperion {
         let scratches = world.spawn();
         world.insert(scratches, Scratches::default());
 
+        let combat_rules = world.spawn();
+        world.insert(combat_rules, CombatRules::default());
+
+        let hit_validation = world.spawn();
+        world.insert(hit_validation, HitValidation::default());
+
         let bounding_boxes = world.spawn();
         world.insert(bounding_boxes, bounding_box::EntityBoundingBoxes::default());
 
         let uuid_lookup = world.spawn();
         world.insert(uuid_lookup, PlayerUuidLookup::default());
 
-        let chunks = world.spawn();
         let biome_registry =
             generate_biome_registry().context("failed to generate biome registry")?;
-        world.insert(chunks, Chunks::new(&biome_registry)?);
+
+        let new_york = Instance {
+            name: ident!("overworld").to_string_ident(),
+            dimension_type: ident!("overworld").to_string_ident(),
+        };
+
+        let default_instance = spawn_instance(
+            &mut world,
+            buffers_id,
+            &biome_registry,
+            new_york,
+            ChunkSource::new_york()?,
+        )?;
+
+        if let Some(diameter) = config::CONFIG.border_diameter {
+            let center = PLAYER_SPAWN_POSITION.as_dvec3();
+            let border = WorldBorder::new(
+                DVec2::new(center.x, center.z),
+                diameter,
+                config::CONFIG.border_action,
+            );
+            world.insert(default_instance, border);
+        }
+
+        let default_instance_id = world.spawn();
+        world.insert(default_instance_id, DefaultInstance(default_instance));
 
         let tasks = world.spawn();
         world.insert(tasks, Tasks::default());
@@ -394,12 +469,16 @@ impl Hyperion {
         let fd_lookup = world.spawn();
         world.insert(fd_lookup, FdLookup::default());
 
+        let npc_chunks = world.spawn();
+        world.insert(npc_chunks, NpcChunks::default());
+
         let mut game = Self {
             shared,
             world,
             last_ticks: VecDeque::default(),
             tick_on: 0,
             server: server_def,
+            buffers: buffers_id,
         };
 
         game.last_ticks.push_back(Instant::now());
@@ -147,6 +147,9 @@ pub struct Packets {
     buffer: BufRef,
     pub local_to_write: ArrayVec<DataWriteInfo, 2>,
     pub number_sending: u8,
+    /// The bytes handed to the socket which have not been sent yet. Sends only report how many
+    /// writes finished, so these are counted until every write has.
+    pub sending_bytes: usize,
 }
 
 impl Packets {
@@ -156,6 +159,7 @@ impl Packets {
             buffer: allocator.obtain().context("failed to obtain buffer")?,
             local_to_write: ArrayVec::new(),
             number_sending: 0,
+            sending_bytes: 0,
         })
     }
 
@@ -176,6 +180,10 @@ impl Packets {
     pub fn set_successfully_sent(&mut self, d_count: u8) {
         debug_assert!(self.number_sending >= d_count);
         self.number_sending -= d_count;
+
+        if self.number_sending == 0 {
+            self.sending_bytes = 0;
+        }
     }
 
     pub fn append<P>(&mut self, pkt: &P, compose: &Compose) -> anyhow::Result<()>
@@ -248,6 +256,19 @@ impl Packets {
     pub const fn can_send(&self) -> bool {
         self.number_sending == 0
     }
+
+    /// The number of bytes which have been appended but not sent yet, including those which have
+    /// been handed to the socket already.
+    #[must_use]
+    pub fn queued_bytes(&self) -> usize {
+        let local: usize = self
+            .local_to_write
+            .iter()
+            .map(|elem| elem.len as usize)
+            .sum();
+
+        local + self.sending_bytes
+    }
 }
 
 #[derive(Debug, Default)]
@@ -18,7 +18,8 @@ use crate::{
 
 const COUNT: usize = 1024;
 
-const BUFFER_SIZE: usize = MAX_PACKET_SIZE * 4;
+/// The size of the ring buffer each player's packets are written to.
+pub const BUFFER_SIZE: usize = MAX_PACKET_SIZE * 4;
 
 #[derive(Component)]
 pub struct BufferAllocator {
@@ -3,7 +3,7 @@
 use evenio::entity::EntityId;
 use valence_protocol::{
     decode::PacketFrame,
-    math::Vec3,
+    math::{Vec2, Vec3},
     packets::{
         play,
         play::{
@@ -15,7 +15,7 @@ use valence_protocol::{
 };
 
 use crate::{
-    components::FullEntityPose,
+    components::{combat::WeaponStats, FullEntityPose, PlayerMotion},
     event,
     event::{AttackEntity, AttackType, Pose, SwingArm},
     singleton::player_id_lookup::EntityIdLookup,
@@ -29,7 +29,7 @@ const fn confirm_teleport(_pkt: &[u8]) {
     // ignore
 }
 
-fn full(mut data: &[u8], full_entity_pose: &mut FullEntityPose) -> anyhow::Result<()> {
+fn full(mut data: &[u8], query: &mut PacketSwitchQuery) -> anyhow::Result<()> {
     const MAX_SPEED: f32 = 100.0;
 
     let pkt = play::FullC2s::decode(&mut data)?;
@@ -38,9 +38,11 @@ fn full(mut data: &[u8], full_entity_pose: &mut FullEntityPose) -> anyhow::Resul
         position,
         yaw,
         pitch,
-        ..
+        on_ground,
     } = pkt;
 
+    let full_entity_pose = &mut *query.pose;
+
     // check to see if the player is moving too fast
     // if they are, ignore the packet
 
@@ -52,6 +54,8 @@ fn full(mut data: &[u8], full_entity_pose: &mut FullEntityPose) -> anyhow::Resul
         // bail!("Player is moving too fast max speed: {MAX_SPEED}");
     }
 
+    query.motion.moved(d_pos.y, on_ground);
+
     // todo: analyze clustering
     full_entity_pose.move_to(position);
     full_entity_pose.yaw = yaw;
@@ -60,34 +64,41 @@ fn full(mut data: &[u8], full_entity_pose: &mut FullEntityPose) -> anyhow::Resul
     Ok(())
 }
 
-fn look_and_on_ground(
-    mut data: &[u8],
-    full_entity_pose: &mut FullEntityPose,
-) -> anyhow::Result<()> {
+fn look_and_on_ground(mut data: &[u8], query: &mut PacketSwitchQuery) -> anyhow::Result<()> {
     let pkt = play::LookAndOnGroundC2s::decode(&mut data)?;
 
     // debug!("look and on ground packet: {:?}", pkt);
 
-    let play::LookAndOnGroundC2s { yaw, pitch, .. } = pkt;
+    let play::LookAndOnGroundC2s {
+        yaw,
+        pitch,
+        on_ground,
+    } = pkt;
 
-    full_entity_pose.yaw = yaw;
-    full_entity_pose.pitch = pitch;
+    query.motion.moved(0.0, on_ground);
+    query.pose.yaw = yaw;
+    query.pose.pitch = pitch;
 
     Ok(())
 }
 
-fn position_and_on_ground(
-    mut data: &[u8],
-    full_entity_pose: &mut FullEntityPose,
-) -> anyhow::Result<()> {
+fn position_and_on_ground(mut data: &[u8], query: &mut PacketSwitchQuery) -> anyhow::Result<()> {
     let pkt = play::PositionAndOnGroundC2s::decode(&mut data)?;
 
     // debug!("position and on ground packet: {:?}", pkt);
 
-    let play::PositionAndOnGroundC2s { position, .. } = pkt;
+    let play::PositionAndOnGroundC2s {
+        position,
+        on_ground,
+    } = pkt;
+
+    let position = position.as_vec3();
+    query
+        .motion
+        .moved(position.y - query.pose.position.y, on_ground);
 
     // todo: handle like full
-    full_entity_pose.move_to(position.as_vec3());
+    query.pose.move_to(position);
 
     Ok(())
 }
@@ -151,7 +162,9 @@ fn player_interact_entity(
                 target,
                 from_pos,
                 from: query.id,
-                damage: 10.0,
+                // worked out from what the attacker holds by `system::melee_damage`
+                damage: WeaponStats::FIST.damage,
+                knockback: Vec2::ZERO,
                 source: AttackType::Melee,
             }
             .into(),
@@ -164,6 +177,7 @@ fn player_interact_entity(
 pub struct PacketSwitchQuery<'a> {
     pub id: EntityId,
     pub pose: &'a mut FullEntityPose,
+    pub motion: &'a mut PlayerMotion,
 }
 
 fn player_action(
@@ -208,17 +222,59 @@ fn player_action(
                 .into(),
             );
         }
+        PlayerAction::ReleaseUseItem => {
+            sender.push(event::ReleaseItem { target: id }.into());
+        }
         _ => {}
     }
 
     Ok(())
 }
 
-// for sneaking
-fn client_command(
+fn player_interact_item(
     mut data: &[u8],
     sender: &mut Vec<SendElem>,
     query: &PacketSwitchQuery,
+) -> anyhow::Result<()> {
+    let packet = play::PlayerInteractItemC2s::decode(&mut data)?;
+
+    sender.push(
+        event::UseItem {
+            target: query.id,
+            hand: packet.hand,
+        }
+        .into(),
+    );
+
+    Ok(())
+}
+
+fn client_settings(
+    mut data: &[u8],
+    sender: &mut Vec<SendElem>,
+    query: &PacketSwitchQuery,
+) -> anyhow::Result<()> {
+    let packet = play::ClientSettingsC2s::decode(&mut data)?;
+
+    sender.push(
+        event::ClientSettingsUpdate {
+            target: query.id,
+            view_distance: packet.view_distance,
+            locale: packet.locale.to_owned(),
+            main_arm: packet.main_arm,
+            displayed_skin_parts: packet.displayed_skin_parts,
+        }
+        .into(),
+    );
+
+    Ok(())
+}
+
+// for sneaking and sprinting
+fn client_command(
+    mut data: &[u8],
+    sender: &mut Vec<SendElem>,
+    query: &mut PacketSwitchQuery,
 ) -> anyhow::Result<()> {
     let packet = play::ClientCommandC2s::decode(&mut data)?;
 
@@ -243,6 +299,8 @@ fn client_command(
                 .into(),
             );
         }
+        ClientCommand::StartSprinting => query.motion.sprinting = true,
+        ClientCommand::StopSprinting => query.motion.sprinting = false,
         _ => {}
     }
 
@@ -264,12 +322,13 @@ pub fn switch(
         play::TeleportConfirmC2s::ID => confirm_teleport(data),
         // play::PlayerInteractBlockC2s::ID => player_interact_block(data)?,
         play::ClientCommandC2s::ID => client_command(data, sender, query)?,
-        // play::ClientSettingsC2s::ID => client_settings(data, player)?,
+        play::ClientSettingsC2s::ID => client_settings(data, sender, query)?,
         // play::CustomPayloadC2s::ID => custom_payload(data),
-        play::FullC2s::ID => full(data, query.pose)?,
+        play::FullC2s::ID => full(data, query)?,
         play::PlayerActionC2s::ID => player_action(data, sender, query)?,
-        play::PositionAndOnGroundC2s::ID => position_and_on_ground(data, query.pose)?,
-        play::LookAndOnGroundC2s::ID => look_and_on_ground(data, query.pose)?,
+        play::PlayerInteractItemC2s::ID => player_interact_item(data, sender, query)?,
+        play::PositionAndOnGroundC2s::ID => position_and_on_ground(data, query)?,
+        play::LookAndOnGroundC2s::ID => look_and_on_ground(data, query)?,
         // play::ClientCommandC2s::ID => player_command(data),
         // play::UpdatePlayerAbilitiesC2s::ID => update_player_abilities(data)?,
         // play::UpdateSelectedSlotC2s::ID => update_selected_slot(data)?,
@@ -2,7 +2,9 @@
 
 pub mod bounding_box;
 pub mod broadcast;
+pub mod default_instance;
 pub mod fd_lookup;
+pub mod npc_chunks;
 pub mod player_aabb_lookup;
 pub mod player_id_lookup;
 pub mod player_uuid_lookup;
@@ -1,5 +1,7 @@
 //! Defines a singleton that is used to query given bounding boxes.
 //! This uses a [`bvh_region::Bvh`] to accelerate collision detection and querying.
+use std::collections::HashMap;
+
 use bvh_region::{aabb::Aabb, HasAabb};
 use evenio::{component::Component, entity::EntityId};
 
@@ -18,16 +20,59 @@ impl HasAabb for Stored {
     }
 }
 
+/// How the bounding boxes of entities are stored. Systems only use
+/// [`bvh_region::BroadPhase`], so this can be any broad-phase, like a
+/// [`bvh_region::SpatialGrid`] for dense crowds of zombies.
+pub type EntityBroadPhase = bvh_region::Bvh<Stored>;
+
 /// See [`crate::singleton::bounding_box`].
 #[derive(Component, Default)]
 pub struct EntityBoundingBoxes {
-    /// The bounding boxes of all entities as stored in a BVH.
-    pub query: bvh_region::Bvh<Stored>,
+    /// The bounding boxes of the entities in each instance, so entities never collide with
+    /// entities in other instances.
+    pub instances: HashMap<EntityId, EntityBroadPhase>,
+}
+
+/// The bounding boxes of entities in one tick, indexed by the index of their [`EntityId`] so
+/// broad-phases can be updated without hashing every entity.
+#[derive(Default)]
+pub struct BoundingBoxTable {
+    slots: Vec<Option<(EntityId, EntityId, Aabb)>>,
+}
+
+impl BoundingBoxTable {
+    /// Sets the bounding box of `id`, which is in `instance`.
+    pub fn insert(&mut self, id: EntityId, instance: EntityId, aabb: Aabb) {
+        let idx = id.index().0 as usize;
+
+        if idx >= self.slots.len() {
+            self.slots.resize(idx + 1, None);
+        }
+
+        self.slots[idx] = Some((id, instance, aabb));
+    }
+
+    /// The bounding box of `id`, or `None` if it was not inserted for `instance`.
+    #[must_use]
+    pub fn get(&self, id: EntityId, instance: EntityId) -> Option<Aabb> {
+        match self.slots.get(id.index().0 as usize)? {
+            Some((stored, in_instance, aabb)) if *stored == id && *in_instance == instance => {
+                Some(*aabb)
+            }
+            _ => None,
+        }
+    }
 }
 
 impl EntityBoundingBoxes {
+    /// The bounding boxes of the entities in `instance`.
+    #[must_use]
+    pub fn get(&self, instance: EntityId) -> Option<&EntityBroadPhase> {
+        self.instances.get(&instance)
+    }
+
     /// Clears the bounding boxes.
     pub fn clear(&mut self) {
-        self.query.clear();
+        self.instances.clear();
     }
 }
@@ -0,0 +1,8 @@
+//! The instance players join.
+
+use derive_more::Deref;
+use evenio::{entity::EntityId, prelude::Component};
+
+/// The instance new players and NPCs are put in. See [`crate::components::instance`].
+#[derive(Component, Debug, Copy, Clone, Deref)]
+pub struct DefaultInstance(pub EntityId);
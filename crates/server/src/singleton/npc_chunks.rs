@@ -0,0 +1,107 @@
+//! Lookup NPCs by the chunk they are in
+
+use evenio::{entity::EntityId, prelude::Component};
+use fxhash::{FxHashMap, FxHashSet};
+use glam::I16Vec2;
+
+/// An instance and a chunk position in it.
+pub type Location = (EntityId, I16Vec2);
+
+/// The chunk every NPC was in when visibility was last updated, see
+/// [`crate::system::visibility`].
+#[derive(Component, Default, Debug)]
+pub struct NpcChunks {
+    located: FxHashMap<EntityId, Location>,
+    chunks: FxHashMap<Location, FxHashSet<EntityId>>,
+}
+
+impl NpcChunks {
+    /// Where the NPC was last put.
+    #[must_use]
+    pub fn location(&self, id: EntityId) -> Option<Location> {
+        self.located.get(&id).copied()
+    }
+
+    /// Moves the NPC to a new location, or removes it if `to` is `None`.
+    ///
+    /// Returns the location it was in before.
+    pub fn relocate(&mut self, id: EntityId, to: Option<Location>) -> Option<Location> {
+        let from = match to {
+            Some(to) => self.located.insert(id, to),
+            None => self.located.remove(&id),
+        };
+
+        if from == to {
+            return from;
+        }
+
+        if let Some(from) = from {
+            if let Some(ids) = self.chunks.get_mut(&from) {
+                ids.remove(&id);
+
+                if ids.is_empty() {
+                    self.chunks.remove(&from);
+                }
+            }
+        }
+
+        if let Some(to) = to {
+            self.chunks.entry(to).or_default().insert(id);
+        }
+
+        from
+    }
+
+    /// The NPCs in a chunk.
+    pub fn in_chunk(&self, location: Location) -> impl Iterator<Item = EntityId> + '_ {
+        self.chunks.get(&location).into_iter().flatten().copied()
+    }
+
+    /// The number of NPCs which have a location.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.located.len()
+    }
+
+    /// Whether no NPC has a location.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.located.is_empty()
+    }
+
+    /// All NPCs which have a location.
+    pub fn ids(&self) -> impl Iterator<Item = EntityId> + '_ {
+        self.located.keys().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use evenio::entity::EntityId;
+    use glam::I16Vec2;
+
+    use super::NpcChunks;
+
+    #[test]
+    fn relocating_moves_between_chunks() {
+        let instance = EntityId::NULL;
+        let npc = EntityId::NULL;
+
+        let a = (instance, I16Vec2::new(0, 0));
+        let b = (instance, I16Vec2::new(1, 0));
+
+        let mut chunks = NpcChunks::default();
+
+        assert_eq!(chunks.relocate(npc, Some(a)), None);
+        assert_eq!(chunks.in_chunk(a).collect::<Vec<_>>(), [npc]);
+
+        assert_eq!(chunks.relocate(npc, Some(a)), Some(a));
+        assert_eq!(chunks.relocate(npc, Some(b)), Some(a));
+        assert_eq!(chunks.in_chunk(a).count(), 0);
+        assert_eq!(chunks.in_chunk(b).collect::<Vec<_>>(), [npc]);
+
+        assert_eq!(chunks.relocate(npc, None), Some(b));
+        assert_eq!(chunks.in_chunk(b).count(), 0);
+        assert!(chunks.is_empty());
+    }
+}
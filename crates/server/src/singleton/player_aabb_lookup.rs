@@ -1,4 +1,6 @@
 //! A singleton designed for querying players based on their bounding boxes.
+use std::collections::HashMap;
+
 use bvh_region::{aabb::Aabb, HasAabb};
 use evenio::{entity::EntityId, prelude::Component};
 
@@ -17,18 +19,22 @@ impl HasAabb for LookupData {
     }
 }
 
+/// How the bounding boxes of players are stored. Systems only use [`bvh_region::BroadPhase`],
+/// so this can be any broad-phase.
+pub type PlayerBroadPhase = bvh_region::Bvh<LookupData>;
+
 /// See [`crate::singleton::player_aabb_lookup`].
 #[derive(Component, Debug, Default)]
 pub struct PlayerBoundingBoxes {
-    /// The bounding boxes of all players
-    pub query: bvh_region::Bvh<LookupData>,
+    /// The bounding boxes of the players in each instance
+    pub instances: HashMap<EntityId, PlayerBroadPhase>,
 }
 
 impl PlayerBoundingBoxes {
-    /// Get the closest player to the given position.
+    /// Get the closest player in `instance` to the given position.
     #[must_use]
-    pub fn closest_to(&self, point: glam::Vec3) -> Option<&LookupData> {
-        let (target, _) = self.query.get_closest(point)?;
+    pub fn closest_to(&self, instance: EntityId, point: glam::Vec3) -> Option<&LookupData> {
+        let (target, _) = self.instances.get(&instance)?.get_closest(point)?;
         Some(target)
     }
 }
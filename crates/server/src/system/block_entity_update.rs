@@ -0,0 +1,40 @@
+use std::borrow::Cow;
+
+use evenio::{event::Receiver, fetch::Single};
+use tracing::warn;
+use valence_protocol::packets::play;
+
+use crate::{
+    components::{
+        chunks::{Chunks, Tasks},
+        instance::InstanceBroadcast,
+    },
+    event,
+    net::Compose,
+};
+
+#[allow(
+    clippy::needless_pass_by_value,
+    reason = "this is used in the event loop"
+)]
+pub fn block_entity_update(
+    r: Receiver<event::UpdateBlockEntity, (&Chunks, &InstanceBroadcast)>,
+    tasks: Single<&Tasks>,
+    encode: Compose,
+) {
+    let event = r.event;
+    let (chunks, broadcast) = r.query;
+
+    let Some(kind) = chunks.set_block_entity(event.position, event.data.clone(), &tasks) else {
+        warn!("no block entity can be placed at {:?}", event.position);
+        return;
+    };
+
+    let pkt = play::BlockEntityUpdateS2c {
+        position: event.position,
+        kind,
+        data: Cow::Borrowed(&event.data),
+    };
+
+    broadcast.append(&pkt, &encode).unwrap();
+}
@@ -1,18 +1,24 @@
-use evenio::{event::Receiver, fetch::Single};
+use evenio::event::Receiver;
 use valence_protocol::{packets::play, VarInt};
 
-use crate::{event, net::Compose};
+use crate::{
+    components::{chunks::Chunks, instance::InstanceBroadcast},
+    event,
+    net::Compose,
+};
 
 #[allow(
     clippy::needless_pass_by_value,
     reason = "this is used in the event loop"
 )]
 pub fn block_update(
-    r: Receiver<event::UpdateBlock>,
-    broadcast: Single<&crate::net::Broadcast>,
+    r: Receiver<event::UpdateBlock, (&Chunks, &InstanceBroadcast)>,
     encode: Compose,
 ) {
     let event = r.event;
+    let (chunks, broadcast) = r.query;
+
+    chunks.set_block(event.position, event.id);
 
     let pkt = play::BlockUpdateS2c {
         position: event.position,
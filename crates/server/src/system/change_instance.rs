@@ -0,0 +1,199 @@
+use std::borrow::Cow;
+
+use evenio::prelude::*;
+use tracing::{instrument, warn};
+use valence_protocol::{
+    game_mode::OptGameMode, packets::play, ByteAngle, ChunkPos, GameMode, VarInt,
+};
+use valence_server::entity::EntityKind;
+
+use crate::{
+    components::{
+        instance::{InInstance, Instance, InstanceBroadcast},
+        metadata::Metadata,
+        world_border::WorldBorder,
+        ChunkLocation, Display, FullEntityPose, Npc, Player, Uuid,
+    },
+    event,
+    net::{Compose, Packets},
+    system::{chunks::ChunkChanges, init_entity::spawn_entity_packet, visibility::VisibleEntities},
+};
+
+#[derive(Query)]
+pub(crate) struct ChangeInstanceQuery<'a> {
+    id: EntityId,
+    uuid: &'a Uuid,
+    pose: &'a FullEntityPose,
+    instance: &'a InInstance,
+    chunk_changes: &'a ChunkChanges,
+    packets: &'a mut Packets,
+    metadata: &'a Metadata,
+    _player: With<&'static Player>,
+}
+
+#[derive(Query)]
+pub(crate) struct EntityQuery<'a> {
+    id: EntityId,
+    uuid: &'a Uuid,
+    pose: &'a FullEntityPose,
+    instance: &'a InInstance,
+    display: Option<&'a Display>,
+    metadata: &'a Metadata,
+    npc: Option<&'a Npc>,
+}
+
+fn entity_id(id: EntityId) -> VarInt {
+    VarInt(id.index().0 as i32)
+}
+
+#[instrument(skip_all)]
+pub fn change_instance(
+    r: Receiver<event::ChangeInstance, ChangeInstanceQuery>,
+    instances: Fetcher<(&Instance, &WorldBorder, &InstanceBroadcast)>,
+    entities: Fetcher<EntityQuery>,
+    compose: Compose,
+    mut s: Sender<(
+        Insert<InInstance>,
+        Insert<ChunkChanges>,
+        Insert<VisibleEntities>,
+        Insert<ChunkLocation>,
+        event::Teleport,
+    )>,
+) {
+    let event = r.event;
+    let query = r.query;
+
+    let old = **query.instance;
+    let new = event.instance;
+
+    if old == new {
+        s.send(event::Teleport {
+            target: query.id,
+            position: event.position,
+        });
+        return;
+    }
+
+    let Ok((instance, border, new_broadcast)) = instances.get(new) else {
+        warn!("tried to move a player to {new:?} which is not an instance");
+        return;
+    };
+
+    let packets = query.packets;
+
+    let old_instance = instances.get(old).ok();
+
+    // the client only drops its chunks and entities on its own if the world name changes
+    if old_instance.is_some_and(|(old_instance, ..)| old_instance.name == instance.name) {
+        for pos in query.chunk_changes.loaded() {
+            let pkt = play::UnloadChunkS2c {
+                pos: ChunkPos::new(i32::from(pos.x), i32::from(pos.y)),
+            };
+            packets.append(&pkt, &compose).unwrap();
+        }
+    }
+
+    let old_entities = entities
+        .iter()
+        .filter(|entity| **entity.instance == old && entity.id != query.id)
+        .map(|entity| entity_id(entity.id))
+        .collect::<Vec<_>>();
+
+    packets
+        .append(
+            &play::EntitiesDestroyS2c {
+                entity_ids: Cow::Owned(old_entities),
+            },
+            &compose,
+        )
+        .unwrap();
+
+    let respawn = play::PlayerRespawnS2c {
+        dimension_type_name: instance.dimension_type.as_str_ident().into(),
+        dimension_name: instance.name.as_str_ident().into(),
+        hashed_seed: 0,
+        game_mode: GameMode::Adventure,
+        previous_game_mode: OptGameMode(Some(GameMode::Adventure)),
+        is_debug: false,
+        is_flat: false,
+        copy_metadata: true,
+        last_death_location: None,
+        portal_cooldown: 0.into(),
+    };
+
+    packets.append(&respawn, &compose).unwrap();
+
+    let pkt = border.initialize_packet(compose.global.tick);
+    packets.append(&pkt, &compose).unwrap();
+
+    // NPCs are spawned once the chunks they are in have been sent
+    for entity in entities
+        .iter()
+        .filter(|entity| **entity.instance == new && entity.id != query.id && entity.npc.is_none())
+    {
+        if let Some(display) = entity.display {
+            let pkt = spawn_entity_packet(entity.id, display.0, *entity.uuid, entity.pose);
+            packets.append(&pkt, &compose).unwrap();
+        } else {
+            let pkt = play::PlayerSpawnS2c {
+                entity_id: entity_id(entity.id),
+                player_uuid: entity.uuid.0,
+                position: entity.pose.position.as_dvec3(),
+                yaw: ByteAngle::from_degrees(entity.pose.yaw),
+                pitch: ByteAngle::from_degrees(entity.pose.pitch),
+            };
+            packets.append(&pkt, &compose).unwrap();
+        }
+
+        let kind = entity
+            .display
+            .map_or(EntityKind::PLAYER, |display| display.0);
+        let mut bytes = Vec::new();
+
+        if let Some(pkt) = entity
+            .metadata
+            .initial_packet(entity_id(entity.id), kind, &mut bytes)
+        {
+            packets.append(&pkt, &compose).unwrap();
+        }
+    }
+
+    // other players only see the player in the instance they are in
+    if let Some((.., old_broadcast)) = old_instance {
+        let pkt = play::EntitiesDestroyS2c {
+            entity_ids: Cow::Borrowed(&[entity_id(query.id)]),
+        };
+        old_broadcast.append(&pkt, &compose).unwrap();
+    }
+
+    let pkt = play::PlayerSpawnS2c {
+        entity_id: entity_id(query.id),
+        player_uuid: query.uuid.0,
+        position: event.position.as_dvec3(),
+        yaw: ByteAngle::from_degrees(query.pose.yaw),
+        pitch: ByteAngle::from_degrees(query.pose.pitch),
+    };
+    new_broadcast.append(&pkt, &compose).unwrap();
+
+    let mut bytes = Vec::new();
+
+    if let Some(pkt) =
+        query
+            .metadata
+            .initial_packet(entity_id(query.id), EntityKind::PLAYER, &mut bytes)
+    {
+        new_broadcast.append(&pkt, &compose).unwrap();
+    }
+
+    s.insert(query.id, InInstance(new));
+    s.insert(query.id, ChunkChanges::default());
+    // the old entities were destroyed above
+    s.insert(query.id, VisibleEntities::default());
+    // so chunks around the new position are always sent
+    s.insert(query.id, ChunkLocation::NULL);
+
+    s.send(event::Teleport {
+        target: query.id,
+        position: event.position,
+    });
+}
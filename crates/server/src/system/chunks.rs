@@ -1,27 +1,110 @@
-use std::collections::HashSet;
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
-use derive_more::{Deref, DerefMut};
 use evenio::prelude::*;
+use fxhash::FxHashSet;
 use glam::I16Vec2;
+use itertools::Itertools;
 use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
 use tracing::{debug, error, instrument};
-use valence_protocol::packets::play;
+use valence_protocol::{packets::play, ChunkPos};
 
 use crate::{
     components::{
         chunks::{ChunkData, Chunks, Tasks},
-        ChunkLocation, FullEntityPose,
+        instance::{InInstance, InstanceBroadcast},
+        ChunkLocation, FullEntityPose, ViewDistance,
     },
     config::CONFIG,
     event::Gametick,
-    net::{Compose, Packets},
+    net::{buffers::BUFFER_SIZE, Compose, Packets},
 };
 
-#[derive(Component, Deref, DerefMut, Default)]
+/// The chunks a player has in view.
+#[derive(Component, Default, Debug)]
 pub struct ChunkChanges {
-    changes: HashSet<I16Vec2>,
+    /// Chunks which have been sent to the client.
+    loaded: FxHashSet<I16Vec2>,
+    /// Chunks which are in view but have not been sent yet, from farthest to nearest so the
+    /// nearest chunk can be popped off the end.
+    pending: Vec<I16Vec2>,
+    /// Bumped whenever `loaded` changes.
+    revision: u64,
+}
+
+impl ChunkChanges {
+    /// Chunks which have been sent to the client.
+    pub fn loaded(&self) -> impl ExactSizeIterator<Item = I16Vec2> + '_ {
+        self.loaded.iter().copied()
+    }
+
+    /// Whether the chunk at `pos` has been sent to the client.
+    #[must_use]
+    pub fn is_loaded(&self, pos: I16Vec2) -> bool {
+        self.loaded.contains(&pos)
+    }
+
+    /// Changes whenever a chunk is loaded or unloaded, so the loaded chunks only need to be
+    /// looked at again if this differs from the last time.
+    #[must_use]
+    pub const fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Updates the chunks in view to the square of the given radius around `center`.
+    ///
+    /// Returns the chunks which were loaded by the client and are now out of view. These are
+    /// no longer tracked and should be unloaded.
+    fn update_view(&mut self, center: I16Vec2, radius: i16) -> Vec<I16Vec2> {
+        let in_view = |pos: &I16Vec2| {
+            (pos.x - center.x).abs() <= radius && (pos.y - center.y).abs() <= radius
+        };
+
+        let removed = self
+            .loaded
+            .iter()
+            .copied()
+            .filter(|pos| !in_view(pos))
+            .collect_vec();
+
+        for pos in &removed {
+            self.loaded.remove(pos);
+        }
+
+        if !removed.is_empty() {
+            self.revision += 1;
+        }
+
+        self.pending.clear();
+
+        for x in center.x - radius..=center.x + radius {
+            for z in center.y - radius..=center.y + radius {
+                let pos = I16Vec2::new(x, z);
+
+                if !self.loaded.contains(&pos) {
+                    self.pending.push(pos);
+                }
+            }
+        }
+
+        let distance = |pos: &I16Vec2| {
+            let offset = (*pos - center).as_ivec2();
+            offset.length_squared()
+        };
+
+        self.pending
+            .sort_unstable_by_key(|pos| std::cmp::Reverse(distance(pos)));
+
+        removed
+    }
 }
 
+/// Chunks which are still loading are only waited on if they are among this many of the nearest
+/// pending chunks. This stops a join from spawning a load task for every chunk in view at once.
+const LOADS_PER_PLAYER_TICK: usize = 64;
+
 #[instrument(skip_all, level = "trace")]
 pub fn generate_chunk_changes(
     _: Receiver<Gametick>,
@@ -30,14 +113,13 @@ pub fn generate_chunk_changes(
         &mut FullEntityPose,
         &mut Packets,
         &mut ChunkChanges,
+        &ViewDistance,
     )>,
     compose: Compose,
 ) {
-    let radius = CONFIG.view_distance as i16;
-
     fetcher
         .par_iter_mut()
-        .for_each(|(last_sent, pose, packets, chunk_changes)| {
+        .for_each(|(last_sent, pose, packets, chunk_changes, view_distance)| {
             let last_sent_chunk = last_sent.0;
 
             let current_chunk = pose.chunk_pos();
@@ -58,51 +140,318 @@ pub fn generate_chunk_changes(
 
             last_sent.0 = current_chunk;
 
-            let last_sent_x_range = last_sent_chunk.x - radius..last_sent_chunk.x + radius;
-            let last_sent_z_range = last_sent_chunk.y - radius..last_sent_chunk.y + radius;
-
-            let current_x_range = current_chunk.x - radius..current_chunk.x + radius;
-            let current_z_range = current_chunk.y - radius..current_chunk.y + radius;
-
-            let added_chunks = current_x_range
-                .flat_map(move |x| current_z_range.clone().map(move |z| I16Vec2::new(x, z)))
-                .filter(|pos| {
-                    !last_sent_x_range.contains(&pos.x) || !last_sent_z_range.contains(&pos.y)
-                });
+            // this is a diff against the chunks the client actually has, so it also works for
+            // teleports which jump further than the view distance
+            for removed in chunk_changes.update_view(current_chunk, view_distance.0) {
+                let pkt = play::UnloadChunkS2c {
+                    pos: ChunkPos::new(i32::from(removed.x), i32::from(removed.y)),
+                };
 
-            for chunk in added_chunks {
-                chunk_changes.insert(chunk);
+                packets.append(&pkt, &compose).unwrap();
             }
         });
 }
 
+/// Sends pending chunks, nearest first.
+///
+/// Every player gets a budget of bytes and chunks per tick, which shrinks as their send buffer
+/// fills up. All players also share one byte budget per tick, so a flood of joins is spread over
+/// several ticks instead of starving the rest of egress.
 #[instrument(skip_all, level = "trace")]
 pub fn send_updates(
     _: Receiver<Gametick>,
-    mut fetcher: Fetcher<(&mut Packets, &mut ChunkChanges)>,
-    chunks: Single<&Chunks>,
+    mut fetcher: Fetcher<(&mut Packets, &mut ChunkChanges, &InInstance)>,
+    instances: Fetcher<(EntityId, &Chunks)>,
     tasks: Single<&Tasks>,
 ) {
-    fetcher.par_iter_mut().for_each(|(packets, chunk_changes)| {
-        let mut left_over = Vec::new();
-
-        for &elem in &chunk_changes.changes {
-            match chunks.get_cached_or_load(elem, &tasks) {
-                Ok(Some(ChunkData::Cached(chunk))) => {
-                    packets.append_raw(&chunk);
-                    continue;
-                }
-                Ok(Some(ChunkData::Task(..)) | None) => {
-                    left_over.push(elem);
-                    continue;
-                }
-                Err(err) => {
-                    error!("failed to get chunk {elem:?}: {err}");
-                    continue;
+    let instances: HashMap<_, _> = instances
+        .iter()
+        .map(|(id, chunks)| (id, chunks.clone()))
+        .collect();
+
+    let global_budget = AtomicUsize::new(CONFIG.chunk_bytes_per_tick);
+
+    fetcher
+        .par_iter_mut()
+        .for_each(|(packets, chunk_changes, instance)| {
+            let ChunkChanges {
+                loaded,
+                pending,
+                revision,
+            } = chunk_changes;
+
+            let Some(chunks) = instances.get(&instance.0) else {
+                return;
+            };
+
+            let mut budget = ChunkBudget::new(
+                packets.queued_bytes(),
+                CONFIG.chunk_bytes_per_player_tick,
+                CONFIG.chunks_per_player_tick,
+                &global_budget,
+            );
+
+            let mut loading = 0;
+
+            let mut idx = pending.len();
+
+            while idx > 0 && !budget.is_exhausted() && loading < LOADS_PER_PLAYER_TICK {
+                idx -= 1;
+                let elem = pending[idx];
+
+                match chunks.get_cached_or_load(elem, &tasks) {
+                    Ok(Some(ChunkData::Cached(chunk))) => {
+                        // the order is kept strict; a big chunk is not skipped for smaller ones
+                        // farther away.
+                        if !budget.try_send(chunk.len()) {
+                            break;
+                        }
+
+                        packets.append_raw(&chunk);
+                        loaded.insert(elem);
+                        pending.remove(idx);
+                        *revision += 1;
+                    }
+                    Ok(Some(ChunkData::Task(..)) | None) => {
+                        loading += 1;
+                    }
+                    Err(err) => {
+                        error!("failed to get chunk {elem:?}: {err}");
+                        pending.remove(idx);
+                    }
                 }
             }
+        });
+}
+
+/// The bytes and chunks a single player may still be sent in one tick.
+struct ChunkBudget<'a> {
+    /// The free half of the send buffer of the player.
+    room: usize,
+    /// The bytes left of the per player budget.
+    bytes: usize,
+    /// The chunks left of the per player budget.
+    chunks: usize,
+    /// How many chunks have been sent this tick.
+    sent: usize,
+    /// The bytes left of the budget all players share.
+    global: &'a AtomicUsize,
+}
+
+impl<'a> ChunkBudget<'a> {
+    /// A budget for a player who has `queued` bytes which have not been sent yet.
+    fn new(queued: usize, bytes: usize, chunks: usize, global: &'a AtomicUsize) -> Self {
+        // never fill more than half of the ring buffer with chunks so other packets always fit
+        let room = (BUFFER_SIZE / 2).saturating_sub(queued);
+
+        Self {
+            room,
+            bytes: room.min(bytes),
+            chunks,
+            sent: 0,
+            global,
+        }
+    }
+
+    /// Whether no more chunks may be sent this tick.
+    const fn is_exhausted(&self) -> bool {
+        self.sent >= self.chunks
+    }
+
+    /// Takes a chunk of `len` bytes out of the budgets. Returns whether it may be sent.
+    fn try_send(&mut self, len: usize) -> bool {
+        // the first chunk may exceed the per player budget so huge chunks are not stuck forever
+        let fits = len <= self.bytes || (self.sent == 0 && len <= self.room);
+
+        if self.is_exhausted() || !fits || !self.reserve(len) {
+            return false;
+        }
+
+        self.bytes = self.bytes.saturating_sub(len);
+        self.room -= len;
+        self.sent += 1;
+
+        true
+    }
+
+    /// Takes `len` bytes out of the global budget if there are enough left.
+    fn reserve(&self, len: usize) -> bool {
+        self.global
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| {
+                left.checked_sub(len)
+            })
+            .is_ok()
+    }
+}
+
+/// Starts relighting the columns whose blocks changed, and broadcasts the light of columns which
+/// have been relit since the last tick to the players in the same instance.
+#[instrument(skip_all, level = "trace")]
+pub fn send_light_updates(
+    _: Receiver<Gametick>,
+    instances: Fetcher<(&Chunks, &InstanceBroadcast)>,
+    tasks: Single<&Tasks>,
+) {
+    for (chunks, broadcast) in instances {
+        chunks.relight(&tasks);
+
+        for update in chunks.drain_light_updates() {
+            broadcast.append_raw(&update);
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Marks every pending chunk as sent.
+    fn send_all(changes: &mut ChunkChanges) {
+        changes.loaded.extend(changes.pending.drain(..));
+    }
+
+    fn sorted(chunks: impl IntoIterator<Item = I16Vec2>) -> Vec<I16Vec2> {
+        chunks
+            .into_iter()
+            .sorted_by_key(|pos| (pos.x, pos.y))
+            .collect()
+    }
+
+    fn column(x: i16, z: impl IntoIterator<Item = i16>) -> Vec<I16Vec2> {
+        z.into_iter().map(|z| I16Vec2::new(x, z)).collect()
+    }
+
+    #[test]
+    fn crossing_a_border_loads_and_unloads_one_row() {
+        let mut changes = ChunkChanges::default();
+
+        assert!(changes.update_view(I16Vec2::ZERO, 2).is_empty());
+        assert_eq!(changes.pending.len(), 25);
+        send_all(&mut changes);
+
+        let removed = changes.update_view(I16Vec2::new(1, 0), 2);
+
+        assert_eq!(sorted(removed), column(-2, -2..=2));
+        assert_eq!(sorted(changes.pending.clone()), column(3, -2..=2));
+        assert_eq!(changes.loaded.len(), 20);
+    }
+
+    #[test]
+    fn chunks_are_unloaded_when_the_view_distance_shrinks() {
+        let mut changes = ChunkChanges::default();
+
+        changes.update_view(I16Vec2::ZERO, 3);
+        send_all(&mut changes);
+
+        let removed = changes.update_view(I16Vec2::ZERO, 1);
+
+        assert_eq!(removed.len(), 7 * 7 - 3 * 3);
+        assert!(removed.iter().all(|pos| pos.x.abs() > 1 || pos.y.abs() > 1));
+        assert!(changes.pending.is_empty());
+        assert_eq!(changes.loaded.len(), 9);
+    }
+
+    #[test]
+    fn teleporting_far_away_replaces_every_chunk() {
+        let mut changes = ChunkChanges::default();
+
+        changes.update_view(I16Vec2::ZERO, 2);
+        send_all(&mut changes);
+
+        let removed = changes.update_view(I16Vec2::new(100, -100), 2);
+
+        assert_eq!(removed.len(), 25);
+        assert!(changes.loaded.is_empty());
+        assert_eq!(changes.pending.len(), 25);
+        assert!(changes
+            .pending
+            .iter()
+            .all(|pos| (pos.x - 100).abs() <= 2 && (pos.y + 100).abs() <= 2));
+    }
+
+    #[test]
+    fn unloading_chunks_changes_the_revision() {
+        let mut changes = ChunkChanges::default();
+
+        changes.update_view(I16Vec2::ZERO, 2);
+        send_all(&mut changes);
+
+        let revision = changes.revision();
+
+        changes.update_view(I16Vec2::ZERO, 2);
+        assert_eq!(changes.revision(), revision);
+
+        changes.update_view(I16Vec2::new(1, 0), 2);
+        assert_ne!(changes.revision(), revision);
+    }
+
+    #[test]
+    fn pending_chunks_are_sent_nearest_first() {
+        let mut changes = ChunkChanges::default();
+        let center = I16Vec2::new(5, -7);
+
+        changes.update_view(center, 4);
+
+        let distances = changes
+            .pending
+            .iter()
+            .rev()
+            .map(|pos| (*pos - center).as_ivec2().length_squared())
+            .collect_vec();
+
+        assert_eq!(changes.pending.last(), Some(&center));
+        assert!(distances.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        // chunks which were already sent are not pending again
+        send_all(&mut changes);
+        changes.update_view(center, 5);
+        assert_eq!(changes.pending.len(), 11 * 11 - 9 * 9);
+    }
+
+    #[test]
+    fn players_are_sent_up_to_their_budget() {
+        let global = AtomicUsize::new(usize::MAX);
+        let mut budget = ChunkBudget::new(0, 1000, 3, &global);
+
+        assert!(budget.try_send(400));
+        assert!(budget.try_send(400));
+        // only 200 bytes are left
+        assert!(!budget.try_send(400));
+        assert!(budget.try_send(200));
+        assert!(budget.is_exhausted());
+        assert!(!budget.try_send(1));
+
+        // a huge first chunk is sent anyway if the buffer has room
+        let mut budget = ChunkBudget::new(0, 1000, 3, &global);
+        assert!(budget.try_send(5000));
+        assert!(!budget.try_send(1));
+    }
+
+    #[test]
+    fn queued_bytes_shrink_the_budget() {
+        let global = AtomicUsize::new(usize::MAX);
+
+        let mut budget = ChunkBudget::new(BUFFER_SIZE / 2 - 100, 1000, 3, &global);
+        assert!(!budget.try_send(200));
+        assert!(budget.try_send(100));
+        assert!(!budget.try_send(1));
+
+        // a full buffer lets not even a first chunk through
+        let mut budget = ChunkBudget::new(BUFFER_SIZE, 1000, 3, &global);
+        assert!(!budget.try_send(1));
+    }
+
+    #[test]
+    fn players_share_the_global_budget() {
+        let global = AtomicUsize::new(1000);
+
+        let mut first = ChunkBudget::new(0, 800, 10, &global);
+        assert!(first.try_send(800));
+
+        let mut second = ChunkBudget::new(0, 800, 10, &global);
+        assert!(!second.try_send(300));
+        assert!(second.try_send(200));
 
-        chunk_changes.changes = left_over.into_iter().collect();
-    });
+        assert_eq!(global.load(Ordering::Relaxed), 0);
+    }
 }
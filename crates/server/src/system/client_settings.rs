@@ -0,0 +1,39 @@
+use evenio::prelude::*;
+use tracing::instrument;
+
+use crate::{
+    components::{metadata::Metadata, ChunkLocation, ClientSettings, ViewDistance},
+    event,
+};
+
+#[derive(Query)]
+pub(crate) struct ClientSettingsQuery<'a> {
+    view_distance: &'a mut ViewDistance,
+    settings: &'a mut ClientSettings,
+    chunk_location: &'a mut ChunkLocation,
+    metadata: &'a mut Metadata,
+}
+
+#[instrument(skip_all)]
+pub fn client_settings(r: ReceiverMut<event::ClientSettingsUpdate, ClientSettingsQuery>) {
+    let event = EventMut::take(r.event);
+    let query = r.query;
+
+    let view_distance = ViewDistance::new(event.view_distance);
+
+    if *query.view_distance != view_distance {
+        *query.view_distance = view_distance;
+
+        // the chunks in view are only recomputed when the player changes chunk
+        *query.chunk_location = ChunkLocation::NULL;
+    }
+
+    // other players see the change once the metadata is synced
+    query.metadata.skin_parts = u8::from(event.displayed_skin_parts);
+
+    *query.settings = ClientSettings {
+        locale: event.locale,
+        main_arm: event.main_arm,
+        displayed_skin_parts: event.displayed_skin_parts,
+    };
+}
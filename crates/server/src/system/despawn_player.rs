@@ -5,19 +5,23 @@ use tracing::{info, instrument};
 use valence_protocol::{packets::play, VarInt};
 
 use crate::{
-    components::{InGameName, Uuid},
+    components::{
+        instance::{InInstance, InstanceBroadcast},
+        InGameName, Uuid,
+    },
     global::Global,
     net::{Broadcast, Compose},
 };
 
 #[instrument(skip_all, level = "trace")]
 pub fn despawn_player(
-    r: Receiver<Despawn, (&Uuid, &InGameName, EntityId)>,
+    r: Receiver<Despawn, (&Uuid, &InGameName, EntityId, &InInstance)>,
     broadcast: Single<&Broadcast>,
+    instances: Fetcher<&InstanceBroadcast>,
     compose: Compose,
     global: Single<&Global>,
 ) {
-    let (uuid, name, id) = r.query;
+    let (uuid, name, id, instance) = r.query;
 
     let uuid = uuid.0;
     let uuids = &[uuid];
@@ -30,8 +34,11 @@ pub fn despawn_player(
         entity_ids: Cow::Borrowed(entity_ids),
     };
 
-    broadcast.append(&pkt, &compose).unwrap();
+    if let Ok(instance_broadcast) = instances.get(**instance) {
+        instance_broadcast.append(&pkt, &compose).unwrap();
+    }
 
+    // the player list is shared by all instances
     let pkt = play::PlayerRemoveS2c {
         uuids: uuids.into(),
     };
@@ -7,10 +7,10 @@ use evenio::{
     query::Query,
 };
 use tracing::instrument;
-use valence_protocol::packets::play;
+use valence_protocol::{packets::play, VarInt};
 
 use crate::{
-    components::{Display, FullEntityPose, Uuid},
+    components::{metadata::Metadata, Display, FullEntityPose, Uuid},
     event,
     net::{Compose, Packets},
     system::init_entity::spawn_entity_packet,
@@ -21,6 +21,7 @@ pub struct DisguisePlayerQuery<'a> {
     id: EntityId,
     uuid: &'a Uuid,
     pose: &'a FullEntityPose,
+    metadata: &'a Metadata,
 }
 
 #[instrument(skip_all, level = "trace")]
@@ -61,6 +62,25 @@ pub fn disguise_player(
         )
         .unwrap();
 
+    // the metadata fields a mob has differ from those of a player
+    let mut metadata = Vec::new();
+    let entity_id = VarInt(query.id.index().0 as i32);
+
+    if let Some(pkt) = query
+        .metadata
+        .initial_packet(entity_id, event.mob, &mut metadata)
+    {
+        compose
+            .encoder()
+            .append_packet(
+                &pkt,
+                &mut bytes,
+                &mut *compose.scratch().borrow_mut(),
+                &mut compose.compressor().borrow_mut(),
+            )
+            .unwrap();
+    }
+
     // todo: add broadcast with mask
     for (packets, id) in all_packets {
         if id == query.id {
@@ -5,7 +5,10 @@ use evenio::{
 use tracing::{instrument, log::warn};
 
 use crate::{
-    components::LoginState,
+    components::{
+        instance::{InInstance, InstanceBroadcast},
+        LoginState,
+    },
     event::Egress,
     net::{encoder::DataWriteInfo, Broadcast, Fd, Packets, ServerDef, WriteItem},
 };
@@ -13,38 +16,24 @@ use crate::{
 #[instrument(skip_all, level = "trace")]
 pub fn egress(
     r: ReceiverMut<Egress>,
-    mut players: Fetcher<(&mut Packets, &Fd, &LoginState)>,
+    mut players: Fetcher<(&mut Packets, &Fd, &LoginState, Option<&InInstance>)>,
+    mut instances: Fetcher<&mut InstanceBroadcast>,
     broadcast: Single<&mut Broadcast>,
 ) {
     let broadcast = broadcast.0;
 
-    let combined = tracing::span!(tracing::Level::TRACE, "broadcast-combine").in_scope(|| {
-        let total_len: usize = broadcast.packets().iter().map(|x| x.data.len()).sum();
-
-        let mut combined = Vec::with_capacity(total_len);
-
-        for data in broadcast.packets_mut().iter_mut().map(|x| x.data.as_mut()) {
-            combined.append(data);
-        }
-
-        combined
-    });
-
-    let broadcast_len = combined.len() as u32;
-
-    let ptr = broadcast.buffer.append(combined.as_slice());
-    broadcast.local_to_write = DataWriteInfo {
-        start_ptr: ptr,
-        len: broadcast_len,
-    };
-
+    let broadcast_len = combine(broadcast);
     let broadcast_index = broadcast.buffer.index();
 
+    for instance in &mut instances {
+        combine(instance);
+    }
+
     let mut event = r.event;
     let server = &mut *event.server;
 
     tracing::span!(tracing::Level::TRACE, "send",).in_scope(|| {
-        for (pkts, fd, login) in &mut players {
+        for (pkts, fd, login, in_instance) in &mut players {
             if !pkts.can_send() {
                 continue;
             }
@@ -63,6 +52,7 @@ pub fn egress(
                 };
 
                 pkts.number_sending += 1;
+                pkts.sending_bytes += elem.len as usize;
 
                 server.write(write_item);
             }
@@ -77,12 +67,31 @@ pub fn egress(
             // todo: append broadcast even if cannot send and have packet prios and stuff
             if broadcast_len != 0 {
                 pkts.number_sending += 1;
+                pkts.sending_bytes += broadcast_len as usize;
                 server.write(WriteItem {
                     info: &broadcast.local_to_write,
                     buffer_idx: broadcast_index,
                     fd: *fd,
                 });
             }
+
+            let Some(in_instance) = in_instance else {
+                continue;
+            };
+
+            let Ok(instance) = instances.get_mut(**in_instance) else {
+                continue;
+            };
+
+            if instance.local_to_write.len != 0 {
+                pkts.number_sending += 1;
+                pkts.sending_bytes += instance.local_to_write.len as usize;
+                server.write(WriteItem {
+                    info: &instance.local_to_write,
+                    buffer_idx: instance.buffer.index(),
+                    fd: *fd,
+                });
+            }
         }
     });
 
@@ -90,3 +99,29 @@ pub fn egress(
         server.submit_events();
     });
 }
+
+/// Moves the packets appended from every thread into the buffer of the broadcast and returns
+/// how many bytes are to be written.
+fn combine(broadcast: &mut Broadcast) -> u32 {
+    let combined = tracing::span!(tracing::Level::TRACE, "broadcast-combine").in_scope(|| {
+        let total_len: usize = broadcast.packets().iter().map(|x| x.data.len()).sum();
+
+        let mut combined = Vec::with_capacity(total_len);
+
+        for data in broadcast.packets_mut().iter_mut().map(|x| x.data.as_mut()) {
+            combined.append(data);
+        }
+
+        combined
+    });
+
+    let broadcast_len = combined.len() as u32;
+
+    let ptr = broadcast.buffer.append(combined.as_slice());
+    broadcast.local_to_write = DataWriteInfo {
+        start_ptr: ptr,
+        len: broadcast_len,
+    };
+
+    broadcast_len
+}
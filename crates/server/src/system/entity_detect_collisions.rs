@@ -1,14 +1,17 @@
+use std::collections::HashMap;
+
+use bvh_region::BroadPhase;
 use evenio::{
     entity::EntityId,
     event::Receiver,
     fetch::{Fetcher, Single},
     query::With,
 };
-use rayon::prelude::*;
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
 use tracing::instrument;
 
 use crate::{
-    components::{EntityReaction, FullEntityPose, Npc},
+    components::{instance::InInstance, EntityReaction, FullEntityPose, Npc},
     event::Gametick,
     singleton::bounding_box::EntityBoundingBoxes,
 };
@@ -17,33 +20,56 @@ use crate::{
 pub fn entity_detect_collisions(
     _: Receiver<Gametick>,
     entity_bounding_boxes: Single<&EntityBoundingBoxes>,
-    mut poses_fetcher: Fetcher<(EntityId, &FullEntityPose, &mut EntityReaction, With<&Npc>)>,
+    mut poses_fetcher: Fetcher<(
+        EntityId,
+        &FullEntityPose,
+        &mut EntityReaction,
+        &InInstance,
+        With<&Npc>,
+    )>,
 ) {
     const MAX_COLLISIONS: usize = 4;
 
-    poses_fetcher
-        .par_iter_mut()
-        .for_each(|(id, pose, reaction, _)| {
-            let mut collisions = 0;
-            entity_bounding_boxes
-                .query
-                .get_collisions(pose.bounding, |collision| {
-                    // do not include self
-                    if collision.id == id {
-                        return true;
-                    }
-
-                    collisions += 1;
-
-                    // short circuit if we have too many collisions
-                    if collisions >= MAX_COLLISIONS {
-                        return false;
-                    }
-
-                    println!("colliding with {id:?}");
-                    pose.apply_entity_collision(&collision.aabb, reaction);
+    let mut by_instance: HashMap<_, Vec<_>> = HashMap::new();
+
+    for (id, pose, reaction, instance, _) in &mut poses_fetcher {
+        by_instance
+            .entry(instance.0)
+            .or_default()
+            .push((id, pose, reaction));
+    }
+
+    for (instance, mut npcs) in by_instance {
+        let Some(query) = entity_bounding_boxes.get(instance) else {
+            continue;
+        };
+
+        let queries: Vec<_> = npcs.iter().map(|(_, pose, _)| pose.bounding).collect();
 
-                    true
-                });
-        });
+        // one more than needed, as every npc also collides with itself
+        let collisions = query.par_collide_all(&queries, MAX_COLLISIONS + 1);
+
+        // each query is looked up by only one thread, so all collisions of an npc are next to
+        // each other in one buffer
+        let mut groups = vec![&[][..]; npcs.len()];
+
+        for collisions in &collisions {
+            for group in collisions.chunk_by(|a, b| a.0 == b.0) {
+                groups[group[0].0] = group;
+            }
+        }
+
+        npcs.par_iter_mut()
+            .zip(groups)
+            .for_each(|((id, pose, reaction), group)| {
+                let others = group
+                    .iter()
+                    .filter(|(_, collision)| collision.id != *id)
+                    .take(MAX_COLLISIONS);
+
+                for (_, collision) in others {
+                    pose.apply_entity_collision(&collision.aabb, reaction);
+                }
+            });
+    }
 }
@@ -1,6 +1,9 @@
+use std::collections::HashMap;
+
 use evenio::{
+    entity::EntityId,
     event::Receiver,
-    fetch::{Fetcher, Single},
+    fetch::Fetcher,
     query::{Query, With},
 };
 use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
@@ -8,9 +11,14 @@ use tracing::instrument;
 use valence_protocol::math::{Vec2, Vec3};
 
 use crate::{
-    components::{EntityReaction, FullEntityPose, Npc, RunningSpeed},
+    components::{
+        chunks::{collision::collide, SolidBlocks, SolidSnapshot},
+        goals::Steering,
+        instance::InInstance,
+        navigation::Navigation,
+        EntityReaction, FullEntityPose, Motion, Npc, RunningSpeed,
+    },
     event::Gametick,
-    singleton::player_aabb_lookup::PlayerBoundingBoxes,
 };
 
 #[derive(Query, Debug)]
@@ -18,6 +26,9 @@ pub struct EntityQuery<'a> {
     running_speed: Option<&'a RunningSpeed>,
     reaction: &'a mut EntityReaction,
     pose: &'a mut FullEntityPose,
+    motion: &'a mut Motion,
+    steering: &'a Steering,
+    instance: &'a InInstance,
     _entity: With<&'static Npc>,
 }
 
@@ -25,52 +36,96 @@ pub struct EntityQuery<'a> {
 pub fn entity_move_logic(
     _: Receiver<Gametick>,
     mut entities: Fetcher<EntityQuery>,
-    lookup: Single<&PlayerBoundingBoxes>,
+    instances: Fetcher<(EntityId, &SolidSnapshot)>,
+    navigation: Fetcher<(EntityId, &Navigation)>,
 ) {
+    /// Blocks per tick squared, like vanilla.
+    const GRAVITY: f32 = 0.08;
+    const DRAG: f32 = 0.98;
+
+    let instances: HashMap<_, _> = instances.iter().collect();
+
+    let fields: HashMap<_, _> = navigation
+        .iter()
+        .filter_map(|(id, navigation)| Some((id, navigation.field.as_ref()?)))
+        .collect();
+
     entities.par_iter_mut().for_each(|query| {
         let EntityQuery {
             running_speed,
             pose,
             reaction,
+            motion,
+            steering,
+            instance,
             ..
         } = query;
 
-        let current = pose.position;
-
-        let Some(target) = lookup.closest_to(current) else {
+        let Some(snapshot) = instances.get(&instance.0) else {
             return;
         };
 
-        let dif_mid = target.aabb.mid() - current;
-        // let dif_height = target.aabb.min.y - current.y;
-
-        let dif2d = Vec2::new(dif_mid.x, dif_mid.z);
+        // like vanilla, entities in columns which are not loaded stand still
+        if !snapshot.contains(pose.chunk_pos()) {
+            reaction.velocity = Vec3::ZERO;
+            return;
+        }
 
-        let yaw = dif2d.y.atan2(dif2d.x).to_degrees();
+        let current = pose.position;
 
-        // subtract 90 degrees
-        let yaw = yaw - 90.0;
+        let dif_mid = match *steering {
+            Steering::Idle => Vec3::ZERO,
+            // follow the flow field of the player if they can be reached, otherwise walk straight
+            // to the point
+            Steering::Seek { point, towards } => {
+                let next_step = towards
+                    .and_then(|id| fields.get(&id))
+                    .and_then(|field| field.next_step(current.floor().as_ivec3()))
+                    .filter(|next| *next != current.floor().as_ivec3());
+
+                match next_step {
+                    Some(next) => next.as_vec3() + Vec3::new(0.5, 0.0, 0.5) - current,
+                    None => point - current,
+                }
+            }
+            Steering::Flee { point } => current - point,
+        };
 
-        // let pitch = -dif_height.atan2(dif2d.length()).to_degrees();
-        let pitch = 0.0;
+        let dif2d = Vec2::new(dif_mid.x, dif_mid.z);
 
-        if dif2d.length_squared() < 0.01 {
-            // info!("Moving entity {:?} by {:?}", id, reaction.velocity);
-            pose.move_by(reaction.velocity);
+        let walk = if dif2d.length_squared() < 0.01 {
+            Vec3::ZERO
         } else {
+            // face where the entity is walking
+            let yaw = dif2d.y.atan2(dif2d.x).to_degrees();
+
+            // subtract 90 degrees
+            pose.yaw = yaw - 90.0;
+            pose.pitch = 0.0;
+
             // normalize
             let dif2d = dif2d.normalize();
 
             let speed = running_speed.copied().unwrap_or_default();
             let dif2d = dif2d * speed.0;
 
-            let vec = Vec3::new(dif2d.x, 0.0, dif2d.y) + reaction.velocity;
+            Vec3::new(dif2d.x, 0.0, dif2d.y)
+        };
 
-            pose.move_by(vec);
-        }
+        // vertical knockback starts a jump instead of moving the entity once
+        let vertical = (motion.vertical_velocity + reaction.velocity.y - GRAVITY) * DRAG;
+
+        let movement = walk + Vec3::new(reaction.velocity.x, vertical, reaction.velocity.z);
+
+        let mut solids = SolidBlocks::new(snapshot);
+        let collision = collide(&pose.bounding, movement, motion.on_ground, |block| {
+            solids.is_solid(block)
+        });
+
+        pose.move_by(collision.offset);
 
-        pose.pitch = pitch;
-        pose.yaw = yaw;
+        motion.on_ground = collision.on_ground;
+        motion.vertical_velocity = if collision.vertical { 0.0 } else { vertical };
 
         reaction.velocity = Vec3::ZERO;
     });
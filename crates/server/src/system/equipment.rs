@@ -1,9 +1,18 @@
-use evenio::{entity::EntityId, event::Receiver, fetch::Fetcher};
+use evenio::{
+    entity::EntityId,
+    event::{Insert, Receiver, Sender},
+    fetch::{Fetcher, Single},
+};
 use tracing::{instrument, log::warn};
 use valence_protocol::VarInt;
 
 use crate::{
+    components::{
+        combat::{Armor, AttackCooldown},
+        HeldItem,
+    },
     event,
+    global::Global,
     net::{Compose, Packets},
     packets,
     packets::vanilla,
@@ -11,13 +20,42 @@ use crate::{
 
 #[instrument(skip_all, level = "trace")]
 pub fn set(
-    r: Receiver<event::SetEquipment, EntityId>,
+    r: Receiver<event::SetEquipment, (EntityId, Option<&mut Armor>, Option<&mut AttackCooldown>)>,
     mut players: Fetcher<(&mut Packets, EntityId)>,
+    global: Single<&Global>,
     compose: Compose,
+    mut s: Sender<(Insert<HeldItem>, Insert<Armor>)>,
 ) {
-    let id = r.query;
+    let (id, armor, cooldown) = r.query;
     let event = r.event;
 
+    // slot 0 is the main hand
+    if let Some(entry) = event.equipment.iter().find(|entry| entry.slot == 0) {
+        s.insert(id, HeldItem(entry.item.clone()));
+
+        // like vanilla, switching items has to be followed by a full cooldown
+        if let Some(cooldown) = cooldown {
+            cooldown.since = global.tick;
+        }
+    }
+
+    if let Some(armor) = armor {
+        for entry in event.equipment.iter() {
+            armor.set(entry.slot, entry.item.clone());
+        }
+    } else {
+        let mut armor = Armor::default();
+        let mut worn = false;
+
+        for entry in event.equipment.iter() {
+            worn |= armor.set(entry.slot, entry.item.clone());
+        }
+
+        if worn {
+            s.insert(id, armor);
+        }
+    }
+
     let pkt_self = packets::vanilla::EntityEquipmentUpdateS2c {
         entity_id: 0.into(),
         equipment: event.equipment.clone(),
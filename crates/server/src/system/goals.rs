@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use evenio::prelude::*;
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+use tracing::instrument;
+
+use crate::{
+    components::{
+        goals::{Goals, Steering, TargetSelector},
+        instance::InInstance,
+        FullEntityPose,
+    },
+    event::Gametick,
+    singleton::player_aabb_lookup::PlayerBoundingBoxes,
+};
+
+#[derive(Query)]
+pub(crate) struct GoalQuery<'a> {
+    pose: &'a FullEntityPose,
+    instance: &'a InInstance,
+    goals: &'a mut Goals,
+    steering: &'a mut Steering,
+}
+
+/// Sets the [`Steering`] of every NPC with [`Goals`] from its highest priority goal which
+/// applies.
+#[instrument(skip_all, level = "trace")]
+pub fn evaluate_goals(
+    _: Receiver<Gametick>,
+    mut npcs: Fetcher<GoalQuery>,
+    poses: Fetcher<&FullEntityPose>,
+    lookup: Single<&PlayerBoundingBoxes>,
+) {
+    // only the entities goals refer to by id
+    let positions: HashMap<_, _> = npcs
+        .iter()
+        .flat_map(|query| query.goals.iter().filter_map(|goal| goal.entity()))
+        .filter_map(|id| Some((id, poses.get(id).ok()?.position)))
+        .collect();
+
+    npcs.par_iter_mut().for_each(|query| {
+        let GoalQuery {
+            pose,
+            instance,
+            goals,
+            steering,
+        } = query;
+
+        let position = pose.position;
+
+        *steering = goals.evaluate(position, |target| match target {
+            TargetSelector::Closest => lookup
+                .closest_to(instance.0, position)
+                .map(|target| (target.id, target.aabb.mid())),
+            TargetSelector::Entity(id) => positions.get(&id).map(|&position| (id, position)),
+        });
+    });
+}
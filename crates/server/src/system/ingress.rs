@@ -29,7 +29,7 @@ use crate::{
 mod player_packet_buffer;
 
 use crate::{
-    components::{FullEntityPose, LoginState},
+    components::{FullEntityPose, LoginState, PlayerMotion},
     net::{buffers::BufferAllocator, Compose, Fd, Packets, MINECRAFT_VERSION, PROTOCOL_VERSION},
     packets::PacketSwitchQuery,
     singleton::player_id_lookup::EntityIdLookup,
@@ -49,6 +49,9 @@ pub type IngressSender<'a> = Sender<
         event::BlockFinishBreak,
         event::Command,
         event::PoseUpdate,
+        event::ClientSettingsUpdate,
+        event::UseItem,
+        event::ReleaseItem,
     ),
 >;
 
@@ -208,6 +211,9 @@ pub enum SendElem {
     BlockFinishBreak(event::BlockFinishBreak),
     Command(event::Command),
     PoseUpdate(event::PoseUpdate),
+    ClientSettingsUpdate(event::ClientSettingsUpdate),
+    UseItem(event::UseItem),
+    ReleaseItem(event::ReleaseItem),
 }
 
 #[instrument(skip_all, level = "trace")]
@@ -221,7 +227,7 @@ pub fn recv_data(
         &mut DecodeBuffer,
         &mut Packets,
         &Fd,
-        Option<&mut FullEntityPose>,
+        Option<(&mut FullEntityPose, &mut PlayerMotion)>,
     )>,
     id_lookup: Single<&EntityIdLookup>,
     mut real_sender: IngressSender,
@@ -234,7 +240,7 @@ pub fn recv_data(
 
     players
         .par_iter_mut()
-        .for_each(|(login_state, decoder, packets, fd, mut pose)| {
+        .for_each(|(login_state, decoder, packets, fd, mut play)| {
             let Some(data) = elements.get(fd) else {
                 return;
             };
@@ -294,8 +300,8 @@ pub fn recv_data(
                                 }
                             }
 
-                            if let Some(pose) = pose.as_mut() {
-                                let mut query = PacketSwitchQuery { id, pose };
+                            if let Some((pose, motion)) = play.as_mut() {
+                                let mut query = PacketSwitchQuery { id, pose, motion };
 
                                 crate::packets::switch(frame, sender, &id_lookup, &mut query)
                                     .unwrap();
@@ -338,6 +344,15 @@ pub fn recv_data(
             SendElem::PoseUpdate(event) => {
                 real_sender.send(event);
             }
+            SendElem::ClientSettingsUpdate(event) => {
+                real_sender.send(event);
+            }
+            SendElem::UseItem(event) => {
+                real_sender.send(event);
+            }
+            SendElem::ReleaseItem(event) => {
+                real_sender.send(event);
+            }
         }
     }
 
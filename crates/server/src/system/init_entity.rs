@@ -1,21 +1,27 @@
 use evenio::{
     entity::EntityId,
     event::{Insert, Receiver, Sender, Spawn},
+    fetch::Fetcher,
     prelude::Single,
 };
 use rand_distr::{Distribution, LogNormal};
-use tracing::{info, instrument};
+use tracing::{info, instrument, warn};
 use valence_protocol::{ByteAngle, VarInt, Velocity};
 use valence_server::entity::EntityKind;
 
 use crate::{
     components::{
-        Display, EntityReaction, FullEntityPose, ImmuneStatus, Npc, RunningSpeed, Uuid, Vitals,
+        entity_kind::KindInfo,
+        goals::{Goal, Goals, Steering, TargetSelector},
+        instance::{InInstance, Instance},
+        metadata::Metadata,
+        Display, EntityReaction, FullEntityPose, ImmuneStatus, Motion, Npc, RunningSpeed, Uuid,
+        Vitals,
     },
     event::InitEntity,
-    net::{Broadcast, Compose},
-    singleton::player_id_lookup::EntityIdLookup,
+    singleton::{default_instance::DefaultInstance, player_id_lookup::EntityIdLookup},
     system::sync_entity_position::PositionSyncMetadata,
+    tracker::Prev,
 };
 
 #[tracing::instrument]
@@ -38,11 +44,14 @@ pub fn spawn_entity_packet(
         pitch: ByteAngle::from_degrees(pose.pitch),
         yaw: ByteAngle::from_degrees(pose.yaw),
         head_yaw: ByteAngle::from_degrees(pose.head_yaw()),
-        data: VarInt::default(),
+        // only non-living entities use this, like projectiles for their owner
+        data: VarInt(0),
         velocity: Velocity([0; 3]),
     }
 }
 
+/// Spawns an NPC. Players are sent its spawn packet once they have the chunk it is in loaded,
+/// see [`crate::system::visibility`].
 #[instrument(skip_all)]
 pub fn init_entity(
     r: Receiver<InitEntity>,
@@ -54,46 +63,71 @@ pub fn init_entity(
         Insert<Uuid>,
         Insert<RunningSpeed>,
         Insert<EntityReaction>,
+        Insert<Motion>,
         Insert<Vitals>,
         Insert<ImmuneStatus>,
         Insert<Display>,
+        Insert<InInstance>,
+        Insert<Metadata>,
+        Insert<Prev<Metadata>>,
+        Insert<Goals>,
+        Insert<Steering>,
         Spawn,
     )>,
-    broadcast: Single<&Broadcast>,
-    compose: Compose,
+    default_instance: Single<&DefaultInstance>,
+    instances: Fetcher<&Instance>,
 ) {
     let event = r.event;
 
+    let instance = event.instance.unwrap_or(***default_instance);
+
+    if instances.get(instance).is_err() {
+        warn!("tried to spawn an entity in {instance:?} which is not an instance");
+        return;
+    }
+
     let id = s.spawn();
 
+    let info = KindInfo::of(event.display);
+
+    let mut pose = event.pose;
+    pose.bounding = info.bounding(pose.position);
+
     let uuid = Uuid::from(uuid::Uuid::new_v4());
 
     s.insert(id, Npc);
-    s.insert(id, event.pose);
+    s.insert(id, pose);
     s.insert(id, uuid);
     s.insert(id, EntityReaction::default());
+    s.insert(id, Motion::default());
     s.insert(id, ImmuneStatus::default());
-    s.insert(id, generate_running_speed());
+    s.insert(id, generate_running_speed(&info));
     s.insert(id, PositionSyncMetadata::default());
     s.insert(id, Display(event.display));
-    s.insert(id, Vitals::ALIVE);
-
-    id_lookup.insert(id.index().0 as i32, id);
+    s.insert(id, Metadata::default());
+    s.insert(id, Prev::from(Metadata::default()));
+    s.insert(id, InInstance(instance));
+    s.insert(id, Vitals::alive(info.max_health));
 
-    let pose = event.pose;
+    let goals = event.goals.clone().unwrap_or_else(|| {
+        Goals::default().with(0, Goal::Chase {
+            target: TargetSelector::Closest,
+            range: f32::INFINITY,
+        })
+    });
 
-    let pkt = spawn_entity_packet(id, EntityKind::ZOMBIE, uuid, &pose);
+    s.insert(id, goals);
+    s.insert(id, Steering::default());
 
-    // todo: use shared scratch if possible
-    broadcast.append(&pkt, &compose).unwrap();
+    id_lookup.insert(id.index().0 as i32, id);
 }
 
-fn generate_running_speed() -> RunningSpeed {
+fn generate_running_speed(info: &KindInfo) -> RunningSpeed {
     // Parameters for the Log-Normal distribution
     let mean = 0.10; // Mean of the underlying Normal distribution
     let std_dev = 0.20; // Standard deviation of the underlying Normal distribution
     let log_normal = LogNormal::new(mean, std_dev).unwrap();
 
-    let speed = log_normal.sample(&mut rand::thread_rng()) * 0.1;
+    let speed = log_normal.sample(&mut rand::thread_rng()) * info.blocks_per_tick();
     RunningSpeed(speed)
 }
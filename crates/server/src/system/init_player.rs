@@ -8,12 +8,21 @@ use valence_protocol::{packets::login, Bounded};
 
 use crate::{
     components::{
-        AiTargetable, ChunkLocation, EntityReaction, FullEntityPose, ImmuneStatus, InGameName,
-        KeepAlive, Player, Uuid, Vitals,
+        combat::AttackCooldown,
+        hit_validation::{AttackRate, Violations},
+        instance::InInstance,
+        metadata::Metadata,
+        navigation::Navigation,
+        AiTargetable, ChunkLocation, ClientSettings, EntityReaction, FullEntityPose, ImmuneStatus,
+        InGameName, KeepAlive, Player, PlayerMotion, Uuid, ViewDistance, Vitals,
     },
     event::{PlayerInit, PlayerJoinWorld},
     net::{Compose, Packets},
-    system::{chunks::ChunkChanges, sync_entity_position::PositionSyncMetadata},
+    singleton::default_instance::DefaultInstance,
+    system::{
+        chunks::ChunkChanges, sync_entity_position::PositionSyncMetadata,
+        visibility::VisibleEntities,
+    },
     tracker::Prev,
 };
 
@@ -31,6 +40,7 @@ fn offline_uuid(username: &str) -> anyhow::Result<uuid::Uuid> {
 pub fn init_player(
     r: ReceiverMut<PlayerInit, &mut Packets>,
     compose: Compose,
+    default_instance: Single<&DefaultInstance>,
     mut s: Sender<(
         Insert<FullEntityPose>,
         Insert<PositionSyncMetadata>,
@@ -45,6 +55,17 @@ pub fn init_player(
         Insert<AiTargetable>,
         Insert<InGameName>,
         Insert<ChunkChanges>,
+        Insert<VisibleEntities>,
+        Insert<ViewDistance>,
+        Insert<ClientSettings>,
+        Insert<InInstance>,
+        Insert<Metadata>,
+        Insert<Prev<Metadata>>,
+        Insert<Navigation>,
+        Insert<PlayerMotion>,
+        Insert<AttackCooldown>,
+        Insert<AttackRate>,
+        Insert<Violations>,
         PlayerJoinWorld,
     )>,
 ) {
@@ -85,6 +106,17 @@ pub fn init_player(
 
     s.insert(entity, FullEntityPose::player());
     s.insert(entity, ChunkChanges::default());
+    s.insert(entity, VisibleEntities::default());
+    s.insert(entity, ViewDistance::default());
+    s.insert(entity, ClientSettings::default());
+    s.insert(entity, Metadata::default());
+    s.insert(entity, Prev::from(Metadata::default()));
+    s.insert(entity, InInstance(***default_instance));
+    s.insert(entity, Navigation::default());
+    s.insert(entity, PlayerMotion::default());
+    s.insert(entity, AttackCooldown::default());
+    s.insert(entity, AttackRate::default());
+    s.insert(entity, Violations::default());
 
     // so we always send updates
     s.insert(entity, ChunkLocation::NULL);
@@ -1,32 +1,20 @@
 use evenio::prelude::*;
 use tracing::instrument;
-use valence_protocol::VarInt;
 
 use crate::{
     components::{Npc, Player},
     event::KillAllEntities,
-    net::{Broadcast, Compose},
 };
 
+/// Despawns every NPC. Players who can see them are sent the destroy packets by
+/// [`crate::system::visibility::update_visible_entities`].
 #[instrument(skip_all)]
 pub fn kill_all(
     _r: ReceiverMut<KillAllEntities>,
     entities: Fetcher<(EntityId, &Npc, Not<&Player>)>,
-    broadcast: Single<&Broadcast>,
     mut s: Sender<Despawn>,
-    compose: Compose,
 ) {
-    let ids = entities.iter().map(|(id, ..)| id).collect::<Vec<_>>();
-
-    #[expect(clippy::cast_possible_wrap, reason = "wrapping is ok in this case")]
-    let entity_ids = ids.iter().map(|id| VarInt(id.index().0 as i32)).collect();
-
-    let despawn_packet = valence_protocol::packets::play::EntitiesDestroyS2c { entity_ids };
-
-    // todo: use shared scratch if possible
-    broadcast.append(&despawn_packet, &compose).unwrap();
-
-    for id in ids {
+    for (id, ..) in entities {
         s.send(Despawn(id));
     }
 }
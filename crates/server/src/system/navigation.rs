@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use evenio::prelude::*;
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+use tracing::instrument;
+
+use crate::{
+    components::{
+        chunks::{Chunks, SolidBlocks, SolidSnapshot},
+        instance::InInstance,
+        navigation::{FlowField, Navigation},
+        FullEntityPose,
+    },
+    config::CONFIG,
+    event::Gametick,
+    global::Global,
+};
+
+/// A field is not recomputed for a moving player more often than this many ticks.
+const MIN_TICKS: i64 = 5;
+
+/// A field is recomputed at least this often so chunks which loaded since are walked through.
+const REFRESH_TICKS: i64 = 40;
+
+#[derive(Query)]
+pub(crate) struct NavigationQuery<'a> {
+    pose: &'a FullEntityPose,
+    instance: &'a InInstance,
+    navigation: &'a mut Navigation,
+}
+
+/// Recomputes the flow fields of players who moved or whose surroundings changed.
+#[instrument(skip_all, level = "trace")]
+pub fn update_navigation(
+    _: Receiver<Gametick>,
+    mut players: Fetcher<NavigationQuery>,
+    instances: Fetcher<(EntityId, &Chunks, &SolidSnapshot)>,
+    global: Single<&Global>,
+) {
+    let tick = global.tick;
+
+    let instances: HashMap<_, _> = instances
+        .iter()
+        .map(|(id, chunks, snapshot)| (id, (chunks, snapshot)))
+        .collect();
+
+    players.par_iter_mut().for_each(|query| {
+        let NavigationQuery {
+            pose,
+            instance,
+            navigation,
+        } = query;
+
+        let Some(&(chunks, snapshot)) = instances.get(&instance.0) else {
+            return;
+        };
+
+        let block = pose.position.floor().as_ivec3();
+        let age = tick - navigation.computed_at;
+
+        if let Some(field) = &navigation.field {
+            let moved = block != navigation.computed_from;
+
+            let stale = age >= REFRESH_TICKS
+                || (moved && age >= MIN_TICKS)
+                || !field.is_current(|column| chunks.revision(column));
+
+            if !stale {
+                return;
+            }
+        }
+
+        let mut solids = SolidBlocks::new(snapshot);
+
+        let field = FlowField::compute(
+            block,
+            CONFIG.navigation_radius,
+            |block| solids.is_solid(block),
+            |column| chunks.revision(column),
+        );
+
+        navigation.field = Some(field);
+        navigation.computed_at = tick;
+        navigation.computed_from = block;
+    });
+}
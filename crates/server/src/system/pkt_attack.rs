@@ -1,13 +1,27 @@
+use std::f32::consts::TAU;
+
 use evenio::prelude::*;
+use glam::Vec2;
 use tracing::instrument;
-use valence_protocol::{packets::play, VarInt};
+use valence_protocol::{packets::play, ItemStack, VarInt};
 
 use crate::{
-    components::{EntityReaction, FullEntityPose, ImmuneStatus, Player, Vitals},
-    event::AttackEntity,
-    net::{Broadcast, Compose, Packets},
+    components::{
+        combat::{knockback, Armor, AttackCooldown, CombatRules, DamageType, Swing},
+        instance::{InInstance, InstanceBroadcast},
+        EntityReaction, FullEntityPose, HeldItem, ImmuneStatus, Player, PlayerMotion, Vitals,
+    },
+    event::{AttackEntity, AttackType},
+    global::Global,
+    net::{Compose, Packets},
 };
 
+/// The animation clients play for critical hits.
+const CRITICAL_EFFECT: u8 = 4;
+
+/// The animation clients play for hits whose damage enchantments increased.
+const MAGIC_CRITICAL_EFFECT: u8 = 5;
+
 #[derive(Query)]
 pub struct AttackPlayerQuery<'a> {
     id: EntityId,
@@ -19,9 +33,87 @@ pub struct AttackPlayerQuery<'a> {
 pub struct AttackEntityQuery<'a> {
     id: EntityId,
     pose: &'a FullEntityPose,
+    instance: &'a InInstance,
     reaction: &'a mut EntityReaction,
     immunity: &'a mut ImmuneStatus,
     vitals: &'a mut Vitals,
+    armor: Option<&'a Armor>,
+}
+
+#[derive(Query)]
+pub struct AttackerQuery<'a> {
+    pose: &'a FullEntityPose,
+    held: Option<&'a HeldItem>,
+    cooldown: &'a mut AttackCooldown,
+    motion: &'a mut PlayerMotion,
+}
+
+fn entity_id(id: EntityId) -> VarInt {
+    VarInt(id.index().0 as i32)
+}
+
+/// Works out the damage and knockback of melee attacks by players from what they hold and how
+/// they move. This runs before [`check_immunity`] because attacking resets the cooldown even if
+/// the target cannot be hurt.
+#[instrument(skip_all, level = "trace")]
+pub fn melee_damage(
+    global: Single<&Global>,
+    rules: Single<&CombatRules>,
+    mut attack: ReceiverMut<AttackEntity, (EntityId, &InInstance)>,
+    mut attackers: Fetcher<AttackerQuery>,
+    instances: Fetcher<&InstanceBroadcast>,
+    compose: Compose,
+) {
+    let (target, instance) = attack.query;
+    let event = &mut attack.event;
+
+    if !matches!(event.source, AttackType::Melee) {
+        return;
+    }
+
+    let Ok(attacker) = attackers.get_mut(event.from) else {
+        return;
+    };
+
+    let empty = ItemStack::default();
+    let item = attacker.held.map_or(&empty, |held| &held.0);
+
+    let hit = rules.melee(Swing {
+        item,
+        ticks: global.tick - attacker.cooldown.since,
+        falling: attacker.motion.falling,
+        sprinting: attacker.motion.sprinting,
+    });
+
+    attacker.cooldown.since = global.tick;
+
+    if hit.stops_sprint {
+        attacker.motion.sprinting = false;
+    }
+
+    // extra knockback pushes the target along the direction the attacker looks
+    let look = attacker.pose.look_direction();
+
+    event.damage = hit.damage;
+    event.knockback = Vec2::new(look.x, look.z).normalize_or_zero() * hit.knockback;
+
+    let Ok(broadcast) = instances.get(**instance) else {
+        return;
+    };
+
+    for (shown, animation) in [
+        (hit.critical, CRITICAL_EFFECT),
+        (hit.enchanted, MAGIC_CRITICAL_EFFECT),
+    ] {
+        if shown {
+            let pkt = play::EntityAnimationS2c {
+                entity_id: entity_id(target),
+                animation,
+            };
+
+            broadcast.append(&pkt, &compose).unwrap();
+        }
+    }
 }
 
 #[instrument(skip_all, level = "trace")]
@@ -44,7 +136,7 @@ pub fn pkt_attack_player(attack: Receiver<AttackEntity, AttackPlayerQuery>, comp
         _player,
     } = attack.query;
 
-    let mut damage_broadcast = get_package(entity_id);
+    let mut damage_broadcast = damage_packet(entity_id, attack.event);
     // local is id 0
     damage_broadcast.entity_id = VarInt(0);
 
@@ -55,60 +147,69 @@ pub fn pkt_attack_player(attack: Receiver<AttackEntity, AttackPlayerQuery>, comp
 #[instrument(skip_all, level = "trace")]
 pub fn pkt_attack_entity(
     global: Single<&crate::global::Global>,
+    rules: Single<&CombatRules>,
     attack: Receiver<AttackEntity, AttackEntityQuery>,
-    broadcast: Single<&Broadcast>,
+    instances: Fetcher<&InstanceBroadcast>,
     compose: Compose,
 ) {
     let AttackEntityQuery {
         id: entity_id,
         pose,
+        instance,
         reaction,
         vitals,
         immunity,
+        armor,
     } = attack.query;
 
-    let damage_broadcast = get_package(entity_id);
-
-    broadcast.append(&damage_broadcast, &compose).unwrap();
-
     let event = attack.event;
 
-    let this = pose.position;
-    let other = event.from_pos;
+    let damage_broadcast = damage_packet(entity_id, event);
 
-    let delta_x = other.x - this.x;
-    let delta_z = other.z - this.z;
+    if let Ok(broadcast) = instances.get(**instance) {
+        broadcast.append(&damage_broadcast, &compose).unwrap();
+    }
 
-    if delta_x.abs() < 0.01 && delta_z.abs() < 0.01 {
-        // todo: implement like vanilla
-        return;
+    let mut away = Vec2::new(
+        pose.position.x - event.from_pos.x,
+        pose.position.z - event.from_pos.z,
+    );
+
+    // like vanilla, an attacker right on top of the target pushes it in a random direction
+    if away.length_squared() < 1.0e-4 {
+        away = Vec2::from_angle(fastrand::f32() * TAU);
     }
 
-    let dist_xz = delta_x.hypot(delta_z);
-    let multiplier = 0.4;
+    let resistance = armor.map_or(0.0, |armor| rules.knockback_resistance(armor));
+    let strength = 1.0 - resistance;
 
-    reaction.velocity.x /= 2.0;
-    reaction.velocity.y /= 2.0;
-    reaction.velocity.z /= 2.0;
-    reaction.velocity.x -= delta_x / dist_xz * multiplier;
-    reaction.velocity.y += multiplier;
-    reaction.velocity.z -= delta_z / dist_xz * multiplier;
+    knockback(&mut reaction.velocity, rules.knockback * strength, away);
+    knockback(
+        &mut reaction.velocity,
+        event.knockback.length() * strength,
+        event.knockback,
+    );
 
-    if reaction.velocity.y > 0.4 {
-        reaction.velocity.y = 0.4;
-    }
+    let damage = armor.map_or(event.damage, |armor| rules.defend(event.damage, armor));
 
-    vitals.hurt(&global, event.damage, immunity);
+    vitals.hurt(&global, damage, immunity);
 }
 
+/// Tells clients `target` was hurt by `attack`, so they show who hurt it and how.
 #[instrument(skip_all, level = "trace")]
-fn get_package(id: EntityId) -> play::EntityDamageS2c {
-    // todo
+fn damage_packet(target: EntityId, attack: &AttackEntity) -> play::EntityDamageS2c {
+    let (source_type, direct) = match attack.source {
+        AttackType::Melee => (DamageType::PlayerAttack, attack.from),
+        AttackType::Shove => (DamageType::MobAttack, attack.from),
+        AttackType::Projectile { kind, projectile } => (kind.damage_type(), projectile),
+    };
+
+    // source entities are offset by one so zero can mean there is none
     play::EntityDamageS2c {
-        entity_id: VarInt(id.index().0 as i32),
-        source_type_id: VarInt::default(),
-        source_cause_id: VarInt::default(),
-        source_direct_id: VarInt::default(),
+        entity_id: entity_id(target),
+        source_type_id: VarInt(source_type.id()),
+        source_cause_id: VarInt(entity_id(attack.from).0 + 1),
+        source_direct_id: VarInt(entity_id(direct).0 + 1),
         source_pos: None,
     }
 }
@@ -3,17 +3,23 @@ use tracing::instrument;
 use valence_protocol::{packets::play, Hand, VarInt};
 
 use crate::{
+    components::instance::{InInstance, InstanceBroadcast},
     event::SwingArm,
-    net::{Broadcast, Compose},
+    net::Compose,
 };
 
 #[instrument(skip_all, level = "trace")]
 pub fn pkt_hand_swing(
-    swing_arm: Receiver<SwingArm, EntityId>,
-    broadcast: Single<&mut Broadcast>,
+    swing_arm: Receiver<SwingArm, (EntityId, &InInstance)>,
+    instances: Fetcher<&InstanceBroadcast>,
     compose: Compose,
 ) {
-    let entity_id = swing_arm.query;
+    let (entity_id, instance) = swing_arm.query;
+
+    let Ok(broadcast) = instances.get(**instance) else {
+        return;
+    };
+
     let entity_id = VarInt(entity_id.index().0 as i32);
     let hand = swing_arm.event.hand;
 
@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+
+use bvh_region::BroadPhase;
 use evenio::{
     entity::EntityId,
     event::{Receiver, Sender},
@@ -5,21 +8,20 @@ use evenio::{
     prelude::With,
     query::Query,
 };
-use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
-use rayon_local::RayonLocal;
 use tracing::instrument;
 
 use crate::{
-    components::{FullEntityPose, Player},
+    components::{instance::InInstance, FullEntityPose, Player},
     event,
     event::Gametick,
-    singleton::bounding_box::EntityBoundingBoxes,
+    singleton::bounding_box::{EntityBoundingBoxes, Stored},
 };
 
 #[derive(Query)]
 pub struct PlayerDetectMobHitsQuery<'a> {
     id: EntityId,
     pose: &'a FullEntityPose,
+    instance: &'a InInstance,
     _player: With<&'static Player>,
 }
 
@@ -27,34 +29,48 @@ pub struct PlayerDetectMobHitsQuery<'a> {
 pub fn player_detect_mob_hits(
     _: Receiver<Gametick>,
     entity_bounding_boxes: Single<&EntityBoundingBoxes>,
-    mut poses_fetcher: Fetcher<PlayerDetectMobHitsQuery>,
+    poses_fetcher: Fetcher<PlayerDetectMobHitsQuery>,
     mut s: Sender<event::BulkShoved>,
 ) {
-    let sender = RayonLocal::init(Vec::new);
+    let mut by_instance: HashMap<_, Vec<_>> = HashMap::new();
+
+    for query in &poses_fetcher {
+        by_instance
+            .entry(query.instance.0)
+            .or_default()
+            .push(Stored {
+                aabb: query.pose.bounding,
+                id: query.id,
+            });
+    }
 
-    poses_fetcher.par_iter_mut().for_each(|query| {
-        let PlayerDetectMobHitsQuery { id, pose, _player } = query;
+    for (instance, players) in by_instance {
+        let Some(query) = entity_bounding_boxes.get(instance) else {
+            continue;
+        };
 
-        let sender = sender.get_local_raw();
-        let sender = unsafe { &mut *sender.get() };
+        let collisions = query.par_collide_all(&players, usize::MAX);
 
-        entity_bounding_boxes
-            .query
-            .get_collisions(pose.bounding, |collision| {
-                // do not include self
-                if collision.id == id {
-                    return true;
-                }
+        let shoved = collisions.map(|collisions| {
+            collisions
+                .into_iter()
+                .filter_map(|(idx, collision)| {
+                    let target = players[idx].id;
 
-                sender.push(event::Shoved {
-                    target: id,
-                    from: collision.id,
-                    from_location: collision.aabb.mid(),
-                });
+                    // do not include self
+                    if collision.id == target {
+                        return None;
+                    }
 
-                true
-            });
-    });
+                    Some(event::Shoved {
+                        target,
+                        from: collision.id,
+                        from_location: collision.aabb.mid(),
+                    })
+                })
+                .collect()
+        });
 
-    s.send(event::BulkShoved(sender));
+        s.send(event::BulkShoved(shoved));
+    }
 }
@@ -4,7 +4,7 @@ use anyhow::{bail, Context};
 use evenio::prelude::*;
 use glam::{I16Vec2, IVec3};
 use serde::Deserialize;
-use tracing::{debug, info, instrument, trace, warn};
+use tracing::{info, instrument, trace, warn};
 use valence_nbt::{value::ValueRef, Value};
 use valence_protocol::{
     game_mode::OptGameMode,
@@ -26,18 +26,25 @@ use valence_registry::{
     biome::{Biome, BiomeEffects},
     BiomeRegistry, RegistryCodec,
 };
+use valence_server::entity::EntityKind;
 
 use crate::{
     components::{
         chunks::{Chunks, Tasks},
-        Display, FullEntityPose, InGameName, Player, Uuid, PLAYER_SPAWN_POSITION,
+        instance::{InInstance, InstanceBroadcast},
+        metadata::Metadata,
+        world_border::WorldBorder,
+        Display, FullEntityPose, InGameName, Npc, Player, Uuid, PLAYER_SPAWN_POSITION,
     },
     config::CONFIG,
     event,
     event::PlayerJoinWorld,
     global::Global,
     net::{Broadcast, Compose, Packets},
-    singleton::{player_id_lookup::EntityIdLookup, player_uuid_lookup::PlayerUuidLookup},
+    singleton::{
+        default_instance::DefaultInstance, player_id_lookup::EntityIdLookup,
+        player_uuid_lookup::PlayerUuidLookup,
+    },
     system::init_entity::spawn_entity_packet,
 };
 
@@ -47,6 +54,10 @@ pub(crate) struct EntityQuery<'a> {
     uuid: &'a Uuid,
     pose: &'a FullEntityPose,
     skin: &'a Display,
+    instance: &'a InInstance,
+    metadata: &'a Metadata,
+    // NPCs are spawned once the chunks they are in have been sent
+    _npc: Not<&'static Npc>,
 }
 
 #[derive(Query)]
@@ -56,6 +67,7 @@ pub(crate) struct PlayerJoinWorldQuery<'a> {
     pose: &'a FullEntityPose,
     packets: &'a mut Packets,
     name: &'a InGameName,
+    instance: &'a InInstance,
     _player: With<&'static Player>,
 }
 
@@ -64,6 +76,8 @@ pub(crate) struct PlayerQuery<'a> {
     id: EntityId,
     uuid: &'a Uuid,
     pose: &'a FullEntityPose,
+    instance: &'a InInstance,
+    metadata: &'a Metadata,
     _player: With<&'static Player>,
     _no_display: Not<&'static Display>,
 }
@@ -80,7 +94,8 @@ pub fn player_join_world(
     mut uuid_lookup: Single<&mut PlayerUuidLookup>,
     mut id_lookup: Single<&mut EntityIdLookup>,
     broadcast: Single<&Broadcast>,
-    chunks: Single<&Chunks>,
+    instances: Fetcher<(&Chunks, &WorldBorder, &InstanceBroadcast)>,
+    default_instance: Single<&DefaultInstance>,
     tasks: Single<&Tasks>,
     compose: Compose,
     mut sender: Sender<event::PostPlayerJoinWorld>,
@@ -89,12 +104,21 @@ pub fn player_join_world(
 
     let compression_level = global.0.shared.compression_threshold;
 
+    let query = r.query;
+
+    // the cached data contains the spawn chunk of the default instance, which is where every
+    // player joins
+    let instance = **query.instance;
+    debug_assert_eq!(instance, ***default_instance);
+
+    let (chunks, border, instance_broadcast) = instances.get(instance).unwrap();
+
     let cached_data = CACHED_DATA.get_or_init(|| {
         let mut encoder = PacketEncoder::new();
         encoder.set_compression(compression_level);
 
         info!("caching world data for new players");
-        inner(&mut encoder, &chunks, &tasks).unwrap();
+        inner(&mut encoder, chunks, &tasks).unwrap();
 
         let bytes = encoder.take();
         bytes.freeze()
@@ -102,8 +126,6 @@ pub fn player_join_world(
 
     trace!("got cached data");
 
-    let query = r.query;
-
     let got_id = query.id;
 
     uuid_lookup.insert(query.uuid.0, query.id);
@@ -127,7 +149,7 @@ pub fn player_join_world(
         overlay: false,
     };
 
-    broadcast.append(&text, &compose).unwrap();
+    instance_broadcast.append(&text, &compose).unwrap();
 
     let local = query.packets;
     {
@@ -146,12 +168,26 @@ pub fn player_join_world(
         entries: Cow::Borrowed(entries),
     };
 
+    // the player list and teams are shared by all instances
     broadcast.append(&info, &compose).unwrap();
 
-    for entity in entities {
+    for entity in entities
+        .iter()
+        .filter(|entity| **entity.instance == instance)
+    {
         info!("spawning entity");
         let pkt = spawn_entity_packet(entity.id, entity.skin.0, *entity.uuid, entity.pose);
         local.append(&pkt, &compose).unwrap();
+
+        let mut bytes = Vec::new();
+        let entity_id = VarInt(entity.id.index().0 as i32);
+
+        if let Some(pkt) = entity
+            .metadata
+            .initial_packet(entity_id, entity.skin.0, &mut bytes)
+        {
+            local.append(&pkt, &compose).unwrap();
+        }
     }
 
     // todo: cache
@@ -213,6 +249,11 @@ pub fn player_join_world(
     let tick = global.tick;
     let time_of_day = tick % 24000;
 
+    // the border can change at any time so it is not part of the cached data
+    local
+        .append(&border.initialize_packet(tick), &compose)
+        .unwrap();
+
     local
         .append(
             &play::WorldTimeUpdateS2c {
@@ -224,7 +265,10 @@ pub fn player_join_world(
         .unwrap();
 
     // todo: cache
-    for current_query in &player_spawns {
+    for current_query in player_spawns
+        .iter()
+        .filter(|player| **player.instance == instance)
+    {
         let id = current_query.id;
         let pose = current_query.pose;
         let uuid = current_query.uuid;
@@ -240,6 +284,16 @@ pub fn player_join_world(
         };
 
         local.append(&pkt, &compose).unwrap();
+
+        let mut bytes = Vec::new();
+
+        if let Some(pkt) =
+            current_query
+                .metadata
+                .initial_packet(entity_id, EntityKind::PLAYER, &mut bytes)
+        {
+            local.append(&pkt, &compose).unwrap();
+        }
     }
 
     global
@@ -269,7 +323,7 @@ pub fn player_join_world(
         )
         .unwrap();
 
-    broadcast.append(&spawn_player, &compose).unwrap();
+    instance_broadcast.append(&spawn_player, &compose).unwrap();
 
     info!("{} joined the world", query.name);
 
@@ -557,27 +611,5 @@ fn inner(encoder: &mut PacketEncoder, chunks: &Chunks, tasks: &Tasks) -> anyhow:
         },
     })?;
 
-    if let Some(diameter) = CONFIG.border_diameter {
-        debug!("Setting world border to diameter {}", diameter);
-
-        encoder.append_packet(&play::WorldBorderInitializeS2c {
-            x: f64::from(PLAYER_SPAWN_POSITION.x),
-            z: f64::from(PLAYER_SPAWN_POSITION.z),
-            old_diameter: diameter,
-            new_diameter: diameter,
-            duration_millis: 1.into(),
-            portal_teleport_boundary: 29_999_984.into(),
-            warning_blocks: 50.into(),
-            warning_time: 200.into(),
-        })?;
-
-        encoder.append_packet(&play::WorldBorderSizeChangedS2c { diameter })?;
-
-        encoder.append_packet(&play::WorldBorderCenterChangedS2c {
-            x_pos: f64::from(PLAYER_SPAWN_POSITION.x),
-            z_pos: f64::from(PLAYER_SPAWN_POSITION.z),
-        })?;
-    }
-
     Ok(())
 }
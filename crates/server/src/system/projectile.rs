@@ -0,0 +1,371 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use evenio::prelude::*;
+use glam::{Vec2, Vec3};
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+use tracing::instrument;
+use valence_protocol::{packets::play, Hand, VarInt, Velocity};
+use valence_server::ItemKind;
+
+use crate::{
+    components::{
+        chunks::{collision, SolidBlocks, SolidSnapshot},
+        entity_kind::KindInfo,
+        instance::{InInstance, InstanceBroadcast},
+        metadata::Metadata,
+        projectile::{
+            bow_power, Projectile, ProjectileKind, DRAG, KNOCKBACK, OWNER_GRACE_TICKS,
+            STUCK_DESPAWN_TICKS,
+        },
+        Display, FullEntityPose, HeldItem, UsingItem, Uuid,
+    },
+    event,
+    event::{AttackType, Gametick},
+    global::Global,
+    net::Compose,
+    singleton::bounding_box::EntityBoundingBoxes,
+    system::{init_entity::spawn_entity_packet, sync_entity_position::PositionSyncMetadata},
+    tracker::Prev,
+};
+
+/// Bows need to be drawn at least this much to shoot, like vanilla.
+const MIN_BOW_POWER: f32 = 0.1;
+
+/// Tridents need to be charged for this many ticks to be thrown, like vanilla.
+const MIN_TRIDENT_TICKS: i64 = 10;
+
+/// Blocks per tick.
+const ARROW_SPEED: f32 = 3.0;
+const SNOWBALL_SPEED: f32 = 1.5;
+const TRIDENT_SPEED: f32 = 2.5;
+
+fn entity_id(id: EntityId) -> VarInt {
+    VarInt(id.index().0 as i32)
+}
+
+/// Clients measure velocity in 1/8000 of a block per tick.
+fn velocity(velocity: Vec3) -> Velocity {
+    let velocity = (velocity * 8000.0).clamp(Vec3::splat(-32768.0), Vec3::splat(32767.0));
+    Velocity([velocity.x as i16, velocity.y as i16, velocity.z as i16])
+}
+
+/// Starts charging bows and tridents, and throws snowballs right away.
+#[instrument(skip_all)]
+pub fn use_item(
+    r: Receiver<event::UseItem, (EntityId, &HeldItem, &FullEntityPose)>,
+    global: Single<&Global>,
+    mut s: Sender<(Insert<UsingItem>, event::LaunchProjectile)>,
+) {
+    let (id, held, pose) = r.query;
+
+    // only the main hand is known
+    if r.event.hand != Hand::Main {
+        return;
+    }
+
+    match held.0.item {
+        ItemKind::Bow | ItemKind::Trident => {
+            s.insert(id, UsingItem { since: global.tick });
+        }
+        ItemKind::Snowball => {
+            s.send(event::LaunchProjectile {
+                shooter: id,
+                kind: ProjectileKind::Snowball,
+                velocity: pose.look_direction() * SNOWBALL_SPEED,
+            });
+        }
+        _ => {}
+    }
+}
+
+/// Shoots bows and throws tridents depending on how long they were charged.
+#[instrument(skip_all)]
+pub fn release_item(
+    r: Receiver<event::ReleaseItem, (EntityId, &HeldItem, &UsingItem, &FullEntityPose)>,
+    global: Single<&Global>,
+    mut s: Sender<(Remove<UsingItem>, event::LaunchProjectile)>,
+) {
+    let (id, held, using, pose) = r.query;
+
+    s.remove::<UsingItem>(id);
+
+    let ticks = global.tick - using.since;
+    let direction = pose.look_direction();
+
+    let (kind, velocity) = match held.0.item {
+        ItemKind::Bow => {
+            let power = bow_power(ticks);
+
+            if power < MIN_BOW_POWER {
+                return;
+            }
+
+            (ProjectileKind::Arrow, direction * power * ARROW_SPEED)
+        }
+        ItemKind::Trident if ticks >= MIN_TRIDENT_TICKS => {
+            (ProjectileKind::Trident, direction * TRIDENT_SPEED)
+        }
+        _ => return,
+    };
+
+    s.send(event::LaunchProjectile {
+        shooter: id,
+        kind,
+        velocity,
+    });
+}
+
+/// Spawns a projectile and shows it to the players in the instance of the shooter.
+#[instrument(skip_all)]
+pub fn launch(
+    r: Receiver<event::LaunchProjectile, (EntityId, &FullEntityPose, &InInstance)>,
+    instances: Fetcher<&InstanceBroadcast>,
+    compose: Compose,
+    mut s: Sender<(
+        Spawn,
+        Insert<Projectile>,
+        Insert<FullEntityPose>,
+        Insert<Uuid>,
+        Insert<InInstance>,
+        Insert<PositionSyncMetadata>,
+        Insert<Display>,
+        Insert<Metadata>,
+        Insert<Prev<Metadata>>,
+    )>,
+) {
+    let event = r.event;
+    let (owner, shooter, instance) = r.query;
+
+    let Ok(broadcast) = instances.get(instance.0) else {
+        return;
+    };
+
+    let kind = event.kind.entity_kind();
+
+    let position = shooter.eye_position() - Vec3::new(0.0, 0.1, 0.0);
+    let horizontal = event.velocity.x.hypot(event.velocity.z);
+
+    let pose = FullEntityPose {
+        position,
+        yaw: event.velocity.x.atan2(event.velocity.z).to_degrees(),
+        pitch: event.velocity.y.atan2(horizontal).to_degrees(),
+        bounding: KindInfo::of(kind).bounding(position),
+    };
+
+    let id = s.spawn();
+    let uuid = Uuid::from(uuid::Uuid::new_v4());
+
+    s.insert(id, Projectile {
+        kind: event.kind,
+        owner,
+        velocity: event.velocity,
+        stuck_since: None,
+        age: 0,
+    });
+    s.insert(id, pose);
+    s.insert(id, uuid);
+    s.insert(id, InInstance(instance.0));
+    s.insert(id, PositionSyncMetadata::default());
+    s.insert(id, Display(kind));
+    s.insert(id, Metadata::default());
+    s.insert(id, Prev::from(Metadata::default()));
+
+    let mut pkt = spawn_entity_packet(id, kind, uuid, &pose);
+
+    // clients show the projectile leaving the owner
+    pkt.data = VarInt(entity_id(owner).0 + 1);
+    pkt.velocity = velocity(event.velocity);
+
+    broadcast.append(&pkt, &compose).unwrap();
+}
+
+#[derive(Query)]
+pub(crate) struct ProjectileQuery<'a> {
+    id: EntityId,
+    projectile: &'a mut Projectile,
+    pose: &'a mut FullEntityPose,
+    instance: &'a InInstance,
+}
+
+enum Impact {
+    Entity {
+        projectile: EntityId,
+        target: EntityId,
+        owner: EntityId,
+        position: Vec3,
+        damage: f32,
+        knockback: Vec2,
+        kind: ProjectileKind,
+    },
+    Despawn {
+        projectile: EntityId,
+    },
+}
+
+/// Moves projectiles along their path and handles what they hit on the way.
+#[instrument(skip_all, level = "trace")]
+pub fn simulate(
+    _: Receiver<Gametick>,
+    mut projectiles: Fetcher<ProjectileQuery>,
+    entities: Fetcher<&InInstance>,
+    instances: Fetcher<(EntityId, &SolidSnapshot, &InstanceBroadcast)>,
+    entity_bounding_boxes: Single<&EntityBoundingBoxes>,
+    global: Single<&Global>,
+    compose: Compose,
+    mut s: Sender<(event::AttackEntity, Despawn)>,
+) {
+    if projectiles.iter().next().is_none() {
+        return;
+    }
+
+    let tick = global.tick;
+
+    let instances: HashMap<_, _> = instances
+        .iter()
+        .map(|(id, snapshot, broadcast)| (id, (snapshot, broadcast)))
+        .collect();
+
+    let impacts: Vec<_> = projectiles
+        .par_iter_mut()
+        .filter_map(|query| {
+            let ProjectileQuery {
+                id,
+                projectile,
+                pose,
+                instance,
+            } = query;
+
+            let &(snapshot, broadcast) = instances.get(&instance.0)?;
+
+            if let Some(since) = projectile.stuck_since {
+                return (tick - since >= STUCK_DESPAWN_TICKS)
+                    .then_some(Impact::Despawn { projectile: id });
+            }
+
+            // there is nothing to hit or stick in past the loaded terrain
+            if !snapshot.contains(pose.chunk_pos()) {
+                return Some(Impact::Despawn { projectile: id });
+            }
+
+            projectile.age += 1;
+
+            let movement = projectile.velocity;
+
+            let mut solids = SolidBlocks::unloaded_as_air(snapshot);
+            let offset = collision::sweep(&pose.bounding, movement, &mut |block| {
+                solids.is_solid(block)
+            });
+
+            #[expect(clippy::float_cmp, reason = "unchanged if no block was hit")]
+            let hit_block = offset != movement;
+
+            // entities are only hit before the block
+            let reach = if hit_block {
+                offset.length() / movement.length()
+            } else {
+                1.0
+            };
+
+            let hit = entity_bounding_boxes
+                .get(instance.0)
+                .and_then(|query| {
+                    query.sweep_query(&pose.bounding, movement, |candidate| {
+                        let owner_in_grace =
+                            candidate.id == projectile.owner && projectile.age <= OWNER_GRACE_TICKS;
+
+                        candidate.id != id && !owner_in_grace
+                    })
+                })
+                .filter(|hit| hit.toi <= reach);
+
+            if let Some(hit) = hit {
+                pose.move_by(movement * hit.toi);
+
+                return Some(Impact::Entity {
+                    projectile: id,
+                    target: hit.element.id,
+                    owner: projectile.owner,
+                    position: pose.position,
+                    damage: projectile.kind.damage(projectile.velocity),
+                    knockback: Vec2::new(movement.x, movement.z).normalize_or_zero() * KNOCKBACK,
+                    kind: projectile.kind,
+                });
+            }
+
+            pose.move_by(offset);
+
+            if hit_block {
+                if !projectile.kind.sticks() {
+                    return Some(Impact::Despawn { projectile: id });
+                }
+
+                projectile.velocity = Vec3::ZERO;
+                projectile.stuck_since = Some(tick);
+
+                let pkt = play::EntityVelocityUpdateS2c {
+                    entity_id: entity_id(id),
+                    velocity: velocity(Vec3::ZERO),
+                };
+
+                broadcast.append(&pkt, &compose).unwrap();
+
+                return None;
+            }
+
+            projectile.velocity *= DRAG;
+            projectile.velocity.y -= projectile.kind.gravity();
+
+            // clients move projectiles on their own between position syncs, so they need to slow
+            // down and fall the same way
+            let pkt = play::EntityVelocityUpdateS2c {
+                entity_id: entity_id(id),
+                velocity: velocity(projectile.velocity),
+            };
+
+            broadcast.append(&pkt, &compose).unwrap();
+
+            None
+        })
+        .collect();
+
+    for impact in impacts {
+        let projectile = match impact {
+            Impact::Entity {
+                projectile,
+                target,
+                owner,
+                position,
+                damage,
+                knockback,
+                kind,
+            } => {
+                s.send(event::AttackEntity {
+                    target,
+                    from_pos: position,
+                    from: owner,
+                    damage,
+                    knockback,
+                    source: AttackType::Projectile { kind, projectile },
+                });
+
+                projectile
+            }
+            Impact::Despawn { projectile } => projectile,
+        };
+
+        let broadcast = entities
+            .get(projectile)
+            .ok()
+            .and_then(|instance| instances.get(&instance.0));
+
+        if let Some((_, broadcast)) = broadcast {
+            let pkt = play::EntitiesDestroyS2c {
+                entity_ids: Cow::Borrowed(&[entity_id(projectile)]),
+            };
+
+            broadcast.append(&pkt, &compose).unwrap();
+        }
+
+        s.send(Despawn(projectile));
+    }
+}
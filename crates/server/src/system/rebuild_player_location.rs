@@ -1,23 +1,28 @@
-use bvh_region::TrivialHeuristic;
+use bvh_region::BroadPhase;
 use evenio::{
     entity::EntityId,
     event::Receiver,
     fetch::{Fetcher, Single},
     query::{Query, With},
 };
+use fxhash::FxHashMap;
 use tracing::instrument;
 
 use crate::{
-    components::{FullEntityPose, Player},
+    components::{instance::InInstance, AiTargetable, FullEntityPose},
     event::Gametick,
-    singleton::player_aabb_lookup::{LookupData, PlayerBoundingBoxes},
+    singleton::{
+        bounding_box::BoundingBoxTable,
+        player_aabb_lookup::{LookupData, PlayerBoundingBoxes},
+    },
 };
 
 #[derive(Query, Debug)]
 pub(crate) struct EntityQuery<'a> {
     id: EntityId,
     pose: &'a FullEntityPose,
-    _player: With<&'static Player>,
+    instance: &'a InInstance,
+    _targetable: With<&'static AiTargetable>,
 }
 
 #[instrument(skip_all, level = "trace")]
@@ -26,15 +31,43 @@ pub fn rebuild_player_location(
     mut lookup: Single<&mut PlayerBoundingBoxes>,
     entities: Fetcher<EntityQuery>,
 ) {
-    let elements: Vec<_> = entities
-        .iter()
-        .map(|query| LookupData {
-            id: query.id,
-            aabb: query.pose.bounding,
-        })
-        .collect();
+    let mut table = BoundingBoxTable::default();
+    let mut by_instance: FxHashMap<_, Vec<_>> = FxHashMap::default();
 
-    let bvh = bvh_region::Bvh::build::<TrivialHeuristic>(elements);
+    for query in &entities {
+        let (id, instance, aabb) = (query.id, query.instance.0, query.pose.bounding);
 
-    lookup.query = bvh;
+        table.insert(id, instance, aabb);
+        by_instance
+            .entry(instance)
+            .or_default()
+            .push(LookupData { id, aabb });
+    }
+
+    let instances = &mut lookup.instances;
+
+    instances.retain(|instance, _| by_instance.contains_key(instance));
+
+    for (instance, elements) in by_instance {
+        let query = instances.entry(instance).or_default();
+
+        // rebuilding is only needed when players join, leave, change instance or stop being
+        // targetable
+        let same_players = query.elements().len() == elements.len()
+            && query
+                .elements()
+                .iter()
+                .all(|data| table.get(data.id, instance).is_some());
+
+        if same_players {
+            query.update(|data| {
+                if let Some(aabb) = table.get(data.id, instance) {
+                    data.aabb = aabb;
+                }
+            });
+            continue;
+        }
+
+        query.rebuild(elements);
+    }
 }
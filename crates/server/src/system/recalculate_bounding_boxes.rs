@@ -1,22 +1,26 @@
-use bvh_region::TrivialHeuristic;
+use bvh_region::BroadPhase;
 use evenio::{
     entity::EntityId,
     event::Receiver,
     fetch::{Fetcher, Single},
-    query::Query,
+    query::{Not, Query},
 };
+use fxhash::FxHashMap;
 use tracing::{instrument, span};
 
 use crate::{
-    components::FullEntityPose,
+    components::{instance::InInstance, projectile::Projectile, FullEntityPose},
     event::Gametick,
-    singleton::bounding_box::{EntityBoundingBoxes, Stored},
+    singleton::bounding_box::{BoundingBoxTable, EntityBoundingBoxes, Stored},
 };
 
 #[derive(Query, Debug)]
 pub struct EntityQuery<'a> {
     id: EntityId,
     pose: &'a FullEntityPose,
+    instance: &'a InInstance,
+    // projectiles look for what they hit themselves and cannot be hit
+    _projectile: Not<&'static Projectile>,
 }
 
 #[instrument(skip_all, level = "trace")]
@@ -27,22 +31,49 @@ pub fn recalculate_bounding_boxes(
 ) {
     let entity_bounding_boxes = entity_bounding_boxes.0;
 
-    span!(tracing::Level::TRACE, "clear-bounding-boxes").in_scope(|| {
-        entity_bounding_boxes.clear();
-    });
+    let (table, by_instance) = span!(tracing::Level::TRACE, "entities-to-table").in_scope(|| {
+        let mut table = BoundingBoxTable::default();
+        let mut by_instance: FxHashMap<_, Vec<_>> = FxHashMap::default();
+
+        for query in &entities {
+            let (id, instance, aabb) = (query.id, query.instance.0, query.pose.bounding);
 
-    // todo: make par iterator
-    let stored: Vec<_> = span!(tracing::Level::TRACE, "entities-to-vec").in_scope(|| {
-        entities
-            .iter()
-            .map(|query| Stored {
-                aabb: query.pose.bounding,
-                id: query.id,
-            })
-            .collect()
+            table.insert(id, instance, aabb);
+            by_instance
+                .entry(instance)
+                .or_default()
+                .push(Stored { aabb, id });
+        }
+
+        (table, by_instance)
     });
 
-    let bvh = bvh_region::Bvh::build::<TrivialHeuristic>(stored);
+    let instances = &mut entity_bounding_boxes.instances;
+
+    instances.retain(|instance, _| by_instance.contains_key(instance));
+
+    for (instance, stored) in by_instance {
+        let query = instances.entry(instance).or_default();
+
+        // most ticks no entity spawns or despawns, so the broad-phase only has to follow the
+        // movement
+        let same_entities = query.elements().len() == stored.len()
+            && query
+                .elements()
+                .iter()
+                .all(|stored| table.get(stored.id, instance).is_some());
+
+        if same_entities {
+            span!(tracing::Level::TRACE, "update").in_scope(|| {
+                query.update(|stored| {
+                    if let Some(aabb) = table.get(stored.id, instance) {
+                        stored.aabb = aabb;
+                    }
+                });
+            });
+            continue;
+        }
 
-    entity_bounding_boxes.query = bvh;
+        query.rebuild(stored);
+    }
 }
@@ -1,4 +1,5 @@
 use evenio::event::{ReceiverMut, Sender};
+use glam::Vec2;
 use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
 use tracing::instrument;
 
@@ -22,6 +23,7 @@ pub fn shoved_reaction(mut r: ReceiverMut<BulkShoved>, mut s: Sender<event::Atta
                 from: event.from,
                 // todo: determine damage
                 damage: 3.0,
+                knockback: Vec2::ZERO,
                 source: AttackType::Shove,
             }
         })
@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+
+use evenio::prelude::*;
+use fxhash::FxHashSet;
+use glam::I16Vec2;
+use tracing::instrument;
+
+use crate::{
+    components::{
+        chunks::{Chunks, SolidSnapshot},
+        instance::InInstance,
+        FullEntityPose, Player,
+    },
+    config::CONFIG,
+    event::Gametick,
+};
+
+/// Takes the solid blocks around every entity of each instance. Entities move less than a column
+/// per tick, so the neighboring columns are enough, except around players, whose flow fields
+/// reach further.
+#[instrument(skip_all, level = "trace")]
+pub fn snapshot_solids(
+    _: Receiver<Gametick>,
+    entities: Fetcher<(&FullEntityPose, &InInstance, Option<&Player>)>,
+    mut instances: Fetcher<(EntityId, &Chunks, &mut SolidSnapshot)>,
+) {
+    let navigation_radius = (CONFIG.navigation_radius >> 4) as i16 + 1;
+
+    let mut by_instance: HashMap<EntityId, FxHashSet<I16Vec2>> = HashMap::new();
+
+    for (pose, instance, player) in &entities {
+        let columns = by_instance.entry(instance.0).or_default();
+        let center = pose.chunk_pos();
+
+        let radius = if player.is_some() {
+            navigation_radius
+        } else {
+            1
+        };
+
+        for dz in -radius..=radius {
+            for dx in -radius..=radius {
+                columns.insert(center + I16Vec2::new(dx, dz));
+            }
+        }
+    }
+
+    for (id, chunks, snapshot) in &mut instances {
+        let columns = by_instance.remove(&id).unwrap_or_default();
+        *snapshot = SolidSnapshot::new(chunks, columns);
+    }
+}
@@ -7,17 +7,19 @@ use valence_protocol::{
 };
 
 use crate::{
+    components::{chunks::Chunks, instance::InstanceBroadcast},
     event::Stats,
     global::Global,
-    net::{Broadcast, Compose},
+    net::Compose,
 };
 
+/// Shows the tick time, the player count and the chunk cache of the instance as boss bars.
 #[instrument(skip_all, level = "trace")]
 pub fn stats_message(
     r: ReceiverMut<Stats>,
-    broadcast: Single<&Broadcast>,
     compose: Compose,
     global: Single<&Global>,
+    instances: Fetcher<(&Chunks, &InstanceBroadcast)>,
 ) {
     let event = r.event;
 
@@ -34,7 +36,7 @@ pub fn stats_message(
     };
 
     // boss bar
-    let pkt = valence_protocol::packets::play::BossBarS2c {
+    let tick_pkt = valence_protocol::packets::play::BossBarS2c {
         id: Uuid::from_u128(0),
         action: BossBarAction::Add {
             title,
@@ -45,8 +47,6 @@ pub fn stats_message(
         },
     };
 
-    broadcast.append(&pkt, &compose).unwrap();
-
     let player_count = global
         .shared
         .player_count
@@ -56,7 +56,7 @@ pub fn stats_message(
     let title = title.into_cow_text();
     let health = (player_count as f32 / 10_000.0).min(1.0);
 
-    let pkt = valence_protocol::packets::play::BossBarS2c {
+    let players_pkt = valence_protocol::packets::play::BossBarS2c {
         id: Uuid::from_u128(1),
         action: BossBarAction::Add {
             title,
@@ -67,5 +67,39 @@ pub fn stats_message(
         },
     };
 
-    broadcast.append(&pkt, &compose).unwrap();
+    for (chunks, broadcast) in instances {
+        broadcast.append(&tick_pkt, &compose).unwrap();
+        broadcast.append(&players_pkt, &compose).unwrap();
+
+        let cache = chunks.cache_stats();
+
+        let lookups = cache.hits + cache.misses;
+        let hit_rate = if lookups == 0 {
+            1.0
+        } else {
+            cache.hits as f32 / lookups as f32
+        };
+
+        let title = format!(
+            "chunk cache {:.1}% hit, {} chunks, {} MiB, {} evictions",
+            hit_rate * 100.0,
+            cache.entries,
+            cache.bytes / (1024 * 1024),
+            cache.evictions
+        );
+        let title = title.into_cow_text();
+
+        let pkt = valence_protocol::packets::play::BossBarS2c {
+            id: Uuid::from_u128(2),
+            action: BossBarAction::Add {
+                title,
+                health: hit_rate,
+                color: BossBarColor::Green,
+                division: BossBarDivision::NoDivision,
+                flags: BossBarFlags::default(),
+            },
+        };
+
+        broadcast.append(&pkt, &compose).unwrap();
+    }
 }
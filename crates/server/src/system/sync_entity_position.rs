@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use evenio::prelude::*;
 use glam::{Vec2, Vec3};
 use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
@@ -5,7 +7,10 @@ use tracing::instrument;
 use valence_protocol::{packets::play, ByteAngle, VarInt};
 
 use crate::{
-    components::{FullEntityPose, Uuid},
+    components::{
+        instance::{InInstance, InstanceBroadcast},
+        FullEntityPose, Uuid,
+    },
     event::Gametick,
     net::{Broadcast, Compose},
     singleton::broadcast::{PacketMetadata, PacketNecessity},
@@ -15,6 +20,7 @@ use crate::{
 pub(crate) struct EntityQuery<'a> {
     id: EntityId,
     uuid: &'a Uuid,
+    instance: &'a InInstance,
 
     pose: &'a mut FullEntityPose,
     last_pose: &'a mut PositionSyncMetadata,
@@ -31,16 +37,25 @@ pub struct PositionSyncMetadata {
 pub fn sync_entity_position(
     _: Receiver<Gametick>,
     mut entities: Fetcher<EntityQuery>,
-    broadcast: Single<&Broadcast>,
+    instances: Fetcher<(EntityId, &InstanceBroadcast)>,
     compose: Compose,
 ) {
+    let instances: HashMap<_, _> = instances.iter().collect();
+
     entities.par_iter_mut().for_each(|query| {
         let EntityQuery {
             id,
             uuid,
+            instance,
             pose,
             last_pose: sync_meta,
         } = query;
+
+        // only players in the same instance can see the entity
+        let Some(broadcast) = instances.get(&instance.0) else {
+            return;
+        };
+
         let pos = pose.position;
         let pitch = ByteAngle::from_degrees(pose.pitch);
         let yaw = ByteAngle::from_degrees(pose.yaw);
@@ -111,7 +126,7 @@ pub fn sync_entity_position(
             exclude_player: Some(uuid.0),
         };
 
-        movement.write_packets(id, &broadcast, metadata, &compose);
+        movement.write_packets(id, broadcast, metadata, &compose);
 
         if let EntityMovement::Teleport { .. } = movement {
             sync_meta.rounding_error = Vec3::ZERO;
@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use evenio::prelude::*;
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+use tracing::instrument;
+use valence_protocol::{packets::play, RawBytes, VarInt};
+use valence_server::entity::EntityKind;
+
+use crate::{
+    components::{
+        instance::{InInstance, InstanceBroadcast},
+        metadata::Metadata,
+        Display,
+    },
+    event::Gametick,
+    net::Compose,
+    tracker::Prev,
+};
+
+#[derive(Query)]
+pub(crate) struct MetadataQuery<'a> {
+    id: EntityId,
+    instance: &'a InInstance,
+    display: Option<&'a Display>,
+    metadata: &'a Metadata,
+    previous: &'a mut Prev<Metadata>,
+}
+
+/// Sends every field of [`Metadata`] which changed this tick in one packet per entity.
+#[instrument(skip_all, level = "trace")]
+pub fn sync_metadata(
+    _: Receiver<Gametick>,
+    mut entities: Fetcher<MetadataQuery>,
+    instances: Fetcher<(EntityId, &InstanceBroadcast)>,
+    compose: Compose,
+) {
+    let instances: HashMap<_, _> = instances.iter().collect();
+
+    entities.par_iter_mut().for_each(|query| {
+        let MetadataQuery {
+            id,
+            instance,
+            display,
+            metadata,
+            previous,
+        } = query;
+
+        if *metadata == **previous {
+            return;
+        }
+
+        let Some(broadcast) = instances.get(&instance.0) else {
+            return;
+        };
+
+        // players only have a display if they are disguised
+        let kind = display.map_or(EntityKind::PLAYER, |display| display.0);
+
+        let mut bytes = Vec::new();
+
+        if metadata.encode_changes(previous, kind, &mut bytes) {
+            let pkt = play::EntityTrackerUpdateS2c {
+                entity_id: VarInt(id.index().0 as i32),
+                tracked_values: RawBytes(&bytes),
+            };
+
+            broadcast.append(&pkt, &compose).unwrap();
+        }
+
+        **previous = metadata.clone();
+    });
+}
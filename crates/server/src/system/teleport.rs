@@ -6,6 +6,7 @@ use valence_protocol::{
 };
 
 use crate::{
+    components::FullEntityPose,
     event,
     net::{Compose, Packets},
 };
@@ -13,6 +14,7 @@ use crate::{
 #[derive(Query)]
 pub struct TeleportQuery<'a> {
     packets: &'a mut Packets,
+    pose: &'a mut FullEntityPose,
 }
 
 #[instrument(skip_all)]
@@ -24,6 +26,10 @@ pub fn teleport(r: Receiver<event::Teleport, TeleportQuery>, compose: Compose) {
     let event = r.event;
     let query = r.query;
 
+    // move the player on the server right away so chunks around the destination are sent (and
+    // chunks around the origin unloaded) without waiting for the client to confirm
+    query.pose.move_to(event.position);
+
     // PlayerPositionLookS2CPacket
 
     let teleport_id = fastrand::i32(..);
@@ -2,13 +2,11 @@ use evenio::prelude::*;
 use tracing::instrument;
 
 use crate::{
-    event::Gametick,
-    global::Global,
-    net::{Broadcast, Compose},
+    components::instance::InstanceBroadcast, event::Gametick, global::Global, net::Compose,
 };
 
 #[instrument(skip_all, level = "trace")]
-pub fn send_time(_: Receiver<Gametick>, broadcast: Single<&Broadcast>, compose: Compose) {
+pub fn send_time(_: Receiver<Gametick>, instances: Fetcher<&InstanceBroadcast>, compose: Compose) {
     let tick = compose.global.tick;
     let time_of_day = tick % 24000;
 
@@ -19,7 +17,9 @@ pub fn send_time(_: Receiver<Gametick>, broadcast: Single<&Broadcast>, compose:
             time_of_day,
         };
 
-        broadcast.append(&pkt, &compose).unwrap();
+        for broadcast in instances {
+            broadcast.append(&pkt, &compose).unwrap();
+        }
     }
 }
 
@@ -0,0 +1,120 @@
+use evenio::prelude::*;
+use tracing::{debug, instrument, warn};
+
+use crate::{
+    components::{
+        chunks::{SolidBlocks, SolidSnapshot},
+        hit_validation::{AttackRate, HitValidation, Violation, Violations},
+        instance::InInstance,
+        metadata::Metadata,
+        FullEntityPose, InGameName, Vitals,
+    },
+    event::{AttackEntity, AttackType, KickPlayer},
+    global::Global,
+    system::chunks::ChunkChanges,
+};
+
+#[derive(Query)]
+pub struct TargetQuery<'a> {
+    id: EntityId,
+    pose: Option<&'a FullEntityPose>,
+    vitals: Option<&'a Vitals>,
+    instance: Option<&'a InInstance>,
+    metadata: Option<&'a Metadata>,
+}
+
+#[derive(Query)]
+pub struct AttackerQuery<'a> {
+    pose: &'a FullEntityPose,
+    instance: &'a InInstance,
+    rate: &'a mut AttackRate,
+    violations: &'a mut Violations,
+    name: &'a InGameName,
+    chunk_changes: &'a ChunkChanges,
+}
+
+/// Drops melee attacks which the attacker could not have made, before they deal any damage or
+/// reset the attack cooldown.
+#[instrument(skip_all, level = "trace")]
+pub fn validate_attack(
+    attack: ReceiverMut<AttackEntity, TargetQuery>,
+    mut attackers: Fetcher<AttackerQuery>,
+    instances: Fetcher<&SolidSnapshot>,
+    validation: Single<&HitValidation>,
+    global: Single<&Global>,
+    mut s: Sender<KickPlayer>,
+) {
+    // projectiles and shoves are caused by the server
+    if !matches!(attack.event.source, AttackType::Melee) {
+        return;
+    }
+
+    let from = attack.event.from;
+
+    let Ok(attacker) = attackers.get_mut(from) else {
+        return;
+    };
+
+    let target = &attack.query;
+    let tick = global.tick;
+
+    let result = validation
+        .check_rate(attacker.rate, tick)
+        .and_then(|()| {
+            let valid = target.id != from
+                && target.instance == Some(attacker.instance)
+                && matches!(target.vitals, Some(Vitals::Alive { .. }));
+
+            match target.pose {
+                Some(pose) if valid => Ok(pose),
+                _ => Err(Violation::InvalidTarget),
+            }
+        })
+        .and_then(|pose| {
+            let invisible = target
+                .metadata
+                .is_some_and(|metadata| metadata.flags.invisible());
+
+            if invisible || !attacker.chunk_changes.is_loaded(pose.chunk_pos()) {
+                return Err(Violation::Hidden);
+            }
+
+            Ok(pose)
+        })
+        .and_then(|pose| {
+            let Ok(snapshot) = instances.get(attacker.instance.0) else {
+                return Ok(());
+            };
+
+            let mut solids = SolidBlocks::new(snapshot);
+
+            validation.check_reach(attacker.pose.eye_position(), &pose.bounding, |block| {
+                solids.is_solid(block)
+            })
+        });
+
+    let Err(violation) = result else {
+        return;
+    };
+
+    EventMut::take(attack.event);
+
+    let score = attacker
+        .violations
+        .add(violation, tick, validation.forgiveness);
+
+    // single violations happen to honest players as well, so only kicks are worth a warning
+    debug!(
+        "{} {violation}, violation score is now {score:.2}",
+        attacker.name
+    );
+
+    if validation.kicks(score) {
+        warn!("{} was kicked for invalid attacks", attacker.name);
+
+        s.send(KickPlayer {
+            target: from,
+            reason: "invalid attacks".into(),
+        });
+    }
+}
@@ -0,0 +1,229 @@
+//! Spawns NPCs for the players who have the chunk they are in loaded, and destroys them again
+//! once they leave the view of the player.
+//!
+//! Only spawning and destroying is done per player. Movement and metadata are still broadcast to
+//! the whole instance; clients ignore packets about entities they have not been sent.
+//!
+//! Players are only looked at again for NPCs which crossed into another chunk, and for chunks
+//! which they loaded or unloaded since the last tick.
+
+use std::borrow::Cow;
+
+use evenio::prelude::*;
+use fxhash::{FxHashMap, FxHashSet};
+use glam::I16Vec2;
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+use tracing::instrument;
+use valence_protocol::{packets::play, VarInt};
+
+use crate::{
+    components::{instance::InInstance, metadata::Metadata, Display, FullEntityPose, Npc, Uuid},
+    event::Gametick,
+    net::{Compose, Packets},
+    singleton::npc_chunks::{Location, NpcChunks},
+    system::{chunks::ChunkChanges, init_entity::spawn_entity_packet},
+};
+
+/// The chunks a player has been sent the NPCs of.
+#[derive(Component, Default, Debug)]
+pub struct VisibleEntities {
+    chunks: FxHashSet<I16Vec2>,
+    /// The [`ChunkChanges::revision`] `chunks` was copied at.
+    revision: Option<u64>,
+}
+
+#[derive(Query)]
+pub(crate) struct NpcQuery<'a> {
+    id: EntityId,
+    uuid: &'a Uuid,
+    pose: &'a FullEntityPose,
+    display: &'a Display,
+    instance: &'a InInstance,
+    metadata: &'a Metadata,
+    _npc: With<&'static Npc>,
+}
+
+/// An NPC which crossed into another chunk of an instance, or into or out of the instance.
+#[derive(Copy, Clone, Debug)]
+struct Move {
+    id: EntityId,
+    from: Option<I16Vec2>,
+    to: Option<I16Vec2>,
+}
+
+fn record(
+    moves: &mut FxHashMap<EntityId, Vec<Move>>,
+    id: EntityId,
+    from: Option<Location>,
+    to: Option<Location>,
+) {
+    match (from, to) {
+        (Some((old, from)), Some((new, to))) if old == new => {
+            moves.entry(new).or_default().push(Move {
+                id,
+                from: Some(from),
+                to: Some(to),
+            });
+        }
+        _ => {
+            if let Some((old, from)) = from {
+                moves.entry(old).or_default().push(Move {
+                    id,
+                    from: Some(from),
+                    to: None,
+                });
+            }
+
+            if let Some((new, to)) = to {
+                moves.entry(new).or_default().push(Move {
+                    id,
+                    from: None,
+                    to: Some(to),
+                });
+            }
+        }
+    }
+}
+
+/// The NPCs to spawn and destroy for one player.
+#[derive(Default)]
+struct Changes {
+    spawned: FxHashSet<EntityId>,
+    destroyed: Vec<VarInt>,
+}
+
+impl Changes {
+    fn spawn(&mut self, id: EntityId) {
+        self.spawned.insert(id);
+    }
+
+    fn destroy(&mut self, id: EntityId) {
+        // an NPC spawned in the same tick has not been sent yet
+        if !self.spawned.remove(&id) {
+            self.destroyed.push(VarInt(id.index().0 as i32));
+        }
+    }
+}
+
+#[instrument(skip_all, level = "trace")]
+pub fn update_visible_entities(
+    _: Receiver<Gametick>,
+    npcs: Fetcher<NpcQuery>,
+    mut players: Fetcher<(
+        EntityId,
+        &mut Packets,
+        &ChunkChanges,
+        &InInstance,
+        &mut VisibleEntities,
+    )>,
+    mut npc_chunks: Single<&mut NpcChunks>,
+    compose: Compose,
+) {
+    let mut moves: FxHashMap<EntityId, Vec<Move>> = FxHashMap::default();
+    let mut count = 0;
+
+    for npc in &npcs {
+        count += 1;
+
+        let to = (npc.instance.0, npc.pose.chunk_pos());
+
+        if npc_chunks.location(npc.id) != Some(to) {
+            let from = npc_chunks.relocate(npc.id, Some(to));
+            record(&mut moves, npc.id, from, Some(to));
+        }
+    }
+
+    // despawned NPCs are not fetched anymore, so they are only left in the lookup
+    if npc_chunks.len() > count {
+        let despawned: Vec<_> = npc_chunks
+            .ids()
+            .filter(|&id| npcs.get(id).is_err())
+            .collect();
+
+        for id in despawned {
+            let from = npc_chunks.relocate(id, None);
+            record(&mut moves, id, from, None);
+        }
+    }
+
+    let npc_chunks = &**npc_chunks;
+
+    let spawns: Vec<_> = players
+        .par_iter_mut()
+        .filter_map(|(id, packets, chunk_changes, instance, visible)| {
+            let mut changes = Changes::default();
+
+            if let Some(moves) = moves.get(&instance.0) {
+                let is_visible =
+                    |pos: Option<I16Vec2>| pos.is_some_and(|pos| visible.chunks.contains(&pos));
+
+                for npc in moves {
+                    match (is_visible(npc.from), is_visible(npc.to)) {
+                        (false, true) => changes.spawn(npc.id),
+                        (true, false) => changes.destroy(npc.id),
+                        _ => {}
+                    }
+                }
+            }
+
+            let revision = chunk_changes.revision();
+
+            if visible.revision != Some(revision) {
+                for &pos in &visible.chunks {
+                    if !chunk_changes.is_loaded(pos) {
+                        for npc in npc_chunks.in_chunk((instance.0, pos)) {
+                            changes.destroy(npc);
+                        }
+                    }
+                }
+
+                for pos in chunk_changes.loaded() {
+                    if !visible.chunks.contains(&pos) {
+                        for npc in npc_chunks.in_chunk((instance.0, pos)) {
+                            changes.spawn(npc);
+                        }
+                    }
+                }
+
+                visible.chunks.clear();
+                visible.chunks.extend(chunk_changes.loaded());
+                visible.revision = Some(revision);
+            }
+
+            if !changes.destroyed.is_empty() {
+                let pkt = play::EntitiesDestroyS2c {
+                    entity_ids: Cow::Owned(changes.destroyed),
+                };
+                packets.append(&pkt, &compose).unwrap();
+            }
+
+            (!changes.spawned.is_empty()).then_some((id, changes.spawned))
+        })
+        .collect();
+
+    // spawning is rare enough to not need the NPCs to be shared between threads
+    for (id, spawned) in spawns {
+        let Ok((_, packets, ..)) = players.get_mut(id) else {
+            continue;
+        };
+
+        for npc in spawned {
+            let Ok(npc) = npcs.get(npc) else {
+                continue;
+            };
+
+            let pkt = spawn_entity_packet(npc.id, npc.display.0, *npc.uuid, npc.pose);
+            packets.append(&pkt, &compose).unwrap();
+
+            let mut bytes = Vec::new();
+            let entity_id = VarInt(npc.id.index().0 as i32);
+
+            if let Some(pkt) = npc
+                .metadata
+                .initial_packet(entity_id, npc.display.0, &mut bytes)
+            {
+                packets.append(&pkt, &compose).unwrap();
+            }
+        }
+    }
+}
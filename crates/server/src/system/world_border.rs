@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use evenio::prelude::*;
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+use tracing::instrument;
+use valence_protocol::{
+    packets::{play, play::player_position_look_s2c::PlayerPositionLookFlags},
+    VarInt,
+};
+
+use crate::{
+    components::{
+        instance::{InInstance, InstanceBroadcast},
+        world_border::{BorderAction, WorldBorder},
+        FullEntityPose, ImmuneStatus, Vitals,
+    },
+    event,
+    event::Gametick,
+    global::Global,
+    net::{Compose, Packets},
+};
+
+#[derive(Query)]
+pub(crate) struct EnforceQuery<'a> {
+    instance: &'a InInstance,
+    pose: &'a mut FullEntityPose,
+    vitals: &'a mut Vitals,
+    immunity: &'a mut ImmuneStatus,
+    packets: Option<&'a mut Packets>,
+}
+
+/// Changes the world border of an instance and shows the change to its players.
+#[instrument(skip_all)]
+pub fn set(
+    r: Receiver<event::SetWorldBorder, (&mut WorldBorder, &InstanceBroadcast)>,
+    compose: Compose,
+) {
+    let event = r.event;
+    let (border, broadcast) = r.query;
+
+    let tick = compose.global.tick;
+    let old_diameter = border.diameter(tick);
+
+    border.interpolate(tick, event.diameter, event.duration_ticks);
+
+    if event.duration_ticks <= 0 {
+        let pkt = play::WorldBorderSizeChangedS2c {
+            diameter: event.diameter,
+        };
+        broadcast.append(&pkt, &compose).unwrap();
+    } else {
+        let pkt = play::WorldBorderInterpolateSizeS2c {
+            old_diameter,
+            new_diameter: event.diameter,
+            duration_millis: (event.duration_ticks * 50).into(),
+        };
+        broadcast.append(&pkt, &compose).unwrap();
+    }
+}
+
+/// Applies the [`BorderAction`] of each instance to the players and NPCs outside its border.
+#[instrument(skip_all, level = "trace")]
+pub fn enforce(
+    _: Receiver<Gametick>,
+    instances: Fetcher<(EntityId, &WorldBorder)>,
+    mut entities: Fetcher<EnforceQuery>,
+    global: Single<&Global>,
+    compose: Compose,
+) {
+    let tick = global.tick;
+
+    let borders: HashMap<_, _> = instances
+        .iter()
+        .filter(|(_, border)| border.action != BorderAction::None)
+        .collect();
+
+    entities.par_iter_mut().for_each(|query| {
+        let EnforceQuery {
+            instance,
+            pose,
+            vitals,
+            immunity,
+            packets,
+        } = query;
+
+        let Some(border) = borders.get(&instance.0) else {
+            return;
+        };
+
+        let outside = border.distance_outside(tick, pose.position);
+
+        if outside <= 0.0 {
+            return;
+        }
+
+        let destination = match border.action {
+            BorderAction::None => return,
+            BorderAction::Push => border.clamp(tick, pose.position),
+            BorderAction::Teleport => {
+                let mut destination = pose.position;
+                destination.x = border.center.x as f32;
+                destination.z = border.center.y as f32;
+                destination
+            }
+            BorderAction::Damage => {
+                // like vanilla, the damage grows with the distance beyond the safe zone
+                let beyond = outside - border.safe_zone;
+
+                if beyond > 0.0 && border.damage_per_block > 0.0 {
+                    let damage = (beyond as f32 * border.damage_per_block).floor().max(1.0);
+                    vitals.hurt(&global, damage, immunity);
+                }
+
+                return;
+            }
+        };
+
+        pose.move_to(destination);
+
+        // the client has to be told or its next movement packet moves the player back
+        if let Some(packets) = packets {
+            let pkt = play::PlayerPositionLookS2c {
+                position: destination.as_dvec3(),
+                yaw: pose.yaw,
+                pitch: pose.pitch,
+                flags: PlayerPositionLookFlags::default(),
+                teleport_id: VarInt(fastrand::i32(..)),
+            };
+
+            packets.append(&pkt, &compose).unwrap();
+        }
+    });
+}
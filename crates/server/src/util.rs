@@ -1,2 +1,3 @@
 pub mod mojang;
 pub mod player_skin;
+pub mod sign;
@@ -0,0 +1,60 @@
+//! Block entity data for signs.
+
+use valence_nbt::{compound, Compound, List, Value};
+
+/// Creates the block entity data of a sign showing `lines` on its front and nothing on its back.
+#[must_use]
+pub fn sign(lines: [&str; 4]) -> Compound {
+    let messages = lines
+        .iter()
+        .map(|line| serde_json::json!({ "text": line }).to_string())
+        .collect();
+
+    compound! {
+        "front_text" => side(messages),
+        "back_text" => side(vec![r#"{"text":""}"#.to_owned(); 4]),
+        "is_waxed" => true,
+    }
+}
+
+fn side(messages: Vec<String>) -> Value {
+    Value::Compound(compound! {
+        "messages" => List::String(messages),
+        "color" => "black",
+        "has_glowing_text" => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(data: &Compound, side: &str) -> Vec<String> {
+        let Some(Value::Compound(side)) = data.get(side) else {
+            panic!("missing {side}");
+        };
+
+        assert_eq!(side.get("color"), Some(&Value::from("black")));
+        assert_eq!(side.get("has_glowing_text"), Some(&Value::from(false)));
+
+        let Some(Value::List(List::String(messages))) = side.get("messages") else {
+            panic!("missing messages");
+        };
+
+        messages.clone()
+    }
+
+    #[test]
+    fn lines_are_json_text() {
+        let data = sign(["first", "", r#"a "quote""#, "last"]);
+
+        assert_eq!(messages(&data, "front_text"), [
+            r#"{"text":"first"}"#,
+            r#"{"text":""}"#,
+            r#"{"text":"a \"quote\""}"#,
+            r#"{"text":"last"}"#,
+        ]);
+        assert_eq!(messages(&data, "back_text"), [r#"{"text":""}"#; 4]);
+        assert_eq!(data.get("is_waxed"), Some(&Value::from(true)));
+    }
+}
@@ -1,5 +1,4 @@
-use evenio::entity::EntityId;
-use server::evenio::component::Component;
+use server::{evenio::component::Component, singleton::bounding_box::Stored};
 
 #[derive(Component, Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Team {
@@ -9,7 +8,7 @@ pub enum Team {
 
 #[derive(Component, Default)]
 pub struct HumanLocations {
-    pub bvh: bvh::Bvh<EntityId>,
+    pub bvh: bvh_region::Bvh<Stored>,
 }
 
 #[derive(Component)]
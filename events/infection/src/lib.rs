@@ -36,7 +36,7 @@ pub fn init_game(address: impl ToSocketAddrs + Send + Sync + 'static) -> anyhow:
         // commands
         world.add_handler(system::zombie_command);
 
-        world.add_handler(system::calculate_chunk_level_bvh);
+        world.add_handler(system::calculate_human_bvh);
         world.add_handler(system::point_close_player);
 
         world.add_handler(system::to_zombie);
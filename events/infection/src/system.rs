@@ -3,23 +3,23 @@
     reason = "this is used in the event loop"
 )]
 
-use bvh::{Data, Point};
+use bvh_region::TrivialHeuristic;
 use evenio::{
     event::{EventMut, Insert, Remove},
     fetch::{Fetcher, Single},
     query::{Query, With},
     rayon,
 };
-use glam::I16Vec2;
 use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
 use server::{
-    components::{ChunkLocation, FullEntityPose, Vitals, PLAYER_SPAWN_POSITION},
+    components::{FullEntityPose, Vitals, PLAYER_SPAWN_POSITION},
     evenio::{
         entity::EntityId,
         event::{Receiver, ReceiverMut, Sender},
     },
     event,
     event::{BulkShoved, Gametick, Shoved},
+    singleton::bounding_box::Stored,
     util::player_skin::PlayerSkin,
     valence_server::{
         entity::EntityKind,
@@ -63,61 +63,48 @@ pub fn scramble_player_name(mut r: ReceiverMut<event::PlayerInit, ()>) {
 #[derive(Query)]
 pub struct BvhHuman<'a> {
     id: EntityId,
-    location: &'a ChunkLocation,
+    pose: &'a FullEntityPose,
     _human: With<&'static Human>,
 }
 
-impl<'a> Point for BvhHuman<'a> {
-    fn point(&self) -> I16Vec2 {
-        self.location.0
-    }
-}
-
-impl<'a> Data for BvhHuman<'a> {
-    type Unit = EntityId;
-
-    fn data(&self) -> &[EntityId] {
-        core::slice::from_ref(&self.id)
-    }
-}
+/// Zombies are pointed at one of this many closest humans so they do not all chase the same one.
+const NEARBY_HUMANS: usize = 4;
 
 #[instrument(skip_all)]
-pub fn calculate_chunk_level_bvh(
+pub fn calculate_human_bvh(
     _: Receiver<Gametick>,
     humans: Fetcher<BvhHuman>,
     mut human_locations: Single<&mut HumanLocations>,
 ) {
-    let humans: Vec<_> = humans.iter().collect();
-
-    let len = humans.len();
-    human_locations.bvh = bvh::Bvh::build(humans, len);
+    let humans: Vec<_> = humans
+        .iter()
+        .map(|human| Stored {
+            aabb: human.pose.bounding,
+            id: human.id,
+        })
+        .collect();
+
+    human_locations.bvh = bvh_region::Bvh::build::<TrivialHeuristic>(humans);
 }
 
 #[instrument(skip_all, level = "trace")]
 pub fn point_close_player(
     _: Receiver<Gametick>,
     human_locations: Single<&HumanLocations>,
-    zombies: Fetcher<(&ChunkLocation, EntityId, With<&Zombie>)>,
-    poses: Fetcher<&FullEntityPose>,
+    zombies: Fetcher<(&FullEntityPose, EntityId, With<&Zombie>)>,
     mut s: Sender<event::PointCompass>,
 ) {
-    for (location, id, _) in zombies {
-        let Some(ids) = human_locations.bvh.get_closest_slice(location.0) else {
-            continue;
-        };
+    for (pose, id, _) in zombies {
+        let nearby = human_locations.bvh.k_nearest(pose.position, NEARBY_HUMANS);
 
-        if ids.is_empty() {
+        if nearby.is_empty() {
             continue;
         }
 
-        let random = fastrand::usize(..ids.len());
-        let point_to_id = ids[random];
-
-        let Ok(point_to_pose) = poses.get(point_to_id) else {
-            continue;
-        };
+        let random = fastrand::usize(..nearby.len());
+        let (human, _) = nearby[random];
 
-        let point_to = BlockPos::from(point_to_pose.position.as_dvec3());
+        let point_to = BlockPos::from(human.aabb.mid().as_dvec3());
 
         s.send(event::PointCompass {
             target: id,